@@ -1,10 +1,12 @@
-use std::{error::Error, net::SocketAddr, str::FromStr, sync::Arc};
+use std::{convert::Infallible, error::Error, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
 
 use actix_web::{
-    web::{self, ReqData},
-    App, HttpServer, Responder,
+    web::{self, Bytes, ReqData},
+    App, HttpResponse, HttpServer, Responder,
 };
+use futures::stream;
 use web_auth_rs::{
+    connection_monitor::ConnectionAuthMonitor,
     core::{
         authentication::{AuthenticationServiceBuilder, SuccessAuthenticationResult},
         authorization::AuthorizationPolicyBuilder,
@@ -18,6 +20,18 @@ async fn test_get(auth_result: ReqData<SuccessAuthenticationResult>) -> impl Res
     format!("<pre>hello world:\n{:#?}</pre>", auth_result.principal)
 }
 
+/// Streams events until the token the connection was opened with expires.
+async fn events(auth_result: ReqData<SuccessAuthenticationResult>) -> impl Responder {
+    let ticks = stream::unfold(0u64, |tick| async move {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Some((Ok::<_, Infallible>(Bytes::from(format!("data: {tick}\n\n"))), tick + 1))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .streaming(ConnectionAuthMonitor::new(&auth_result.principal).guard(ticks))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     HttpServer::new(|| {
@@ -28,7 +42,15 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
 
         let jwt_handler = JwtBearerHandler {
             validation_opt: validation,
-            decoding_key: DecodingKey::from_secret("1234567890123456".as_bytes()),
+            keys: DecodingKey::from_secret("1234567890123456".as_bytes()).into(),
+            strict_access_token_profile: false,
+            challenge: Default::default(),
+            expected_claims: Default::default(),
+            claim_aliases: Default::default(),
+            expiry: Default::default(),
+            claim_mapping: Default::default(),
+            required_claims: Default::default(),
+            token_cache: Default::default(),
         };
 
         let auth_service = Arc::new(
@@ -39,14 +61,17 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
                 .unwrap(),
         );
 
-        let authorize = Authorize::new(
-            AuthorizationPolicyBuilder::new()
-                .require_role("test".to_owned())
-                .build(auth_service.clone()),
-        );
+        let policy = AuthorizationPolicyBuilder::new()
+            .require_role("test".to_owned())
+            .build(auth_service.clone());
 
         App::new()
-            .route("/{tail:.*}", web::get().to(test_get).wrap(authorize))
+            .service(
+                web::resource("/events")
+                    .wrap(Authorize::new(policy.clone()))
+                    .route(web::get().to(events)),
+            )
+            .route("/{tail:.*}", web::get().to(test_get).wrap(Authorize::new(policy)))
             .wrap(Authentication(auth_service))
     })
     .bind(&SocketAddr::from_str("0.0.0.0:8000")?)?
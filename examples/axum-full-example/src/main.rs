@@ -1,7 +1,16 @@
-use std::{net::SocketAddr, str::FromStr, sync::Arc};
+use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
 
-use axum::{response::Html, routing::get, BoxError, Extension, Router};
+use axum::{
+    response::{
+        sse::{Event, Sse},
+        Html,
+    },
+    routing::get,
+    BoxError, Extension, Router,
+};
+use futures::{stream, Stream};
 use web_auth_rs::{
+    connection_monitor::ConnectionAuthMonitor,
     core::{
         authentication::{AuthenticationServiceBuilder, SuccessAuthenticationResult},
         authorization::AuthorizationPolicyBuilder,
@@ -15,6 +24,18 @@ async fn test_get(Extension(auth_result): Extension<SuccessAuthenticationResult>
     Html(format!("<pre>hello world:\n{:#?}</pre>", auth_result.principal))
 }
 
+/// Streams events until the token the connection was opened with expires.
+async fn events(
+    Extension(auth_result): Extension<SuccessAuthenticationResult>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let ticks = stream::unfold(0u64, |tick| async move {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Some((Ok(Event::default().data(tick.to_string())), tick + 1))
+    });
+
+    Sse::new(ConnectionAuthMonitor::new(&auth_result.principal).guard(ticks))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), BoxError> {
     let mut validation = Validation::default();
@@ -24,7 +45,15 @@ async fn main() -> Result<(), BoxError> {
 
     let jwt_handler = JwtBearerHandler {
         validation_opt: validation,
-        decoding_key: DecodingKey::from_secret("1234567890123456".as_bytes()),
+        keys: DecodingKey::from_secret("1234567890123456".as_bytes()).into(),
+        strict_access_token_profile: false,
+        challenge: Default::default(),
+        expected_claims: Default::default(),
+        claim_aliases: Default::default(),
+        expiry: Default::default(),
+        claim_mapping: Default::default(),
+        required_claims: Default::default(),
+        token_cache: Default::default(),
     };
 
     let auth_service = Arc::new(
@@ -42,6 +71,7 @@ async fn main() -> Result<(), BoxError> {
     );
 
     let router = Router::new()
+        .route("/events", get(events).layer(authorize_layer.clone()))
         .route("/*rest", get(test_get).layer(authorize_layer))
         .layer(AuthenticationLayer { service: auth_service });
 
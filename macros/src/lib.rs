@@ -0,0 +1,73 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, spanned::Spanned, FnArg, ItemFn, LitStr};
+
+/// Requires the user to satisfy a policy (`#[authorize(policy = "AdminOnly")]`) or just to be authenticated
+/// (`#[authorize]`) before the handler runs.
+#[proc_macro_attribute]
+pub fn authorize(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut policy = None::<LitStr>;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("policy") {
+            policy = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `policy = \"...\"`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let access = match policy {
+        Some(policy) => quote!(::web_auth_rs::core::authorization_map::RouteAccess::Policy(#policy.to_owned())),
+        None => quote!(::web_auth_rs::core::authorization_map::RouteAccess::Authenticated),
+    };
+    endpoint(access, parse_macro_input!(item as ItemFn))
+}
+
+/// Marks the handler as reachable without authentication.
+#[proc_macro_attribute]
+pub fn allow_anonymous(args: TokenStream, item: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return syn::Error::new(Span::call_site(), "#[allow_anonymous] doesn't take arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    endpoint(
+        quote!(::web_auth_rs::core::authorization_map::RouteAccess::Anonymous),
+        parse_macro_input!(item as ItemFn),
+    )
+}
+
+/// Emits a metadata type next to the handler and adds an extractor for it as the first argument, so the
+/// check runs before any other extractor touches the request.
+fn endpoint(access: proc_macro2::TokenStream, mut function: ItemFn) -> TokenStream {
+    if let Some(receiver @ FnArg::Receiver(_)) = function.sig.inputs.first() {
+        return syn::Error::new(receiver.span(), "endpoint attributes only support free functions")
+            .to_compile_error()
+            .into();
+    }
+
+    let vis = function.vis.clone();
+    let metadata = format_ident!("__{}_endpoint", function.sig.ident);
+    function.sig.inputs.insert(
+        0,
+        parse_quote!(_: ::web_auth_rs::core::endpoint::EndpointAuthorized<#metadata>),
+    );
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #vis struct #metadata;
+
+        impl ::web_auth_rs::core::endpoint::EndpointMetadata for #metadata {
+            fn access() -> ::web_auth_rs::core::authorization_map::RouteAccess {
+                #access
+            }
+        }
+
+        #function
+    }
+    .into()
+}
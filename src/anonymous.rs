@@ -0,0 +1,144 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::{ready, Ready},
+    time::Duration,
+};
+
+use http::{HeaderMap, HeaderValue, StatusCode};
+
+use crate::core::{
+    authentication::{AuthenticationHandler, AuthenticationResult},
+    authorization::{AuthorizationFailure, AuthorizationHandlerContext, AuthorizationRequirement},
+    http::{get_cookie, AuthResponse, Request},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, Claims, UserPrincipal},
+};
+
+pub mod claim_names {
+    pub const ANONYMOUS: &str = "anonymous";
+}
+
+const MAX_ID_LENGTH: usize = 64;
+
+pub struct AnonymousIdCookie {
+    pub name: String,
+    pub max_age: Duration,
+}
+
+pub struct AnonymousHandler {
+    pub claims: Claims,
+    pub id_cookie: Option<AnonymousIdCookie>,
+}
+
+impl AnonymousHandler {
+    pub fn new() -> Self {
+        Self {
+            claims: HashMap::from([(
+                claim_types::SUBJECT.into(),
+                ClaimValue::PlainValue(ClaimPlainValue::String("anonymous".into())),
+            )]),
+            id_cookie: None,
+        }
+    }
+
+    pub fn id_cookie_header(&self, id: &str) -> Option<HeaderValue> {
+        let cookie = self.id_cookie.as_ref()?;
+        if !is_valid_id(id) {
+            return None;
+        }
+
+        HeaderValue::try_from(format!(
+            "{}={id}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            cookie.name,
+            cookie.max_age.as_secs()
+        ))
+        .ok()
+    }
+}
+
+impl Default for AnonymousHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthenticationHandler for AnonymousHandler {
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let mut claims = self.claims.clone();
+        claims.insert(
+            claim_names::ANONYMOUS.into(),
+            ClaimValue::PlainValue(ClaimPlainValue::Bool(true)),
+        );
+
+        let id = self
+            .id_cookie
+            .as_ref()
+            .and_then(|cookie| get_cookie(request, &cookie.name))
+            .filter(|id| is_valid_id(id));
+        if let Some(id) = id {
+            claims.insert(
+                claim_types::SUBJECT.into(),
+                ClaimValue::PlainValue(ClaimPlainValue::String(id.into())),
+            );
+        }
+
+        ready(Ok(UserPrincipal { claims }))
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+
+    fn supports_challenge(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone)]
+pub struct DenyAnonymousRequirement;
+
+impl AuthorizationRequirement for DenyAnonymousRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("DenyAnonymous")
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        if !is_anonymous(context.principal()) {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+pub fn is_anonymous(principal: &UserPrincipal) -> bool {
+    principal
+        .claim(claim_names::ANONYMOUS)
+        .is_some_and(|c| c.iter().any(|v| v.as_bool() == Some(true)))
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LENGTH
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
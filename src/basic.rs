@@ -0,0 +1,84 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    credentials::CredentialValidator,
+    http::{AuthResponse, Request},
+};
+
+pub struct BasicAuthHandler {
+    pub realm: String,
+    pub validator: Arc<dyn CredentialValidator>,
+}
+
+impl AuthenticationHandler for BasicAuthHandler {
+    type AuthFut = Pin<Box<dyn Future<Output = AuthenticationResult> + Send>>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let credentials = request
+            .get_header(&AUTHORIZATION)
+            .and_then(|h| h.to_str().ok()?.strip_prefix("Basic "))
+            .map(parse_credentials);
+
+        let (username, password) = match credentials {
+            Some(Ok(credentials)) => credentials,
+            Some(Err(err)) => return Box::pin(ready(Err(AuthenticationError::fail(err)))),
+            None => return Box::pin(ready(Err(AuthenticationError::NoResult))),
+        };
+
+        let validator = self.validator.clone();
+        Box::pin(async move {
+            match validator.validate(&username, &password).await {
+                Ok(Some(principal)) => Ok(principal),
+                Ok(None) => Err(AuthenticationError::Fail(AuthError::InvalidCredentials(
+                    "Invalid username or password".to_owned(),
+                ))),
+                Err(err) => Err(AuthenticationError::fail(err)),
+            }
+        })
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        let header_value = HeaderValue::try_from(format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm))
+            .unwrap_or_else(|_| HeaderValue::from_static("Basic"));
+
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, header_value)]),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn parse_credentials(encoded: &str) -> Result<(String, String), anyhow::Error> {
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim())?)?;
+    let (username, password) = decoded
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Basic credentials don't contain a ':' separator"))?;
+
+    Ok((username.to_owned(), password.to_owned()))
+}
@@ -0,0 +1,87 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{stream::TakeUntil, Stream, StreamExt};
+use tokio::time::{Instant, Sleep};
+
+use crate::core::{
+    authentication::SuccessAuthenticationResult,
+    http::{Request, RequestExtensions},
+    principal::{ClaimPlainValue, UserPrincipal},
+};
+
+/// Watches the credentials a long-lived response, such as server-sent events or a streamed download, was
+/// authorized with, so the connection can be ended once they expire instead of outliving them.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionAuthMonitor {
+    expires_at: Option<SystemTime>,
+}
+
+impl ConnectionAuthMonitor {
+    /// Uses the `exp` claim of the principal. Principals without one never expire.
+    pub fn new(principal: &UserPrincipal) -> Self {
+        let exp = principal.claim("exp").and_then(|exp| match exp.iter().next()? {
+            ClaimPlainValue::Int(exp) => u64::try_from(*exp).ok(),
+            ClaimPlainValue::Float(exp) => Some(*exp as u64),
+            _ => None,
+        });
+
+        Self::with_expiry(exp.map(|exp| UNIX_EPOCH + Duration::from_secs(exp)))
+    }
+
+    pub fn with_expiry(expires_at: Option<SystemTime>) -> Self {
+        Self { expires_at }
+    }
+
+    /// Monitors the authenticated principal of `request`, if any.
+    pub fn from_request(request: &impl Request) -> Option<Self> {
+        request
+            .get_extensions()
+            .get::<SuccessAuthenticationResult>()
+            .map(|result| Self::new(&result.principal))
+    }
+
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+
+    /// Completes once the credentials expire, never when they don't.
+    pub fn expired(&self) -> ConnectionExpired {
+        let sleep = self.expires_at.map(|expires_at| {
+            let remaining = expires_at.duration_since(SystemTime::now()).unwrap_or_default();
+            Box::pin(tokio::time::sleep_until(Instant::now() + remaining))
+        });
+
+        ConnectionExpired { sleep }
+    }
+
+    /// Ends `stream` once the credentials expire.
+    pub fn guard<St: Stream>(&self, stream: St) -> TakeUntil<St, ConnectionExpired> {
+        stream.take_until(self.expired())
+    }
+}
+
+/// Completes when the credentials of a [`ConnectionAuthMonitor`] expire.
+pub struct ConnectionExpired {
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Future for ConnectionExpired {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.sleep {
+            Some(sleep) => sleep.as_mut().poll(cx),
+            None => Poll::Pending,
+        }
+    }
+}
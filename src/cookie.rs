@@ -0,0 +1,368 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode};
+
+use crate::core::{
+    authentication::{
+        AuthError, AuthenticationError, AuthenticationHandler, AuthenticationProperties, AuthenticationResult,
+        IssuedCookie, SignInOutAuthenticationHandler, SignInResult,
+    },
+    authorization::AuthorizationFailure,
+    http::{get_cookie, AuthResponse, Request, RequestExtensions},
+    principal::{claim_types, UserPrincipal},
+    redirect::ReturnUrlValidator,
+    response_headers::PendingResponseHeaders,
+    session::{Session, SessionPolicy, SessionStore},
+};
+
+pub mod claim_names {
+    pub const SECURITY_STAMP: &str = "security_stamp";
+}
+
+#[derive(Debug, Clone)]
+pub enum PrincipalValidation {
+    Valid,
+    Replace(UserPrincipal),
+    Reject,
+}
+
+#[async_trait]
+pub trait PrincipalValidator: Send + Sync + 'static {
+    async fn validate(&self, session: &Session) -> Result<PrincipalValidation, anyhow::Error>;
+}
+
+#[async_trait]
+pub trait SecurityStampStore: Send + Sync + 'static {
+    async fn security_stamp(&self, subject: &str) -> Result<Option<String>, anyhow::Error>;
+}
+
+pub struct SecurityStampValidator {
+    pub store: Arc<dyn SecurityStampStore>,
+    pub persistent_only: bool,
+}
+
+#[async_trait]
+impl PrincipalValidator for SecurityStampValidator {
+    async fn validate(&self, session: &Session) -> Result<PrincipalValidation, anyhow::Error> {
+        if self.persistent_only && !session.is_persistent {
+            return Ok(PrincipalValidation::Valid);
+        }
+
+        let session_stamp = session
+            .principal
+            .claim(claim_names::SECURITY_STAMP)
+            .and_then(|c| c.iter().find_map(|v| v.as_str()));
+        let current_stamp = self.store.security_stamp(&session.subject).await?;
+
+        if current_stamp.is_some() && current_stamp.as_deref() == session_stamp {
+            Ok(PrincipalValidation::Valid)
+        } else {
+            Ok(PrincipalValidation::Reject)
+        }
+    }
+}
+
+pub struct CookieAuthHandler {
+    pub cookie_name: String,
+    pub secure: bool,
+    pub login_path: Option<String>,
+    pub return_url: ReturnUrlValidator,
+    pub session_lifetime: Duration,
+    pub persistent_lifetime: Duration,
+    pub session_store: Arc<dyn SessionStore>,
+    pub session_policy: SessionPolicy,
+    pub principal_validator: Option<Arc<dyn PrincipalValidator>>,
+    pub validation_interval: Duration,
+    /// Extends a session expiring within this window by its full lifetime, refreshing the cookie of persistent
+    /// sessions on the response. Needs the authentication layer to add the cookie.
+    pub renewal_window: Option<Duration>,
+}
+
+impl CookieAuthHandler {
+    pub fn new(session_store: Arc<dyn SessionStore>) -> Self {
+        Self {
+            cookie_name: ".auth".to_owned(),
+            secure: true,
+            login_path: None,
+            return_url: ReturnUrlValidator::new(),
+            session_lifetime: Duration::from_secs(60 * 60),
+            persistent_lifetime: Duration::from_secs(14 * 24 * 60 * 60),
+            session_store,
+            session_policy: SessionPolicy::default(),
+            principal_validator: None,
+            validation_interval: Duration::from_secs(30 * 60),
+            renewal_window: None,
+        }
+    }
+
+    fn cookie_header(&self, value: &str, max_age: Option<Duration>) -> Option<HeaderValue> {
+        let mut cookie = format!("{}={value}; Path=/; HttpOnly; SameSite=Lax", self.cookie_name);
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        if let Some(max_age) = max_age {
+            cookie.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+
+        HeaderValue::try_from(cookie).ok()
+    }
+}
+
+impl AuthenticationHandler for CookieAuthHandler {
+    type AuthFut = Pin<Box<dyn Future<Output = AuthenticationResult> + Send>>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let Some(session_id) = get_cookie(request, &self.cookie_name).map(str::to_owned) else {
+            return Box::pin(ready(Err(AuthenticationError::NoResult)));
+        };
+
+        let session_store = self.session_store.clone();
+        let principal_validator = self.principal_validator.clone();
+        let validation_interval = self.validation_interval;
+        // Without the layer's headers a renewed persistent session would outlive its cookie, so it isn't renewed.
+        let renewal = self
+            .renewal_window
+            .zip(request.get_extensions().get::<PendingResponseHeaders>().cloned());
+        let (session_lifetime, persistent_lifetime) = (self.session_lifetime, self.persistent_lifetime);
+        let renewed_cookie = self.cookie_header(&session_id, Some(self.persistent_lifetime));
+        Box::pin(async move {
+            let mut session = session_store
+                .load(&session_id)
+                .await
+                .map_err(AuthenticationError::fail)?
+                .ok_or_else(|| {
+                    AuthenticationError::Fail(AuthError::InvalidToken("Session is not found or expired".to_owned()))
+                })?;
+
+            let now = SystemTime::now();
+            let validation_due = session
+                .validated_at
+                .checked_add(validation_interval)
+                .is_none_or(|due_at| due_at <= now);
+            if let Some(validator) = principal_validator.filter(|_| validation_due) {
+                match validator.validate(&session).await.map_err(AuthenticationError::fail)? {
+                    PrincipalValidation::Valid => {}
+                    PrincipalValidation::Replace(principal) => session.principal = principal,
+                    PrincipalValidation::Reject => {
+                        session_store
+                            .remove(&session_id)
+                            .await
+                            .map_err(AuthenticationError::fail)?;
+                        return Err(AuthenticationError::Fail(AuthError::InvalidToken(
+                            "Session is no longer valid".to_owned(),
+                        )));
+                    }
+                }
+
+                session.validated_at = now;
+                session_store
+                    .store(session.clone())
+                    .await
+                    .map_err(AuthenticationError::fail)?;
+            }
+
+            if let Some((renewal_window, response_headers)) = renewal {
+                let renewal_due = session
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= now + renewal_window);
+                if renewal_due {
+                    let lifetime = if session.is_persistent {
+                        persistent_lifetime
+                    } else {
+                        session_lifetime
+                    };
+                    session.expires_at = Some(now + lifetime);
+                    session_store
+                        .store(session.clone())
+                        .await
+                        .map_err(AuthenticationError::fail)?;
+                    if let Some(cookie) = renewed_cookie.filter(|_| session.is_persistent) {
+                        response_headers.append(SET_COOKIE, cookie);
+                    }
+                }
+            }
+
+            Ok(session.principal)
+        })
+    }
+
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut {
+        ready(match &self.login_path {
+            Some(login_path) => self.return_url.login_redirect(login_path, request),
+            None => AuthResponse {
+                status_code: StatusCode::UNAUTHORIZED,
+                headers: HeaderMap::default(),
+                body: Vec::new(),
+            },
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+impl SignInOutAuthenticationHandler for CookieAuthHandler {
+    type SignInFut = Pin<Box<dyn Future<Output = SignInResult> + Send>>;
+
+    type SignOutFut = Pin<Box<dyn Future<Output = AuthResponse> + Send>>;
+
+    fn sign_in(&self, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut {
+        let now = SystemTime::now();
+        let lifetime = if properties.is_persistent {
+            self.persistent_lifetime
+        } else {
+            self.session_lifetime
+        };
+        let expires_at = properties.expires_at.unwrap_or(now + lifetime);
+        let max_age = properties
+            .is_persistent
+            .then(|| expires_at.duration_since(now).unwrap_or_default());
+
+        let session_id = match generate_session_id() {
+            Ok(session_id) => session_id,
+            Err(_) => return Box::pin(ready(internal_error().into())),
+        };
+        let Some(cookie) = self.cookie_header(&session_id, max_age) else {
+            return Box::pin(ready(internal_error().into()));
+        };
+
+        let issued_cookie = IssuedCookie {
+            name: self.cookie_name.clone(),
+            value: session_id.clone(),
+        };
+        let session = Session {
+            id: session_id,
+            subject: user
+                .claim(claim_types::SUBJECT)
+                .and_then(|c| c.iter().find_map(|v| v.as_str()))
+                .unwrap_or_default()
+                .to_owned(),
+            provider_session_id: None,
+            principal: user.clone(),
+            created_at: now,
+            validated_at: now,
+            expires_at: Some(expires_at),
+            is_persistent: properties.is_persistent,
+        };
+
+        let session_store = self.session_store.clone();
+        let session_policy = self.session_policy;
+        Box::pin(async move {
+            match session_policy.enforce(&*session_store, &session.subject).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return AuthResponse {
+                        status_code: StatusCode::FORBIDDEN,
+                        headers: HeaderMap::default(),
+                        body: Vec::new(),
+                    }
+                    .into()
+                }
+                Err(_) => return internal_error().into(),
+            }
+
+            match session_store.store(session).await {
+                Ok(()) => SignInResult {
+                    response: AuthResponse {
+                        status_code: StatusCode::OK,
+                        headers: HeaderMap::from_iter([(SET_COOKIE, cookie)]),
+                        body: Vec::new(),
+                    },
+                    token: None,
+                    cookies: vec![issued_cookie],
+                    expires_at: Some(expires_at),
+                },
+                Err(_) => internal_error().into(),
+            }
+        })
+    }
+
+    fn sign_out(&self, request: &impl Request) -> Self::SignOutFut {
+        let session_id = get_cookie(request, &self.cookie_name).map(str::to_owned);
+        let headers = self
+            .cookie_header("", Some(Duration::ZERO))
+            .map(|cookie| HeaderMap::from_iter([(SET_COOKIE, cookie)]))
+            .unwrap_or_default();
+
+        let session_store = self.session_store.clone();
+        Box::pin(async move {
+            if let Some(session_id) = session_id {
+                if session_store.remove(&session_id).await.is_err() {
+                    return internal_error();
+                }
+            }
+
+            AuthResponse {
+                status_code: StatusCode::OK,
+                headers,
+                body: Vec::new(),
+            }
+        })
+    }
+}
+
+fn generate_session_id() -> Result<String, getrandom::Error> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+fn internal_error() -> AuthResponse {
+    AuthResponse {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        headers: HeaderMap::default(),
+        body: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+    use crate::core::{session::InMemorySessionStore, testing::TestRequest};
+
+    #[tokio::test]
+    async fn sign_out_removes_session_from_store() {
+        let store = Arc::new(InMemorySessionStore::new());
+        let now = SystemTime::now();
+        store
+            .store(Session {
+                id: "session-1".to_owned(),
+                subject: "alice".to_owned(),
+                provider_session_id: None,
+                principal: UserPrincipal {
+                    claims: Default::default(),
+                },
+                created_at: now,
+                validated_at: now,
+                expires_at: None,
+                is_persistent: false,
+            })
+            .await
+            .unwrap();
+        let handler = CookieAuthHandler::new(store.clone());
+
+        let request = TestRequest::new(Method::POST, "/logout", &[("cookie", ".auth=session-1")]);
+        let response = handler.sign_out(&request).await;
+
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert!(response.headers.get(SET_COOKIE).is_some());
+        assert!(store.load("session-1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use pin_project::pin_project;
+
+use super::{
+    authentication::SuccessAuthenticationResult,
+    authorization::{AuthorizationError, AuthorizationFailure, AuthorizationService},
+    http::{Request, RequestExtensions},
+    principal::UserPrincipal,
+};
+
+/// Suggested message header name for a protected [`AuthContext`].
+pub const AUTH_CONTEXT_HEADER: &str = "x-auth-context";
+
+#[cfg(all(feature = "data-protection", feature = "serde"))]
+const PROTECTION_PURPOSE: &str = "auth-context";
+
+thread_local! {
+    static CURRENT: RefCell<Option<AuthContext>> = const { RefCell::new(None) };
+}
+
+/// The principal of a request, detached from it so that work outliving the request, such as spawned tasks
+/// or queued messages, runs on its behalf.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthContext {
+    pub principal: UserPrincipal,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl AuthContext {
+    pub fn new(principal: UserPrincipal) -> Self {
+        Self {
+            principal,
+            expires_at: None,
+        }
+    }
+
+    /// The authenticated principal of `request`, if any.
+    pub fn capture(request: &impl Request) -> Option<Self> {
+        request
+            .get_extensions()
+            .get::<SuccessAuthenticationResult>()
+            .map(|result| Self::new(result.principal.clone()))
+    }
+
+    /// Limits how long the context can be re-established, e.g. from a message that sat in a queue.
+    pub fn with_lifetime(self, lifetime: Duration) -> Self {
+        Self {
+            expires_at: Some(SystemTime::now() + lifetime),
+            ..self
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+
+    /// The context established by the innermost [`Self::scope`] or [`Self::sync_scope`] being run.
+    pub fn current() -> Option<Self> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Runs `fut` with this context as [`Self::current`], on whatever task or thread polls it.
+    pub fn scope<Fut: Future>(self, fut: Fut) -> AuthContextScope<Fut> {
+        AuthContextScope {
+            fut,
+            context: Some(self),
+        }
+    }
+
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        let _guard = CurrentGuard::enter(self);
+        f()
+    }
+
+    pub async fn authorize(
+        &self,
+        authorization_service: &AuthorizationService,
+        policy: &str,
+    ) -> Result<(), AuthorizationError> {
+        if self.is_expired() {
+            return Err(AuthorizationFailure::default().into());
+        }
+
+        authorization_service.authorize(&self.principal, policy).await
+    }
+
+    /// A signed value for a message header, see [`AUTH_CONTEXT_HEADER`].
+    #[cfg(all(feature = "data-protection", feature = "serde"))]
+    pub fn protect(&self, protector: &dyn crate::data_protection::DataProtector) -> Result<String, anyhow::Error> {
+        Ok(protector.protect(PROTECTION_PURPOSE, &serde_json::to_vec(self)?))
+    }
+
+    #[cfg(all(feature = "data-protection", feature = "serde"))]
+    pub fn unprotect(
+        protector: &dyn crate::data_protection::DataProtector,
+        protected: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let context = serde_json::from_slice::<Self>(&protector.unprotect(PROTECTION_PURPOSE, protected)?)?;
+        if context.is_expired() {
+            anyhow::bail!("Auth context has expired");
+        }
+
+        Ok(context)
+    }
+}
+
+/// Sets the current context and restores the previous one when dropped, also on panics.
+struct CurrentGuard {
+    previous: Option<Option<AuthContext>>,
+}
+
+impl CurrentGuard {
+    fn enter(context: AuthContext) -> Self {
+        Self {
+            previous: Some(CURRENT.with(|current| current.replace(Some(context)))),
+        }
+    }
+
+    /// Restores the previous context, returning the one set by [`Self::enter`].
+    fn exit(mut self) -> Option<AuthContext> {
+        let previous = self.previous.take().flatten();
+        CURRENT.with(|current| current.replace(previous))
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.with(|current| current.replace(previous));
+        }
+    }
+}
+
+#[pin_project]
+pub struct AuthContextScope<Fut> {
+    #[pin]
+    fut: Fut,
+    context: Option<AuthContext>,
+}
+
+impl<Fut: Future> Future for AuthContextScope<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let Some(context) = this.context.take() else {
+            return this.fut.poll(cx);
+        };
+
+        let guard = CurrentGuard::enter(context);
+        let result = this.fut.poll(cx);
+        *this.context = guard.exit();
+
+        result
+    }
+}
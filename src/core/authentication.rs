@@ -1,23 +1,429 @@
-use std::future::{ready, Future, Ready};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Display,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
 
-use futures::future::OptionFuture;
+use async_trait::async_trait;
+use futures::future::{join_all, OptionFuture};
+use http::{HeaderMap, HeaderName};
+use pin_project::pin_project;
 
 use super::{
+    authorization::AuthorizationFailure,
+    circuit_breaker::CircuitOpen,
+    cors::ChallengeCors,
+    dyn_handler::DynCompoundAuthenticationHandler,
     futures::{select_seq_ok, select_seq_some, SelectSeqOk, SelectSeqSome},
-    http::{AuthResponse, Request, RequestExtensions},
+    http::{AuthResponse, Request, RequestExtensions, ResponseHead},
+    maintenance::MaintenanceGate,
     principal::UserPrincipal,
+    problem_details::ProblemDetails,
+    request_id::RequestId,
+    response_headers::{PendingResponseHeaders, SuccessResponseHeaders},
+    scheme_filter::SchemeFilter,
+    subject_validation::{SubjectDenied, SubjectValidation, SubjectValidator},
 };
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct SchemeName(Arc<str>);
+
+impl SchemeName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for SchemeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for SchemeName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for SchemeName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SchemeName {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<&str> for SchemeName {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl PartialEq<str> for SchemeName {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SchemeName {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<SchemeName> for &str {
+    fn eq(&self, other: &SchemeName) -> bool {
+        *self == &*other.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemeInfo {
+    pub name: SchemeName,
+    pub supports_challenge: bool,
+    pub supports_sign_in: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthenticationProperties {
+    pub is_persistent: bool,
+    pub expires_at: Option<SystemTime>,
+}
+
+/// A cookie set by a sign-in, see [`SignInResult`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuedCookie {
+    pub name: String,
+    pub value: String,
+}
+
+/// The outcome of a sign-in: the response the handler would send, along with what it issued, so login endpoints
+/// can build their own bodies, e.g. `{"access_token": ..., "expires_in": ...}`.
+#[derive(Debug)]
+pub struct SignInResult {
+    pub response: AuthResponse,
+    /// A token for the user to present, for handlers issuing one.
+    pub token: Option<String>,
+    pub cookies: Vec<IssuedCookie>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl SignInResult {
+    /// The time left until the issued artifacts expire, zero when they already have.
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| expires_at.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+}
+
+impl From<AuthResponse> for SignInResult {
+    fn from(response: AuthResponse) -> Self {
+        Self {
+            response,
+            token: None,
+            cookies: Vec::new(),
+            expires_at: None,
+        }
+    }
+}
+
+/// Why a scheme rejected the credentials it found, so callers can tell e.g. expired tokens from an identity
+/// provider being unreachable.
+#[derive(Debug)]
+pub enum AuthError {
+    /// Credentials are malformed or not acceptable for a reason without its own variant.
+    InvalidToken(String),
+    Expired,
+    WrongAudience,
+    InvalidSignature,
+    /// Well-formed credentials that don't match, e.g. a wrong password.
+    InvalidCredentials(String),
+    /// A dependency needed to validate the credentials couldn't be reached.
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl AuthError {
+    /// Looks into transport and handler-specific errors.
+    pub fn downcast_ref<T: std::error::Error + 'static>(&self) -> Option<&T> {
+        match self {
+            AuthError::Transport(error) | AuthError::Other(error) => error.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::InvalidToken(reason) => write!(f, "Invalid token: {reason}"),
+            AuthError::Expired => write!(f, "Credentials have expired"),
+            AuthError::WrongAudience => write!(f, "Credentials were issued for another audience"),
+            AuthError::InvalidSignature => write!(f, "Signature doesn't match"),
+            AuthError::InvalidCredentials(reason) => write!(f, "Invalid credentials: {reason}"),
+            AuthError::Transport(error) => write!(f, "Transport error: {error}"),
+            AuthError::Other(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Transport(error) | AuthError::Other(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for AuthError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<AuthError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        #[cfg(feature = "jwt")]
+        let error = match error.downcast::<jsonwebtoken::errors::Error>() {
+            Ok(error) => return error.into(),
+            Err(error) => error,
+        };
+        #[cfg(feature = "reqwest")]
+        let error = match error.downcast::<reqwest::Error>() {
+            Ok(error) => return AuthError::Transport(Box::new(error)),
+            Err(error) => error,
+        };
+        match error.downcast::<CircuitOpen>() {
+            Ok(error) => AuthError::Transport(Box::new(error)),
+            Err(error) => AuthError::Other(error.into()),
+        }
+    }
+}
+
+#[cfg(feature = "jwt")]
+impl From<jsonwebtoken::errors::Error> for AuthError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        use jsonwebtoken::errors::ErrorKind;
+
+        match error.kind() {
+            ErrorKind::ExpiredSignature => AuthError::Expired,
+            ErrorKind::InvalidAudience => AuthError::WrongAudience,
+            ErrorKind::InvalidSignature => AuthError::InvalidSignature,
+            _ => AuthError::InvalidToken(error.to_string()),
+        }
+    }
+}
+
 pub enum AuthenticationError {
     NoResult,
-    Fail(anyhow::Error),
+    Fail(AuthError),
+}
+
+impl AuthenticationError {
+    pub fn fail(error: impl Into<AuthError>) -> Self {
+        AuthenticationError::Fail(error.into())
+    }
 }
 
 pub type AuthenticationResult = Result<UserPrincipal, AuthenticationError>;
 
+/// Tells whether the request went through authentication: pending while lazy authentication hasn't run yet,
+/// absent when no authentication layer saw the request.
+struct AuthenticationState {
+    completed: bool,
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SuccessAuthenticationResult {
     pub principal: UserPrincipal,
+    pub actor: Option<UserPrincipal>,
+    /// The scheme that authenticated the request.
+    pub scheme: SchemeName,
+}
+
+/// Inserted into the request extensions when a scheme accepted credentials that have expired, e.g. a JWT
+/// handler set to mark expired tokens. Policies refuse them with
+/// [`require_fresh_credentials`](super::authorization::AuthorizationPolicyBuilder::require_fresh_credentials).
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiredCredential {
+    pub expired_at: SystemTime,
+}
+
+/// The bearer token that authenticated the request, inserted by bearer schemes such as the JWT handler so it can
+/// be forwarded to upstream services.
+#[derive(Clone)]
+pub struct BearerToken(pub Arc<str>);
+
+impl BearerToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BearerToken(..)")
+    }
+}
+
+#[derive(Debug)]
+pub struct SchemeError {
+    pub scheme: SchemeName,
+    pub error: AuthError,
+}
+
+pub type CompoundAuthenticationResult = Result<(SchemeName, UserPrincipal), Vec<SchemeError>>;
+
+#[derive(Debug)]
+pub struct AuthenticationFailure {
+    pub errors: Vec<SchemeError>,
+    pub request_id: Option<RequestId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    #[default]
+    Continue,
+    StopOnFail,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthenticateOptions {
+    pub failure_policy: FailurePolicy,
+    pub parallel: bool,
+}
+
+#[pin_project]
+pub struct WithSchemeErrors<Fut> {
+    /// `None` when the scheme's [`SchemeFilter`] skipped the request.
+    #[pin]
+    fut: OptionFuture<Fut>,
+    scheme: Option<SchemeName>,
+}
+
+impl<Fut> Future for WithSchemeErrors<Fut>
+where
+    Fut: Future<Output = AuthenticationResult>,
+{
+    type Output = CompoundAuthenticationResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures::ready!(this.fut.poll(cx)).unwrap_or(Err(AuthenticationError::NoResult));
+        let scheme = this.scheme.take().expect("Future is polled after completion");
+        Poll::Ready(match result {
+            Ok(principal) => Ok((scheme, principal)),
+            Err(AuthenticationError::NoResult) => Err(Vec::new()),
+            Err(AuthenticationError::Fail(error)) => Err(vec![SchemeError { scheme, error }]),
+        })
+    }
+}
+
+type SubjectValidationFuture = Pin<Box<dyn Future<Output = CompoundAuthenticationResult> + Send>>;
+
+/// Authentication followed by the service's [`SubjectValidator`]. Only the validation is boxed, and only when a
+/// validator is set.
+#[pin_project(project = ValidateSubjectProj)]
+pub enum ValidateSubject<Fut> {
+    Authenticating {
+        #[pin]
+        fut: Fut,
+        validator: Option<Arc<dyn SubjectValidator>>,
+    },
+    Validating(SubjectValidationFuture),
+}
+
+impl<Fut> Future for ValidateSubject<Fut>
+where
+    Fut: Future<Output = CompoundAuthenticationResult>,
+{
+    type Output = CompoundAuthenticationResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                ValidateSubjectProj::Authenticating { fut, validator } => {
+                    let result = futures::ready!(fut.poll(cx));
+                    let (validator, (scheme, principal)) = match (validator.take(), result) {
+                        (Some(validator), Ok(success)) => (validator, success),
+                        (_, result) => return Poll::Ready(result),
+                    };
+
+                    self.set(ValidateSubject::Validating(Box::pin(async move {
+                        let error = match validator.validate(&principal).await {
+                            Ok(SubjectValidation::Allow) => return Ok((scheme, principal)),
+                            Ok(SubjectValidation::Deny) => AuthError::Other(Box::new(SubjectDenied)),
+                            Err(error) => error.into(),
+                        };
+                        Err(vec![SchemeError { scheme, error }])
+                    })));
+                }
+                ValidateSubjectProj::Validating(validation) => return validation.as_mut().poll(cx),
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait ForbidResponseCustomizer: Send + Sync + 'static {
+    async fn customize(
+        &self,
+        scheme: &str,
+        failure: Option<&AuthorizationFailure>,
+        response: AuthResponse,
+    ) -> AuthResponse;
+}
+
+/// A component that failed [`AuthenticationService::initialize`], e.g. a scheme or an [`Initializer`].
+#[derive(Debug)]
+pub struct InitializationFailure {
+    pub component: String,
+    pub error: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub struct InitializationError(pub Vec<InitializationFailure>);
+
+impl Display for InitializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Authentication failed to initialize")?;
+        for (i, failure) in self.0.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{separator}{}: {:#}", failure.component, failure.error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for InitializationError {}
+
+/// Work [`AuthenticationService::initialize`] does before the handlers' configuration is checked, e.g.
+/// fetching signing keys.
+#[async_trait]
+pub trait Initializer: Send + Sync + 'static {
+    fn name(&self) -> String;
+
+    async fn initialize(&self) -> Result<(), anyhow::Error>;
+}
+
+/// Runs on responses that went through the authentication layer, e.g. to add headers for the authenticated
+/// user. `authentication` is only set when the layer itself authenticated the request, so not with lazy
+/// authentication.
+#[async_trait]
+pub trait ResponseHook: Send + Sync + 'static {
+    async fn on_response(&self, authentication: Option<&SuccessAuthenticationResult>, response: &mut ResponseHead);
 }
 
 pub trait AuthenticationHandler: Send + Sync + 'static {
@@ -29,41 +435,58 @@ pub trait AuthenticationHandler: Send + Sync + 'static {
 
     fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut;
 
-    fn challenge(&self) -> Self::ChallengeFut;
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut;
+
+    fn forbid(&self, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut;
 
-    fn forbid(&self) -> Self::ForbidFut;
+    fn supports_challenge(&self) -> bool {
+        true
+    }
+
+    /// Checks the configuration once the [`Initializer`]s ran, see [`AuthenticationService::initialize`].
+    fn validate_configuration(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
 }
 
 pub trait SignInOutAuthenticationHandler: AuthenticationHandler {
-    type SignInFut: Future<Output = AuthResponse>;
+    type SignInFut: Future<Output = SignInResult>;
 
     type SignOutFut: Future<Output = AuthResponse>;
 
-    fn sign_in(&self, user: &UserPrincipal) -> Self::SignInFut;
+    fn sign_in(&self, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut;
 
-    fn sign_out(&self) -> Self::SignOutFut;
+    fn sign_out(&self, request: &impl Request) -> Self::SignOutFut;
 }
 
 pub trait CompoundAuthenticationHandler: Send + Sync + 'static {
-    type AuthFut: Future<Output = AuthenticationResult>;
+    type AuthFut: Future<Output = CompoundAuthenticationResult>;
 
     type ChallengeFut: Future<Output = Option<AuthResponse>>;
 
     type ForbidFut: Future<Output = Option<AuthResponse>>;
 
-    type SignInFut: Future<Output = Option<AuthResponse>>;
+    type SignInFut: Future<Output = Option<SignInResult>>;
 
     type SignOutFut: Future<Output = Option<AuthResponse>>;
 
-    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut;
+    type SchemeAuthFut: Future<Output = Option<AuthenticationResult>>;
+
+    fn authenticate(&self, request: &mut impl Request, options: AuthenticateOptions) -> Self::AuthFut;
+
+    fn authenticate_scheme(&self, scheme: &str, request: &mut impl Request) -> Self::SchemeAuthFut;
+
+    fn challenge(&self, scheme: &str, request: &impl Request) -> Self::ChallengeFut;
 
-    fn challenge(&self, scheme: &str) -> Self::ChallengeFut;
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut;
 
-    fn forbid(&self, scheme: &str) -> Self::ForbidFut;
+    fn sign_in(&self, scheme: &str, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut;
 
-    fn sign_in(&self, scheme: &str, user: &UserPrincipal) -> Self::SignInFut;
+    fn sign_out(&self, scheme: &str, request: &impl Request) -> Self::SignOutFut;
 
-    fn sign_out(&self, scheme: &str) -> Self::SignOutFut;
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>);
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>);
 }
 
 impl<H1, H2> CompoundAuthenticationHandler for (H1, H2)
@@ -71,7 +494,7 @@ where
     H1: CompoundAuthenticationHandler,
     H2: CompoundAuthenticationHandler,
 {
-    type AuthFut = SelectSeqOk<H1::AuthFut, H2::AuthFut>;
+    type AuthFut = SelectSeqOk<H1::AuthFut, H2::AuthFut, (SchemeName, UserPrincipal), SchemeError>;
 
     type ChallengeFut = SelectSeqSome<H1::ChallengeFut, H2::ChallengeFut>;
 
@@ -81,85 +504,168 @@ where
 
     type SignOutFut = SelectSeqSome<H1::SignOutFut, H2::SignOutFut>;
 
-    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
-        select_seq_ok(self.0.authenticate(request), self.1.authenticate(request))
+    type SchemeAuthFut = SelectSeqSome<H1::SchemeAuthFut, H2::SchemeAuthFut>;
+
+    fn authenticate(&self, request: &mut impl Request, options: AuthenticateOptions) -> Self::AuthFut {
+        select_seq_ok(
+            self.0.authenticate(request, options),
+            self.1.authenticate(request, options),
+            options.failure_policy == FailurePolicy::StopOnFail,
+            options.parallel,
+        )
+    }
+
+    fn authenticate_scheme(&self, scheme: &str, request: &mut impl Request) -> Self::SchemeAuthFut {
+        select_seq_some(
+            self.0.authenticate_scheme(scheme, request),
+            self.1.authenticate_scheme(scheme, request),
+        )
+    }
+
+    fn challenge(&self, scheme: &str, request: &impl Request) -> Self::ChallengeFut {
+        select_seq_some(self.0.challenge(scheme, request), self.1.challenge(scheme, request))
+    }
+
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        select_seq_some(self.0.forbid(scheme, failure), self.1.forbid(scheme, failure))
+    }
+
+    fn sign_in(&self, scheme: &str, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut {
+        select_seq_some(
+            self.0.sign_in(scheme, user, properties),
+            self.1.sign_in(scheme, user, properties),
+        )
     }
 
-    fn challenge(&self, scheme: &str) -> Self::ChallengeFut {
-        select_seq_some(self.0.challenge(scheme), self.1.challenge(scheme))
+    fn sign_out(&self, scheme: &str, request: &impl Request) -> Self::SignOutFut {
+        select_seq_some(self.0.sign_out(scheme, request), self.1.sign_out(scheme, request))
     }
 
-    fn forbid(&self, scheme: &str) -> Self::ForbidFut {
-        select_seq_some(self.0.forbid(scheme), self.1.forbid(scheme))
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>) {
+        self.0.collect_schemes(schemes);
+        self.1.collect_schemes(schemes);
+    }
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>) {
+        self.0.validate_configuration(failures);
+        self.1.validate_configuration(failures);
+    }
+}
+
+/// The handlers of a builder, giving access to the one added last.
+pub trait LastScheme {
+    fn set_filter(&mut self, filter: SchemeFilter);
+}
+
+impl<H1, H2: LastScheme> LastScheme for (H1, H2) {
+    fn set_filter(&mut self, filter: SchemeFilter) {
+        self.1.set_filter(filter);
     }
+}
 
-    fn sign_in(&self, scheme: &str, user: &UserPrincipal) -> Self::SignInFut {
-        select_seq_some(self.0.sign_in(scheme, user), self.1.sign_in(scheme, user))
+impl<H: AuthenticationHandler> LastScheme for AuthenticationHandlerWithScheme<H> {
+    fn set_filter(&mut self, filter: SchemeFilter) {
+        self.filter = Some(filter);
     }
+}
 
-    fn sign_out(&self, scheme: &str) -> Self::SignOutFut {
-        select_seq_some(self.0.sign_out(scheme), self.1.sign_out(scheme))
+impl<H: SignInOutAuthenticationHandler> LastScheme for SignInOutAuthenticationHandlerWithScheme<H> {
+    fn set_filter(&mut self, filter: SchemeFilter) {
+        self.filter = Some(filter);
     }
 }
 
 pub struct AuthenticationHandlerWithScheme<Handler: AuthenticationHandler> {
-    pub scheme: String,
+    pub scheme: SchemeName,
     pub handler: Handler,
+    pub filter: Option<SchemeFilter>,
 }
 
 impl<H> CompoundAuthenticationHandler for AuthenticationHandlerWithScheme<H>
 where
     H: AuthenticationHandler,
 {
-    type AuthFut = H::AuthFut;
+    type AuthFut = WithSchemeErrors<H::AuthFut>;
 
     type ChallengeFut = OptionFuture<H::ChallengeFut>;
 
     type ForbidFut = OptionFuture<H::ForbidFut>;
 
-    type SignInFut = Ready<Option<AuthResponse>>;
+    type SignInFut = Ready<Option<SignInResult>>;
 
     type SignOutFut = Ready<Option<AuthResponse>>;
 
-    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
-        self.handler.authenticate(request)
+    type SchemeAuthFut = OptionFuture<H::AuthFut>;
+
+    fn authenticate(&self, request: &mut impl Request, _: AuthenticateOptions) -> Self::AuthFut {
+        let is_included = self.filter.as_ref().is_none_or(|filter| filter.matches(request));
+        WithSchemeErrors {
+            fut: is_included.then(|| self.handler.authenticate(request)).into(),
+            scheme: Some(self.scheme.clone()),
+        }
+    }
+
+    fn authenticate_scheme(&self, scheme: &str, request: &mut impl Request) -> Self::SchemeAuthFut {
+        if scheme == self.scheme {
+            Some(self.handler.authenticate(request)).into()
+        } else {
+            None.into()
+        }
     }
 
-    fn challenge(&self, scheme: &str) -> Self::ChallengeFut {
+    fn challenge(&self, scheme: &str, request: &impl Request) -> Self::ChallengeFut {
         if scheme == self.scheme {
-            Some(self.handler.challenge()).into()
+            Some(self.handler.challenge(request)).into()
         } else {
             None.into()
         }
     }
 
-    fn forbid(&self, scheme: &str) -> Self::ForbidFut {
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut {
         if scheme == self.scheme {
-            Some(self.handler.forbid()).into()
+            Some(self.handler.forbid(failure)).into()
         } else {
             None.into()
         }
     }
 
-    fn sign_in(&self, _: &str, _: &UserPrincipal) -> Self::SignInFut {
+    fn sign_in(&self, _: &str, _: &UserPrincipal, _: &AuthenticationProperties) -> Self::SignInFut {
         ready(None)
     }
 
-    fn sign_out(&self, _: &str) -> Self::SignOutFut {
+    fn sign_out(&self, _: &str, _: &impl Request) -> Self::SignOutFut {
         ready(None)
     }
+
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>) {
+        schemes.push(SchemeInfo {
+            name: self.scheme.clone(),
+            supports_challenge: self.handler.supports_challenge(),
+            supports_sign_in: false,
+        });
+    }
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>) {
+        if let Err(error) = self.handler.validate_configuration() {
+            failures.push(InitializationFailure {
+                component: self.scheme.to_string(),
+                error,
+            });
+        }
+    }
 }
 
 pub struct SignInOutAuthenticationHandlerWithScheme<Handler: SignInOutAuthenticationHandler> {
-    pub scheme: String,
+    pub scheme: SchemeName,
     pub handler: Handler,
+    pub filter: Option<SchemeFilter>,
 }
 
 impl<H> CompoundAuthenticationHandler for SignInOutAuthenticationHandlerWithScheme<H>
 where
     H: SignInOutAuthenticationHandler,
 {
-    type AuthFut = H::AuthFut;
+    type AuthFut = WithSchemeErrors<H::AuthFut>;
 
     type ChallengeFut = OptionFuture<H::ChallengeFut>;
 
@@ -169,41 +675,72 @@ where
 
     type SignOutFut = OptionFuture<H::SignOutFut>;
 
-    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
-        self.handler.authenticate(request)
+    type SchemeAuthFut = OptionFuture<H::AuthFut>;
+
+    fn authenticate(&self, request: &mut impl Request, _: AuthenticateOptions) -> Self::AuthFut {
+        let is_included = self.filter.as_ref().is_none_or(|filter| filter.matches(request));
+        WithSchemeErrors {
+            fut: is_included.then(|| self.handler.authenticate(request)).into(),
+            scheme: Some(self.scheme.clone()),
+        }
     }
 
-    fn challenge(&self, scheme: &str) -> Self::ChallengeFut {
+    fn authenticate_scheme(&self, scheme: &str, request: &mut impl Request) -> Self::SchemeAuthFut {
         if scheme == self.scheme {
-            Some(self.handler.challenge()).into()
+            Some(self.handler.authenticate(request)).into()
         } else {
             None.into()
         }
     }
 
-    fn forbid(&self, scheme: &str) -> Self::ForbidFut {
+    fn challenge(&self, scheme: &str, request: &impl Request) -> Self::ChallengeFut {
         if scheme == self.scheme {
-            Some(self.handler.forbid()).into()
+            Some(self.handler.challenge(request)).into()
         } else {
             None.into()
         }
     }
 
-    fn sign_in(&self, scheme: &str, user: &UserPrincipal) -> Self::SignInFut {
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut {
         if scheme == self.scheme {
-            Some(self.handler.sign_in(user)).into()
+            Some(self.handler.forbid(failure)).into()
         } else {
             None.into()
         }
     }
 
-    fn sign_out(&self, scheme: &str) -> Self::SignOutFut {
+    fn sign_in(&self, scheme: &str, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut {
         if scheme == self.scheme {
-            Some(self.handler.sign_out()).into()
+            Some(self.handler.sign_in(user, properties)).into()
         } else {
             None.into()
         }
     }
+
+    fn sign_out(&self, scheme: &str, request: &impl Request) -> Self::SignOutFut {
+        if scheme == self.scheme {
+            Some(self.handler.sign_out(request)).into()
+        } else {
+            None.into()
+        }
+    }
+
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>) {
+        schemes.push(SchemeInfo {
+            name: self.scheme.clone(),
+            supports_challenge: self.handler.supports_challenge(),
+            supports_sign_in: true,
+        });
+    }
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>) {
+        if let Err(error) = self.handler.validate_configuration() {
+            failures.push(InitializationFailure {
+                component: self.scheme.to_string(),
+                error,
+            });
+        }
+    }
 }
 
 pub struct AuthenticationService<Handler>
@@ -211,91 +748,473 @@ where
     Handler: CompoundAuthenticationHandler,
 {
     handler: Handler,
-    default_scheme: String,
+    default_scheme: SchemeName,
+    schemes: Vec<SchemeInfo>,
+    lazy: bool,
+    forbid_customizer: Option<Arc<dyn ForbidResponseCustomizer>>,
+    options: AuthenticateOptions,
+    response_headers: HashMap<SchemeName, SuccessResponseHeaders>,
+    response_hook: Option<Arc<dyn ResponseHook>>,
+    request_id_header: Option<HeaderName>,
+    subject_validator: Option<Arc<dyn SubjectValidator>>,
+    maintenance_gate: Option<MaintenanceGate>,
+    initializers: Vec<Arc<dyn Initializer>>,
+    problem_details: Option<ProblemDetails>,
+    challenge_cors: Option<ChallengeCors>,
 }
 
 impl<Handler> AuthenticationService<Handler>
 where
     Handler: CompoundAuthenticationHandler,
 {
+    pub fn schemes(&self) -> &[SchemeInfo] {
+        &self.schemes
+    }
+
+    pub fn default_scheme(&self) -> &SchemeName {
+        &self.default_scheme
+    }
+
+    pub fn response_hook(&self) -> Option<&Arc<dyn ResponseHook>> {
+        self.response_hook.as_ref()
+    }
+
+    /// Headers to add to the response of an authenticated request, see
+    /// [`AuthenticationServiceBuilder::set_response_headers`].
+    pub fn success_response_headers(&self, request: &impl Request) -> Option<HeaderMap> {
+        let extensions = request.get_extensions();
+        let result = extensions.get::<SuccessAuthenticationResult>()?;
+        let headers = self.response_headers.get(&result.scheme)?.headers_for(result);
+        (!headers.is_empty()).then_some(headers)
+    }
+
+    /// Runs the [`Initializer`]s concurrently and then checks the configuration of every handler, so problems
+    /// surface at startup instead of on the first request. Every failure is reported.
+    pub async fn initialize(&self) -> Result<(), InitializationError> {
+        let results = join_all(self.initializers.iter().map(|initializer| initializer.initialize())).await;
+        let mut failures = self
+            .initializers
+            .iter()
+            .zip(results)
+            .filter_map(|(initializer, result)| {
+                Some(InitializationFailure {
+                    component: initializer.name(),
+                    error: result.err()?,
+                })
+            })
+            .collect::<Vec<_>>();
+        self.handler.validate_configuration(&mut failures);
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(InitializationError(failures))
+        }
+    }
+
+    pub async fn handle_request(&self, request: &mut impl Request) {
+        if let Some(authentication) = self.start_request(request) {
+            complete_authentication(request, authentication.await);
+        }
+    }
+
+    /// The synchronous part of [`Self::handle_request`]. Returns the authentication to await and pass to
+    /// `complete_authentication`, or `None` when there's nothing to do now.
+    pub(crate) fn start_request(&self, request: &mut impl Request) -> Option<ValidateSubject<Handler::AuthFut>> {
+        if request.get_extensions().get::<AuthenticationState>().is_some() {
+            return None;
+        }
+
+        self.resolve_request_id(request);
+        request
+            .get_extensions_mut()
+            .insert(AuthenticationState { completed: !self.lazy });
+        request.get_extensions_mut().insert(PendingResponseHeaders::default());
+        (!self.lazy).then(|| self.authenticate_validated(request))
+    }
+
+    fn authenticate_validated(&self, request: &mut impl Request) -> ValidateSubject<Handler::AuthFut> {
+        ValidateSubject::Authenticating {
+            fut: self.handler.authenticate(request, self.options),
+            validator: self.subject_validator.clone(),
+        }
+    }
+
+    /// Reports authorization that runs without the authentication layer having seen the request. Release
+    /// builds carry on and authenticate on demand; debug builds fail with a 500 so the ordering gets fixed.
+    pub fn layer_order_error(&self, request: &impl Request) -> Option<AuthResponse> {
+        if request.get_extensions().get::<AuthenticationState>().is_some() {
+            return None;
+        }
+
+        const MESSAGE: &str = "Authorization ran before the authentication layer, add the authentication layer \
+                               outside of the authorization layers";
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            path = request.get_uri().path(),
+            request_id = self.request_id(request).as_ref().map(RequestId::as_str),
+            "{MESSAGE}"
+        );
+
+        cfg!(debug_assertions).then(|| AuthResponse {
+            status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
+            headers: http::HeaderMap::default(),
+            body: MESSAGE.as_bytes().to_vec(),
+        })
+    }
+
+    /// The response refusing the request while the [`MaintenanceGate`] is enabled, checked by the authorization
+    /// layers once the request is authenticated.
+    pub fn maintenance_response(&self, request: &impl Request) -> Option<AuthResponse> {
+        let gate = self.maintenance_gate.as_ref()?;
+        let extensions = request.get_extensions();
+        gate.check(
+            extensions
+                .get::<SuccessAuthenticationResult>()
+                .map(|result| &result.principal),
+        )
+    }
+
+    /// `response` with a [`ProblemDetails`] body when configured, applied by the authorization layers to the
+    /// responses refusing requests.
+    pub fn problem_details(&self, request: &impl Request, response: AuthResponse) -> AuthResponse {
+        match &self.problem_details {
+            Some(problem_details) => problem_details.apply(request, response),
+            None => response,
+        }
+    }
+
+    /// `response` with CORS headers for cross-origin requests from the origins allowed by [`ChallengeCors`],
+    /// applied by the authorization layers last.
+    pub fn cors_response(&self, request: &impl Request, response: AuthResponse) -> AuthResponse {
+        match &self.challenge_cors {
+            Some(cors) => cors.apply(request, response),
+            None => response,
+        }
+    }
+
+    /// The request's [`RequestId`], looking at the configured header when it's not resolved yet.
+    pub fn request_id(&self, request: &impl Request) -> Option<RequestId> {
+        RequestId::of(request).or_else(|| RequestId::from_header(request, self.request_id_header.as_ref()?))
+    }
+
+    fn resolve_request_id(&self, request: &mut impl Request) {
+        if request.get_extensions().get::<RequestId>().is_some() {
+            return;
+        }
+
+        if let Some(request_id) = self.request_id(request) {
+            request.get_extensions_mut().insert(request_id);
+        }
+    }
+
+    pub async fn ensure_authenticated(&self, request: &mut impl Request) {
+        // Without the state the authentication layer is missing or runs after authorization, so the request is
+        // authenticated here rather than challenged as anonymous.
+        let is_pending = match request.get_extensions_mut().get_mut::<AuthenticationState>() {
+            Some(state) if !state.completed => {
+                state.completed = true;
+                true
+            }
+            Some(_) => false,
+            None => true,
+        };
+        if is_pending {
+            self.authenticate(request).await;
+        }
+    }
+
     pub async fn authenticate(&self, request: &mut impl Request) {
-        let result = self.handler.authenticate(request).await;
-        if let Ok(principal) = result {
-            request
-                .get_extensions_mut()
-                .insert(SuccessAuthenticationResult { principal });
+        self.resolve_request_id(request);
+        request
+            .get_extensions_mut()
+            .insert(AuthenticationState { completed: true });
+        let result = self.authenticate_validated(request).await;
+        complete_authentication(request, result);
+    }
+
+    /// Fails with [`SchemeLookupError`] as an [`AuthError::Other`] when `scheme` isn't registered.
+    pub fn authenticate_scheme<'a>(
+        &'a self,
+        scheme: &'a str,
+        request: &mut impl Request,
+    ) -> impl Future<Output = AuthenticationResult> + 'a {
+        let result = self.handler.authenticate_scheme(scheme, request);
+        async move {
+            result.await.unwrap_or_else(|| {
+                Err(AuthenticationError::fail(AuthError::Other(Box::new(
+                    SchemeLookupError::NotConfigured(scheme.to_owned()),
+                ))))
+            })
         }
     }
 
-    pub async fn challenge(&self, scheme: Option<&str>) -> AuthResponse {
-        let scheme = scheme.unwrap_or(&self.default_scheme);
-        self.handler
-            .challenge(scheme)
-            .await
-            .unwrap_or_else(|| panic!("Scheme {scheme} is not configured"))
+    pub fn challenge<'a>(
+        &'a self,
+        scheme: Option<&'a str>,
+        request: &impl Request,
+    ) -> impl Future<Output = Result<AuthResponse, SchemeLookupError>> + 'a {
+        let scheme = scheme.unwrap_or(self.default_scheme.as_str());
+        // A dependency being down isn't the client's fault, so don't ask it for other credentials.
+        let unavailable = request
+            .get_extensions()
+            .get::<AuthenticationFailure>()
+            .and_then(|failure| {
+                failure
+                    .errors
+                    .iter()
+                    .find_map(|err| err.error.downcast_ref::<CircuitOpen>())
+                    .map(CircuitOpen::response)
+            });
+        let challenge = self.handler.challenge(scheme, request);
+        async move {
+            if let Some(response) = unavailable {
+                return Ok(response);
+            }
+
+            challenge
+                .await
+                .ok_or_else(|| SchemeLookupError::NotConfigured(scheme.to_owned()))
+        }
     }
 
-    pub async fn forbid(&self, scheme: Option<&str>) -> AuthResponse {
-        let scheme = scheme.unwrap_or(&self.default_scheme);
-        self.handler
-            .forbid(scheme)
+    pub async fn forbid(
+        &self,
+        scheme: Option<&str>,
+        failure: Option<&AuthorizationFailure>,
+    ) -> Result<AuthResponse, SchemeLookupError> {
+        let scheme = scheme.unwrap_or(self.default_scheme.as_str());
+        let response = self
+            .handler
+            .forbid(scheme, failure)
             .await
-            .unwrap_or_else(|| panic!("Scheme {scheme} is not configured"))
+            .ok_or_else(|| SchemeLookupError::NotConfigured(scheme.to_owned()))?;
+
+        Ok(match &self.forbid_customizer {
+            Some(customizer) => customizer.customize(scheme, failure, response).await,
+            None => response,
+        })
     }
 
-    pub async fn sign_in(&self, scheme: Option<&str>, user: &UserPrincipal) -> AuthResponse {
-        let scheme = scheme.unwrap_or(&self.default_scheme);
+    pub async fn sign_in(
+        &self,
+        scheme: Option<&str>,
+        user: &UserPrincipal,
+        properties: &AuthenticationProperties,
+    ) -> Result<AuthResponse, SchemeLookupError> {
+        Ok(self.sign_in_result(scheme, user, properties).await?.response)
+    }
+
+    /// Like [`sign_in`](Self::sign_in), also returning what the handler issued.
+    pub async fn sign_in_result(
+        &self,
+        scheme: Option<&str>,
+        user: &UserPrincipal,
+        properties: &AuthenticationProperties,
+    ) -> Result<SignInResult, SchemeLookupError> {
+        let scheme = scheme.unwrap_or(self.default_scheme.as_str());
         self.handler
-            .sign_in(scheme, user)
+            .sign_in(scheme, user, properties)
             .await
-            .unwrap_or_else(|| panic!("Scheme {scheme} is not configured or it doesn't support sign-in/sign-out"))
+            .ok_or_else(|| self.sign_in_lookup_error(scheme))
     }
 
-    pub async fn sign_out(&self, scheme: Option<&str>) -> AuthResponse {
-        let scheme = scheme.unwrap_or(&self.default_scheme);
+    pub async fn sign_out(
+        &self,
+        scheme: Option<&str>,
+        request: &impl Request,
+    ) -> Result<AuthResponse, SchemeLookupError> {
+        let scheme = scheme.unwrap_or(self.default_scheme.as_str());
         self.handler
-            .sign_out(scheme)
+            .sign_out(scheme, request)
             .await
-            .unwrap_or_else(|| panic!("Scheme {scheme} is not configured or it doesn't support sign-in/sign-out"))
+            .ok_or_else(|| self.sign_in_lookup_error(scheme))
+    }
+
+    fn sign_in_lookup_error(&self, scheme: &str) -> SchemeLookupError {
+        if self.schemes.iter().any(|info| info.name == scheme) {
+            SchemeLookupError::SignInNotSupported(scheme.to_owned())
+        } else {
+            SchemeLookupError::NotConfigured(scheme.to_owned())
+        }
+    }
+}
+
+impl<Handler> AuthenticationService<Handler>
+where
+    Handler: CompoundAuthenticationHandler + DynCompoundAuthenticationHandler,
+{
+    /// Erases the handler type, trading a boxed future per call for a single instantiation of the framework
+    /// layers and handlers. See [`super::dyn_handler::DynRequest`] for how request extensions are handled.
+    pub fn into_dyn(self) -> AuthenticationService<Arc<dyn DynCompoundAuthenticationHandler>> {
+        AuthenticationService {
+            handler: Arc::new(self.handler),
+            default_scheme: self.default_scheme,
+            schemes: self.schemes,
+            lazy: self.lazy,
+            forbid_customizer: self.forbid_customizer,
+            options: self.options,
+            response_headers: self.response_headers,
+            response_hook: self.response_hook,
+            request_id_header: self.request_id_header,
+            subject_validator: self.subject_validator,
+            maintenance_gate: self.maintenance_gate,
+            initializers: self.initializers,
+            problem_details: self.problem_details,
+            challenge_cors: self.challenge_cors,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    MissingDefaultScheme,
+    UnknownDefaultScheme(SchemeName),
+    DuplicateScheme(SchemeName),
+    UnknownResponseHeadersScheme(SchemeName),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::MissingDefaultScheme => {
+                write!(f, "Default scheme must be set when more than one scheme is registered")
+            }
+            BuildError::UnknownDefaultScheme(scheme) => write!(f, "Default scheme {scheme} is not registered"),
+            BuildError::DuplicateScheme(scheme) => write!(f, "Scheme {scheme} is registered more than once"),
+            BuildError::UnknownResponseHeadersScheme(scheme) => {
+                write!(f, "Response headers are set for unregistered scheme {scheme}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// A scheme passed to [`AuthenticationService`] that can't serve the call.
+#[derive(Debug)]
+pub enum SchemeLookupError {
+    NotConfigured(String),
+    SignInNotSupported(String),
+}
+
+impl SchemeLookupError {
+    /// Asking for an unregistered scheme is a bug of the application, not of the client.
+    pub fn response(&self) -> AuthResponse {
+        #[cfg(feature = "tracing")]
+        tracing::error!("{self}");
+
+        AuthResponse {
+            status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
+            headers: http::HeaderMap::default(),
+            body: Vec::new(),
+        }
+    }
+}
+
+impl Display for SchemeLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemeLookupError::NotConfigured(scheme) => write!(f, "Scheme {scheme} is not configured"),
+            SchemeLookupError::SignInNotSupported(scheme) => {
+                write!(f, "Scheme {scheme} doesn't support sign-in/sign-out")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemeLookupError {}
+
+pub(crate) fn complete_authentication(request: &mut impl Request, result: CompoundAuthenticationResult) {
+    match result {
+        Ok((scheme, principal)) => {
+            let actor = principal.actor();
+            request.get_extensions_mut().insert(SuccessAuthenticationResult {
+                principal,
+                actor,
+                scheme,
+            });
+        }
+        Err(errors) if !errors.is_empty() => {
+            let request_id = RequestId::of(request);
+            request
+                .get_extensions_mut()
+                .insert(AuthenticationFailure { errors, request_id });
+        }
+        Err(_) => {}
     }
 }
 
 pub struct AuthenticationServiceBuilder<Handler> {
     handler: Handler,
-    default_scheme: Option<String>,
+    settings: BuilderOptions,
+}
+
+/// The settings of an [`AuthenticationServiceBuilder`] that don't depend on its handlers, moved as a unit when a
+/// handler is added.
+#[derive(Default)]
+struct BuilderOptions {
+    default_scheme: Option<SchemeName>,
+    lazy: bool,
+    forbid_customizer: Option<Arc<dyn ForbidResponseCustomizer>>,
+    options: AuthenticateOptions,
+    response_headers: HashMap<SchemeName, SuccessResponseHeaders>,
+    response_hook: Option<Arc<dyn ResponseHook>>,
+    request_id_header: Option<HeaderName>,
+    subject_validator: Option<Arc<dyn SubjectValidator>>,
+    maintenance_gate: Option<MaintenanceGate>,
+    initializers: Vec<Arc<dyn Initializer>>,
+    problem_details: Option<ProblemDetails>,
+    challenge_cors: Option<ChallengeCors>,
 }
 
 impl AuthenticationServiceBuilder<()> {
     pub fn new() -> AuthenticationServiceBuilder<()> {
         AuthenticationServiceBuilder {
             handler: (),
-            default_scheme: None,
+            settings: BuilderOptions::default(),
         }
     }
 
     pub fn add_authentication_handler<H: AuthenticationHandler>(
         self,
-        scheme: String,
+        scheme: impl Into<SchemeName>,
         handler: H,
     ) -> AuthenticationServiceBuilder<AuthenticationHandlerWithScheme<H>> {
         AuthenticationServiceBuilder {
-            handler: AuthenticationHandlerWithScheme { scheme, handler },
-            default_scheme: self.default_scheme,
+            handler: AuthenticationHandlerWithScheme {
+                scheme: scheme.into(),
+                handler,
+                filter: None,
+            },
+            settings: self.settings,
         }
     }
 
     pub fn add_sign_in_out_authentication_handler<H: SignInOutAuthenticationHandler>(
         self,
-        scheme: String,
+        scheme: impl Into<SchemeName>,
         handler: H,
     ) -> AuthenticationServiceBuilder<SignInOutAuthenticationHandlerWithScheme<H>> {
         AuthenticationServiceBuilder {
-            handler: SignInOutAuthenticationHandlerWithScheme { scheme, handler },
-            default_scheme: self.default_scheme,
+            handler: SignInOutAuthenticationHandlerWithScheme {
+                scheme: scheme.into(),
+                handler,
+                filter: None,
+            },
+            settings: self.settings,
         }
     }
 }
 
+impl<Handler: LastScheme> AuthenticationServiceBuilder<Handler> {
+    /// Restricts the handler added last to the requests matching `filter`, e.g. a cookie scheme to `/app/**`.
+    pub fn restrict_to(mut self, filter: SchemeFilter) -> Self {
+        self.handler.set_filter(filter);
+        self
+    }
+}
+
 impl Default for AuthenticationServiceBuilder<()> {
     fn default() -> Self {
         Self::new()
@@ -308,43 +1227,155 @@ where
 {
     pub fn add_authentication_handler<H: AuthenticationHandler>(
         self,
-        scheme: String,
+        scheme: impl Into<SchemeName>,
         handler: H,
     ) -> AuthenticationServiceBuilder<(Handler, AuthenticationHandlerWithScheme<H>)> {
         AuthenticationServiceBuilder {
-            handler: (self.handler, AuthenticationHandlerWithScheme { scheme, handler }),
-            default_scheme: self.default_scheme,
+            handler: (
+                self.handler,
+                AuthenticationHandlerWithScheme {
+                    scheme: scheme.into(),
+                    handler,
+                    filter: None,
+                },
+            ),
+            settings: self.settings,
         }
     }
 
     pub fn add_sign_in_out_authentication_handler<H: SignInOutAuthenticationHandler>(
         self,
-        scheme: String,
+        scheme: impl Into<SchemeName>,
         handler: H,
     ) -> AuthenticationServiceBuilder<(Handler, SignInOutAuthenticationHandlerWithScheme<H>)> {
         AuthenticationServiceBuilder {
             handler: (
                 self.handler,
-                SignInOutAuthenticationHandlerWithScheme { scheme, handler },
+                SignInOutAuthenticationHandlerWithScheme {
+                    scheme: scheme.into(),
+                    handler,
+                    filter: None,
+                },
             ),
-            default_scheme: self.default_scheme,
+            settings: self.settings,
         }
     }
 
-    pub fn set_default_scheme(self, scheme: String) -> Self {
-        Self {
-            default_scheme: Some(scheme),
-            ..self
-        }
+    pub fn set_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.settings.options.failure_policy = failure_policy;
+        self
     }
 
-    pub fn build(self) -> Option<AuthenticationService<Handler>> {
-        let Some(default_scheme) = self.default_scheme else {
-            return None;
+    pub fn set_parallel(mut self, parallel: bool) -> Self {
+        self.settings.options.parallel = parallel;
+        self
+    }
+
+    pub fn set_forbid_customizer(mut self, customizer: Arc<dyn ForbidResponseCustomizer>) -> Self {
+        self.settings.forbid_customizer = Some(customizer);
+        self
+    }
+
+    /// Headers added to responses of requests `scheme` authenticated. The framework layers add them when the
+    /// authentication layer itself authenticated the request, so not with lazy authentication.
+    pub fn set_response_headers(mut self, scheme: impl Into<SchemeName>, headers: SuccessResponseHeaders) -> Self {
+        self.settings.response_headers.insert(scheme.into(), headers);
+        self
+    }
+
+    pub fn set_response_hook(mut self, hook: Arc<dyn ResponseHook>) -> Self {
+        self.settings.response_hook = Some(hook);
+        self
+    }
+
+    /// Reads the [`RequestId`] from `header`, e.g. [`super::request_id::REQUEST_ID_HEADER`], for requests that
+    /// don't have one in their extensions.
+    pub fn set_request_id_header(mut self, header: HeaderName) -> Self {
+        self.settings.request_id_header = Some(header);
+        self
+    }
+
+    /// Checks every authenticated principal, e.g. against a
+    /// [`SubjectBlocklist`](super::subject_validation::SubjectBlocklist). Denied requests fail authentication
+    /// with [`SubjectDenied`].
+    pub fn set_subject_validator(mut self, validator: Arc<dyn SubjectValidator>) -> Self {
+        self.settings.subject_validator = Some(validator);
+        self
+    }
+
+    /// Run by [`AuthenticationService::initialize`].
+    pub fn add_initializer(mut self, initializer: Arc<dyn Initializer>) -> Self {
+        self.settings.initializers.push(initializer);
+        self
+    }
+
+    pub fn set_maintenance_gate(mut self, gate: MaintenanceGate) -> Self {
+        self.settings.maintenance_gate = Some(gate);
+        self
+    }
+
+    pub fn set_problem_details(mut self, problem_details: ProblemDetails) -> Self {
+        self.settings.problem_details = Some(problem_details);
+        self
+    }
+
+    pub fn set_challenge_cors(mut self, cors: ChallengeCors) -> Self {
+        self.settings.challenge_cors = Some(cors);
+        self
+    }
+
+    pub fn set_lazy(mut self, lazy: bool) -> Self {
+        self.settings.lazy = lazy;
+        self
+    }
+
+    pub fn set_default_scheme(mut self, scheme: impl Into<SchemeName>) -> Self {
+        self.settings.default_scheme = Some(scheme.into());
+        self
+    }
+
+    pub fn build(self) -> Result<AuthenticationService<Handler>, BuildError> {
+        let settings = self.settings;
+        let mut schemes = Vec::new();
+        self.handler.collect_schemes(&mut schemes);
+
+        for (i, scheme) in schemes.iter().enumerate() {
+            if schemes[..i].iter().any(|s| s.name == scheme.name) {
+                return Err(BuildError::DuplicateScheme(scheme.name.clone()));
+            }
+        }
+
+        let default_scheme = match (settings.default_scheme, &schemes[..]) {
+            (Some(default_scheme), _) if !schemes.iter().any(|s| s.name == default_scheme) => {
+                return Err(BuildError::UnknownDefaultScheme(default_scheme))
+            }
+            (Some(default_scheme), _) => default_scheme,
+            (None, [scheme]) => scheme.name.clone(),
+            (None, _) => return Err(BuildError::MissingDefaultScheme),
         };
 
-        Some(AuthenticationService {
+        if let Some(scheme) = settings
+            .response_headers
+            .keys()
+            .find(|&scheme| !schemes.iter().any(|s| s.name == *scheme))
+        {
+            return Err(BuildError::UnknownResponseHeadersScheme(scheme.clone()));
+        }
+
+        Ok(AuthenticationService {
             default_scheme,
+            schemes,
+            lazy: settings.lazy,
+            forbid_customizer: settings.forbid_customizer,
+            options: settings.options,
+            response_headers: settings.response_headers,
+            response_hook: settings.response_hook,
+            request_id_header: settings.request_id_header,
+            subject_validator: settings.subject_validator,
+            maintenance_gate: settings.maintenance_gate,
+            initializers: settings.initializers,
+            problem_details: settings.problem_details,
+            challenge_cors: settings.challenge_cors,
             handler: self.handler,
         })
     }
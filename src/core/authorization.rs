@@ -1,31 +1,390 @@
 use std::{
+    any::{type_name, Any},
+    borrow::Cow,
+    collections::HashMap,
     future::{ready, Ready},
-    sync::Arc,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock},
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use async_trait::async_trait;
 use futures::{
     future::{join, Join},
     Future,
 };
+use http::{HeaderMap, Method};
+use pin_project::pin_project;
 
 use super::{
-    authentication::{AuthenticationService, CompoundAuthenticationHandler, SuccessAuthenticationResult},
-    futures::{merge_bool_and, MergeBoolAnd},
-    http::{AuthResponse, Request, RequestExtensions},
-    principal::UserPrincipal,
+    authentication::{
+        AuthenticationService, CompoundAuthenticationHandler, ExpiredCredential, SuccessAuthenticationResult,
+    },
+    dyn_handler::DynCompoundAuthenticationHandler,
+    futures::{merge_unit, MergeUnit},
+    http::{AuthResponse, Request, RequestExtensions, RouteParams},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+    request_id::RequestId,
 };
 
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizationFailure {
+    pub failed_explicitly: bool,
+    pub requirement_names: Vec<String>,
+    pub messages: Vec<String>,
+    pub request_id: Option<RequestId>,
+}
+
+/// Why [`AuthorizationService::authorize`] refused a user.
+#[derive(Debug, Clone)]
+pub enum AuthorizationError {
+    /// No policy is registered under the name, e.g. after [`AuthorizationService::replace_policies`] removed it.
+    UnknownPolicy(String),
+    Failed(AuthorizationFailure),
+}
+
+impl std::fmt::Display for AuthorizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthorizationError::UnknownPolicy(policy) => write!(f, "Policy {policy} is not configured"),
+            AuthorizationError::Failed(failure) => match failure.requirement_names.is_empty() {
+                true => write!(f, "Authorization failed"),
+                false => write!(f, "Authorization failed: {}", failure.requirement_names.join(", ")),
+            },
+        }
+    }
+}
+
+impl std::error::Error for AuthorizationError {}
+
+impl From<AuthorizationFailure> for AuthorizationError {
+    fn from(failure: AuthorizationFailure) -> Self {
+        AuthorizationError::Failed(failure)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequirementOutcome {
+    Succeeded,
+    NotSatisfied,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct RequirementTrace {
+    pub name: String,
+    pub outcome: RequirementOutcome,
+    pub duration: Duration,
+}
+
+/// Inserted into the request extensions by policies with tracing enabled.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizationTrace {
+    pub requirements: Vec<RequirementTrace>,
+    pub request_id: Option<RequestId>,
+}
+
+#[derive(Default)]
+struct AuthorizationState {
+    pending_requirements: Vec<Cow<'static, str>>,
+    messages: Vec<String>,
+    has_failed: bool,
+    trace: Option<Vec<RequirementTrace>>,
+}
+
+/// Values computed by requirements that stay valid for the rest of the request, such as loaded resource
+/// owners. Shared by every policy evaluated for the same request.
+#[derive(Clone, Default)]
+pub struct AuthorizationCache {
+    entries: Arc<Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl AuthorizationCache {
+    pub fn get<T: Clone + Send + Sync + 'static>(&self, key: &str) -> Option<T> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.get(key)?.downcast_ref::<T>().cloned()
+    }
+
+    pub fn insert<T: Send + Sync + 'static>(&self, key: impl Into<String>, value: T) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.insert(key.into(), Arc::new(value));
+    }
+}
+
+/// The request headers, copied once per request and shared by every policy evaluated for it.
+#[derive(Clone)]
+struct RequestHeaders(Arc<HeaderMap>);
+
+pub(crate) fn ensure_authorization_cache(request: &mut impl Request) {
+    if request.get_extensions().get::<AuthorizationCache>().is_none() {
+        request.get_extensions_mut().insert(AuthorizationCache::default());
+    }
+    if request.get_extensions().get::<RequestHeaders>().is_none() {
+        let headers = RequestHeaders(Arc::new(request.get_headers()));
+        request.get_extensions_mut().insert(headers);
+    }
+}
+
+/// What requirements may know about the request being authorized.
+#[derive(Clone, Default)]
+struct RequestData {
+    route_params: RouteParams,
+    headers: Arc<HeaderMap>,
+    cache: AuthorizationCache,
+    request_id: Option<RequestId>,
+    expired_credential: Option<ExpiredCredential>,
+}
+
+impl RequestData {
+    fn from_request(request: &impl Request) -> Self {
+        Self {
+            route_params: request.get_route_params(),
+            headers: request
+                .get_extensions()
+                .get::<RequestHeaders>()
+                .map(|headers| headers.0.clone())
+                .unwrap_or_else(|| Arc::new(request.get_headers())),
+            cache: request
+                .get_extensions()
+                .get::<AuthorizationCache>()
+                .cloned()
+                .unwrap_or_default(),
+            request_id: RequestId::of(request),
+            expired_credential: request.get_extensions().get::<ExpiredCredential>().cloned(),
+        }
+    }
+}
+
+pub struct AuthorizationHandlerContext<'a> {
+    principal: &'a UserPrincipal,
+    request: RequestData,
+    state: Mutex<AuthorizationState>,
+}
+
+impl<'a> AuthorizationHandlerContext<'a> {
+    pub fn new(principal: &'a UserPrincipal) -> Self {
+        Self {
+            principal,
+            request: RequestData::default(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn with_route_params(mut self, route_params: RouteParams) -> Self {
+        self.request.route_params = route_params;
+        self
+    }
+
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.request.headers = Arc::new(headers);
+        self
+    }
+
+    fn with_request(self, request: RequestData) -> Self {
+        Self { request, ..self }
+    }
+
+    pub fn with_trace(principal: &'a UserPrincipal) -> Self {
+        let context = Self::new(principal);
+        context.state().trace = Some(Vec::new());
+        context
+    }
+
+    pub fn principal(&self) -> &'a UserPrincipal {
+        self.principal
+    }
+
+    pub fn route_params(&self) -> &RouteParams {
+        &self.request.route_params
+    }
+
+    pub fn route_param(&self, name: &str) -> Option<&str> {
+        self.request.route_params.get(name)
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.request.headers
+    }
+
+    pub fn request_id(&self) -> Option<&RequestId> {
+        self.request.request_id.as_ref()
+    }
+
+    /// Set when the request was authenticated with credentials accepted past their expiry.
+    pub fn expired_credential(&self) -> Option<&ExpiredCredential> {
+        self.request.expired_credential.as_ref()
+    }
+
+    pub fn cache(&self) -> &AuthorizationCache {
+        &self.request.cache
+    }
+
+    pub fn add_pending_requirement(&self, requirement_name: impl Into<Cow<'static, str>>) {
+        self.state().pending_requirements.push(requirement_name.into());
+    }
+
+    pub fn pending_requirements(&self) -> Vec<String> {
+        self.state()
+            .pending_requirements
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    pub fn succeed(&self, requirement_name: &str) {
+        let mut state = self.state();
+        if let Some(index) = state.pending_requirements.iter().position(|n| n == requirement_name) {
+            state.pending_requirements.remove(index);
+        }
+    }
+
+    /// Fails the evaluation, which stops at once: requirements of a tuple still running are dropped, and the
+    /// ones not started yet don't run.
+    pub fn fail(&self) {
+        self.state().has_failed = true;
+    }
+
+    pub fn fail_with_message(&self, message: impl Into<String>) {
+        let mut state = self.state();
+        state.has_failed = true;
+        state.messages.push(message.into());
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.state().has_failed
+    }
+
+    pub fn has_succeeded(&self) -> bool {
+        let state = self.state();
+        !state.has_failed && state.pending_requirements.is_empty()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.state().trace.is_some()
+    }
+
+    pub fn into_result(self) -> Result<(), AuthorizationFailure> {
+        self.into_result_with_trace().0
+    }
+
+    pub fn into_result_with_trace(self) -> (Result<(), AuthorizationFailure>, AuthorizationTrace) {
+        let state = self.state.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let trace = AuthorizationTrace {
+            requirements: state.trace.unwrap_or_default(),
+            request_id: self.request.request_id.clone(),
+        };
+        if !state.has_failed && state.pending_requirements.is_empty() {
+            return (Ok(()), trace);
+        }
+
+        let failure = AuthorizationFailure {
+            failed_explicitly: state.has_failed,
+            requirement_names: state.pending_requirements.into_iter().map(Cow::into_owned).collect(),
+            messages: state.messages,
+            request_id: self.request.request_id,
+        };
+        (Err(failure), trace)
+    }
+
+    fn record(&self, name: Cow<'static, str>, failed_before: bool, started_at: Instant) {
+        let mut state = self.state();
+        let outcome = if state.has_failed && !failed_before {
+            RequirementOutcome::Failed
+        } else if state.pending_requirements.contains(&name) {
+            RequirementOutcome::NotSatisfied
+        } else {
+            RequirementOutcome::Succeeded
+        };
+
+        if let Some(trace) = &mut state.trace {
+            trace.push(RequirementTrace {
+                name: name.into_owned(),
+                outcome,
+                duration: started_at.elapsed(),
+            });
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, AuthorizationState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 pub trait AuthorizationRequirement: Clone + Send + Sync + 'static {
-    type AuthorizeFut: Future<Output = bool>;
+    type AuthorizeFut<'a>: Future<Output = ()> + Send + 'a
+    where
+        Self: 'a;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(type_name::<Self>())
+    }
+
+    /// Composite requirements, such as tuples, are traced through their parts rather than as a whole.
+    fn is_composite(&self) -> bool {
+        false
+    }
+
+    fn register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        context.add_pending_requirement(self.name());
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a>;
+}
+
+#[pin_project]
+pub struct Traced<'a, Fut> {
+    #[pin]
+    fut: Fut,
+    trace: Option<(Cow<'static, str>, bool, Instant)>,
+    context: &'a AuthorizationHandlerContext<'a>,
+}
+
+impl<Fut> Future for Traced<'_, Fut>
+where
+    Fut: Future<Output = ()>,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if this.fut.poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        if let Some((name, failed_before, started_at)) = this.trace.take() {
+            this.context.record(name, failed_before, started_at);
+        }
+
+        Poll::Ready(())
+    }
+}
 
-    fn authorize(&self, principal: &mut UserPrincipal) -> Self::AuthorizeFut;
+fn traced<'a, R: AuthorizationRequirement>(
+    requirement: &'a R,
+    context: &'a AuthorizationHandlerContext<'a>,
+) -> Traced<'a, R::AuthorizeFut<'a>> {
+    let trace = (!requirement.is_composite() && context.is_tracing())
+        .then(|| (requirement.name(), context.has_failed(), Instant::now()));
+
+    Traced {
+        fut: requirement.authorize(context),
+        trace,
+        context,
+    }
 }
 
 impl AuthorizationRequirement for () {
-    type AuthorizeFut = Ready<bool>;
+    type AuthorizeFut<'a> = Ready<()>;
 
-    fn authorize(&self, _: &mut UserPrincipal) -> Self::AuthorizeFut {
-        ready(true)
+    fn is_composite(&self) -> bool {
+        true
+    }
+
+    fn register_pending(&self, _: &AuthorizationHandlerContext<'_>) {}
+
+    fn authorize<'a>(&'a self, _: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        ready(())
     }
 }
 
@@ -34,10 +393,130 @@ where
     R1: AuthorizationRequirement,
     R2: AuthorizationRequirement,
 {
-    type AuthorizeFut = MergeBoolAnd<Join<R1::AuthorizeFut, R2::AuthorizeFut>>;
+    type AuthorizeFut<'a> =
+        ShortCircuit<'a, MergeUnit<Join<Traced<'a, R1::AuthorizeFut<'a>>, Traced<'a, R2::AuthorizeFut<'a>>>>>;
+
+    fn is_composite(&self) -> bool {
+        true
+    }
+
+    fn register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        self.0.register_pending(context);
+        self.1.register_pending(context);
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        ShortCircuit {
+            fut: merge_unit(join(traced(&self.0, context), traced(&self.1, context))),
+            context,
+        }
+    }
+}
+
+/// Completes as soon as the context has failed, dropping the requirements still running, as they can't change
+/// the outcome anymore.
+#[pin_project]
+pub struct ShortCircuit<'a, Fut> {
+    #[pin]
+    fut: Fut,
+    context: &'a AuthorizationHandlerContext<'a>,
+}
+
+impl<Fut> Future for ShortCircuit<'_, Fut>
+where
+    Fut: Future<Output = ()>,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if this.context.has_failed() {
+            return Poll::Ready(());
+        }
+
+        match this.fut.poll(cx) {
+            Poll::Pending if this.context.has_failed() => Poll::Ready(()),
+            poll => poll,
+        }
+    }
+}
+
+/// Like the tuple, but `R2` is only evaluated once `R1` has been satisfied, so an expensive requirement
+/// doesn't run for requests that are going to be denied anyway.
+#[derive(Clone)]
+pub struct Sequential<R1, R2>(pub R1, pub R2);
+
+impl<R1, R2> AuthorizationRequirement for Sequential<R1, R2>
+where
+    R1: AuthorizationRequirement,
+    R2: AuthorizationRequirement,
+{
+    type AuthorizeFut<'a> = SequentialFut<'a, R1, R2>;
+
+    fn is_composite(&self) -> bool {
+        true
+    }
+
+    fn register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        self.0.register_pending(context);
+        self.1.register_pending(context);
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let names = AuthorizationHandlerContext::new(context.principal());
+        self.0.register_pending(&names);
+
+        SequentialFut {
+            first: Some(traced(&self.0, context)),
+            second: None,
+            first_names: names.pending_requirements(),
+            requirement: &self.1,
+            context,
+        }
+    }
+}
+
+#[pin_project]
+pub struct SequentialFut<'a, R1, R2>
+where
+    R1: AuthorizationRequirement,
+    R2: AuthorizationRequirement,
+{
+    #[pin]
+    first: Option<Traced<'a, R1::AuthorizeFut<'a>>>,
+    #[pin]
+    second: Option<Traced<'a, R2::AuthorizeFut<'a>>>,
+    first_names: Vec<String>,
+    requirement: &'a R2,
+    context: &'a AuthorizationHandlerContext<'a>,
+}
+
+impl<'a, R1, R2> Future for SequentialFut<'a, R1, R2>
+where
+    R1: AuthorizationRequirement,
+    R2: AuthorizationRequirement,
+{
+    type Output = ();
 
-    fn authorize(&self, principal: &mut UserPrincipal) -> Self::AuthorizeFut {
-        merge_bool_and(join(self.0.authorize(principal), self.1.authorize(principal)))
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(first) = this.first.as_mut().as_pin_mut() {
+            if first.poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.first.set(None);
+
+            let pending = this.context.pending_requirements();
+            if this.context.has_failed() || this.first_names.iter().any(|name| pending.contains(name)) {
+                return Poll::Ready(());
+            }
+            this.second.set(Some(traced(*this.requirement, this.context)));
+        }
+
+        match this.second.as_pin_mut() {
+            Some(second) => second.poll(cx),
+            None => Poll::Ready(()),
+        }
     }
 }
 
@@ -45,13 +524,347 @@ where
 pub struct IsInRoleRequirement(pub String);
 
 impl AuthorizationRequirement for IsInRoleRequirement {
-    type AuthorizeFut = Ready<bool>;
+    type AuthorizeFut<'a> = Ready<()>;
 
-    fn authorize(&self, principal: &mut UserPrincipal) -> Self::AuthorizeFut {
-        ready(principal.is_in_role(&self.0))
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("IsInRole({})", self.0))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        if context.principal().is_in_role(&self.0) {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+/// Succeeds when a numeric claim, e.g. a subscription level encoded in the token, is within the inclusive
+/// bounds. Claims that aren't numbers never satisfy it.
+#[derive(Clone)]
+pub struct ClaimRangeRequirement {
+    pub claim_type: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl AuthorizationRequirement for ClaimRangeRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        let bound = |bound: Option<f64>| bound.map_or_else(String::new, |bound| bound.to_string());
+        Cow::Owned(format!(
+            "ClaimRange({}, {}..={})",
+            self.claim_type,
+            bound(self.min),
+            bound(self.max)
+        ))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let in_range = context.principal().claim(&self.claim_type).is_some_and(|claim| {
+            claim.iter().any(|value| {
+                let value = value.as_i64().map(|value| value as f64).or_else(|| value.as_f64());
+                value.is_some_and(|value| {
+                    self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+                })
+            })
+        });
+        if in_range {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+/// Refuses credentials that were accepted past their expiry, see [`ExpiredCredential`].
+#[derive(Clone, Copy)]
+pub struct FreshCredentialRequirement;
+
+impl AuthorizationRequirement for FreshCredentialRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("FreshCredential")
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        if context.expired_credential().is_none() {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+/// Succeeds when the `email_verified` or `phone_number_verified` claim is true. Identity providers that encode
+/// them as strings are handled, so `"true"` counts too.
+#[derive(Clone, Copy)]
+pub struct VerifiedRequirement;
+
+impl AuthorizationRequirement for VerifiedRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Verified")
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let is_verified = [claim_types::EMAIL_VERIFIED, claim_types::PHONE_NUMBER_VERIFIED]
+            .into_iter()
+            .filter_map(|claim_type| context.principal().claim(claim_type))
+            .flat_map(ClaimValue::iter)
+            .any(|value| match value {
+                ClaimPlainValue::Bool(verified) => *verified,
+                ClaimPlainValue::String(verified) => verified.eq_ignore_ascii_case("true"),
+                _ => false,
+            });
+        if is_verified {
+            context.succeed(&self.name());
+        }
+
+        ready(())
     }
 }
 
+/// Succeeds when the `birthdate` claim, an ISO `YYYY-MM-DD` date, is at least `min_age` years before today in
+/// UTC. A year-only birthdate counts from the end of that year, and one without a year (`0000-MM-DD`) never
+/// satisfies it.
+#[derive(Clone)]
+pub struct MinimumAgeRequirement {
+    pub min_age: u32,
+    /// Where today's date comes from, e.g. a fixed time in tests.
+    pub clock: fn() -> SystemTime,
+}
+
+impl MinimumAgeRequirement {
+    pub fn new(min_age: u32) -> Self {
+        Self {
+            min_age,
+            clock: SystemTime::now,
+        }
+    }
+
+    fn is_old_enough(&self, birthdate: &str) -> bool {
+        let (Some(birthdate), Some(today)) = (parse_birthdate(birthdate), civil_date((self.clock)())) else {
+            return false;
+        };
+
+        let age = today.0 - birthdate.0 - i64::from((today.1, today.2) < (birthdate.1, birthdate.2));
+        age >= i64::from(self.min_age)
+    }
+}
+
+impl AuthorizationRequirement for MinimumAgeRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("MinimumAge({})", self.min_age))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let is_old_enough = context
+            .principal()
+            .claim_strs(claim_types::BIRTHDATE)
+            .next()
+            .is_some_and(|birthdate| self.is_old_enough(birthdate));
+        if is_old_enough {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+/// `(year, month, day)` of an OpenID Connect `birthdate`.
+fn parse_birthdate(birthdate: &str) -> Option<(i64, u32, u32)> {
+    let mut parts = birthdate.splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok().filter(|&year| year > 0)?;
+    match (parts.next(), parts.next()) {
+        (None, _) => Some((year, 12, 31)),
+        (Some(month), Some(day)) => {
+            let month = month.parse().ok().filter(|month| (1..=12).contains(month))?;
+            let day = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+            Some((year, month, day))
+        }
+        (Some(_), None) => None,
+    }
+}
+
+/// `(year, month, day)` of `time` in UTC, using the days-to-civil algorithm of the proleptic Gregorian calendar.
+fn civil_date(time: SystemTime) -> Option<(i64, u32, u32)> {
+    let days = i64::try_from(time.duration_since(UNIX_EPOCH).ok()?.as_secs() / 86_400).ok()? + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    Some((year, u32::try_from(month).ok()?, u32::try_from(day).ok()?))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImpersonationRequirement {
+    Require,
+    Forbid,
+}
+
+impl AuthorizationRequirement for ImpersonationRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        match self {
+            ImpersonationRequirement::Require => Cow::Borrowed("RequireImpersonation"),
+            ImpersonationRequirement::Forbid => Cow::Borrowed("ForbidImpersonation"),
+        }
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let is_impersonated = context.principal().is_impersonated();
+        if is_impersonated == (*self == ImpersonationRequirement::Require) {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+/// Succeeds when the wrapped requirement doesn't, e.g. "not in role banned".
+#[derive(Clone)]
+pub struct Not<R: AuthorizationRequirement>(pub R);
+
+impl<R> AuthorizationRequirement for Not<R>
+where
+    R: AuthorizationRequirement,
+{
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("Not({})", self.0.name()))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        Box::pin(async move {
+            // The inner requirement runs in its own context so its failure doesn't fail the whole policy.
+            if evaluate_requirement(&self.0, context.principal(), &context.request)
+                .await
+                .is_err()
+            {
+                context.succeed(&self.name());
+            }
+        })
+    }
+}
+
+type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+trait ObjectSafeRequirement: Send + Sync + 'static {
+    fn erased_name(&self) -> Cow<'static, str>;
+
+    fn erased_is_composite(&self) -> bool;
+
+    fn erased_register_pending(&self, context: &AuthorizationHandlerContext<'_>);
+
+    fn erased_authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> AuthorizeFut<'a>;
+}
+
+impl<R: AuthorizationRequirement> ObjectSafeRequirement for R {
+    fn erased_name(&self) -> Cow<'static, str> {
+        AuthorizationRequirement::name(self)
+    }
+
+    fn erased_is_composite(&self) -> bool {
+        AuthorizationRequirement::is_composite(self)
+    }
+
+    fn erased_register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        AuthorizationRequirement::register_pending(self, context)
+    }
+
+    fn erased_authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> AuthorizeFut<'a> {
+        Box::pin(AuthorizationRequirement::authorize(self, context))
+    }
+}
+
+/// A requirement with its type erased, so that differently built requirements share one type. It's
+/// transparent to tracing: names and composite parts are reported as if it weren't boxed.
+#[derive(Clone)]
+pub struct BoxedRequirement(Arc<dyn ObjectSafeRequirement>);
+
+impl BoxedRequirement {
+    pub fn new(requirement: impl AuthorizationRequirement) -> Self {
+        Self(Arc::new(requirement))
+    }
+}
+
+impl AuthorizationRequirement for BoxedRequirement {
+    type AuthorizeFut<'a> = AuthorizeFut<'a>;
+
+    fn name(&self) -> Cow<'static, str> {
+        self.0.erased_name()
+    }
+
+    fn is_composite(&self) -> bool {
+        self.0.erased_is_composite()
+    }
+
+    fn register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        self.0.erased_register_pending(context)
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        self.0.erased_authorize(context)
+    }
+}
+
+pub trait AuthorizationHandler: Clone + Send + Sync + 'static {
+    type HandleFut<'a>: Future<Output = ()> + Send + 'a
+    where
+        Self: 'a;
+
+    fn handle<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::HandleFut<'a>;
+}
+
+#[derive(Clone)]
+pub struct ContributingHandler<Handler: AuthorizationHandler>(pub Handler);
+
+impl<H> AuthorizationRequirement for ContributingHandler<H>
+where
+    H: AuthorizationHandler,
+{
+    type AuthorizeFut<'a> = H::HandleFut<'a>;
+
+    fn register_pending(&self, _: &AuthorizationHandlerContext<'_>) {}
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        self.0.handle(context)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationAuditEvent {
+    pub method: Method,
+    pub path: String,
+    pub subject: Option<String>,
+    pub request_id: Option<RequestId>,
+    /// `None` when the request was denied for not being authenticated.
+    pub failure: Option<AuthorizationFailure>,
+    /// `false` when the policy is in report-only mode and the request was let through.
+    pub enforced: bool,
+}
+
+#[async_trait]
+pub trait AuthorizationAuditSink: Send + Sync + 'static {
+    async fn record(&self, event: AuthorizationAuditEvent);
+}
+
 pub struct AuthorizationPolicy<Handler, Requirement = ()>
 where
     Handler: CompoundAuthenticationHandler,
@@ -59,6 +872,10 @@ where
 {
     auth_service: Arc<AuthenticationService<Handler>>,
     requirement: Requirement,
+    trace: bool,
+    report_only: bool,
+    audit_sink: Option<Arc<dyn AuthorizationAuditSink>>,
+    not_found: bool,
 }
 
 impl<Handler, Requirement> AuthorizationPolicy<Handler, Requirement>
@@ -66,20 +883,176 @@ where
     Handler: CompoundAuthenticationHandler,
     Requirement: AuthorizationRequirement,
 {
+    /// Records the outcome and duration of every requirement into an [`AuthorizationTrace`] request
+    /// extension (and `tracing` events when that feature is enabled).
+    pub fn trace_evaluation(self, enabled: bool) -> Self {
+        Self { trace: enabled, ..self }
+    }
+
+    /// Evaluates the policy but lets denied requests through, reporting them to the audit sink instead.
+    /// Meant as a dry run before a new policy is enforced. Anonymous requests are still challenged.
+    pub fn report_only(self, enabled: bool) -> Self {
+        Self {
+            report_only: enabled,
+            ..self
+        }
+    }
+
+    /// Receives every denial, enforced or not.
+    pub fn audit_sink(self, audit_sink: Arc<dyn AuthorizationAuditSink>) -> Self {
+        Self {
+            audit_sink: Some(audit_sink),
+            ..self
+        }
+    }
+
+    /// Answers challenged and forbidden requests with `404 Not Found`, hiding e.g. admin endpoints from users who
+    /// can't use them.
+    pub fn respond_not_found(self, enabled: bool) -> Self {
+        Self {
+            not_found: enabled,
+            ..self
+        }
+    }
+
+    pub fn boxed(self) -> BoxedPolicy<Handler> {
+        AuthorizationPolicy {
+            auth_service: self.auth_service,
+            requirement: BoxedRequirement::new(self.requirement),
+            trace: self.trace,
+            report_only: self.report_only,
+            audit_sink: self.audit_sink,
+            not_found: self.not_found,
+        }
+    }
+
     pub async fn authorize(&self, request: &mut impl Request) -> Result<(), AuthResponse> {
-        let mut extensions = request.get_extensions_mut();
-        let Some(auth_result) = extensions.get_mut::<SuccessAuthenticationResult>() else {
-            return Err(self.auth_service.challenge(None).await);
+        let result = self
+            .authorize_request(request)
+            .await
+            .map_err(|response| self.auth_service.problem_details(request, response));
+        let result = match result {
+            Err(response) if self.not_found => Err(response.into_not_found()),
+            result => result,
         };
+        result.map_err(|response| self.auth_service.cors_response(request, response))
+    }
 
-        if !self.requirement.authorize(&mut auth_result.principal).await {
-            return Err(self.auth_service.forbid(None).await);
+    async fn authorize_request(&self, request: &mut impl Request) -> Result<(), AuthResponse> {
+        if let Some(response) = self.auth_service.layer_order_error(request) {
+            return Err(response);
         }
+        self.auth_service.ensure_authenticated(request).await;
+        if let Some(response) = self.auth_service.maintenance_response(request) {
+            return Err(response);
+        }
+        ensure_authorization_cache(request);
+
+        let request_data = RequestData::from_request(request);
+        let (result, trace, subject) = match request.get_extensions().get::<SuccessAuthenticationResult>() {
+            Some(auth_result) => {
+                let subject = auth_result
+                    .principal
+                    .claim(claim_types::SUBJECT)
+                    .and_then(|subject| subject.iter().next()?.as_str().map(str::to_owned));
+                if self.trace {
+                    let (result, trace) = self.evaluate_traced(&auth_result.principal, request_data).await;
+                    (result.map_err(Some), Some(trace), subject)
+                } else {
+                    let result = evaluate_requirement(&self.requirement, &auth_result.principal, &request_data).await;
+                    (result.map_err(Some), None, subject)
+                }
+            }
+            None => (Err(None), None, None),
+        };
+
+        if let Some(trace) = trace {
+            request.get_extensions_mut().insert(trace);
+        }
+
+        let Err(failure) = result else {
+            return Ok(());
+        };
 
-        Ok(())
+        if let Some(audit_sink) = &self.audit_sink {
+            audit_sink
+                .record(AuthorizationAuditEvent {
+                    method: request.get_method().clone(),
+                    path: request.get_uri().path().to_owned(),
+                    subject,
+                    request_id: RequestId::of(request),
+                    failure: failure.clone(),
+                    enforced: !self.report_only || failure.is_none(),
+                })
+                .await;
+        }
+
+        match failure {
+            Some(_) if self.report_only => Ok(()),
+            Some(failure) => {
+                let response = self
+                    .auth_service
+                    .forbid(None, Some(&failure))
+                    .await
+                    .unwrap_or_else(|err| err.response());
+                request.get_extensions_mut().insert(failure);
+                Err(response)
+            }
+            None => Err(self
+                .auth_service
+                .challenge(None, request)
+                .await
+                .unwrap_or_else(|err| err.response())),
+        }
+    }
+
+    pub async fn evaluate(&self, principal: &UserPrincipal) -> Result<(), AuthorizationFailure> {
+        evaluate_requirement(&self.requirement, principal, &RequestData::default()).await
+    }
+
+    pub async fn evaluate_with_trace(
+        &self,
+        principal: &UserPrincipal,
+    ) -> (Result<(), AuthorizationFailure>, AuthorizationTrace) {
+        self.evaluate_traced(principal, RequestData::default()).await
+    }
+
+    async fn evaluate_traced(
+        &self,
+        principal: &UserPrincipal,
+        request: RequestData,
+    ) -> (Result<(), AuthorizationFailure>, AuthorizationTrace) {
+        let context = AuthorizationHandlerContext::with_trace(principal).with_request(request);
+        self.requirement.register_pending(&context);
+        traced(&self.requirement, &context).await;
+        let (result, trace) = context.into_result_with_trace();
+
+        #[cfg(feature = "tracing")]
+        for requirement in &trace.requirements {
+            tracing::debug!(
+                requirement = %requirement.name,
+                request_id = trace.request_id.as_ref().map(RequestId::as_str),
+                outcome = ?requirement.outcome,
+                duration = ?requirement.duration,
+                "Evaluated authorization requirement"
+            );
+        }
+
+        (result, trace)
     }
 }
 
+async fn evaluate_requirement<R: AuthorizationRequirement>(
+    requirement: &R,
+    principal: &UserPrincipal,
+    request: &RequestData,
+) -> Result<(), AuthorizationFailure> {
+    let context = AuthorizationHandlerContext::new(principal).with_request(request.clone());
+    requirement.register_pending(&context);
+    requirement.authorize(&context).await;
+    context.into_result()
+}
+
 impl<Handler, Requirement> Clone for AuthorizationPolicy<Handler, Requirement>
 where
     Handler: CompoundAuthenticationHandler,
@@ -89,10 +1062,18 @@ where
         Self {
             auth_service: self.auth_service.clone(),
             requirement: self.requirement.clone(),
+            trace: self.trace,
+            report_only: self.report_only,
+            audit_sink: self.audit_sink.clone(),
+            not_found: self.not_found,
         }
     }
 }
 
+/// A policy that can be stored in collections or passed around without naming its requirement chain.
+pub type BoxedPolicy<Handler = Arc<dyn DynCompoundAuthenticationHandler>> =
+    AuthorizationPolicy<Handler, BoxedRequirement>;
+
 pub struct AuthorizationPolicyBuilder<Requirement>
 where
     Requirement: AuthorizationRequirement,
@@ -119,10 +1100,85 @@ where
         }
     }
 
+    pub fn add_handler<H: AuthorizationHandler>(
+        self,
+        handler: H,
+    ) -> AuthorizationPolicyBuilder<(Requirement, ContributingHandler<H>)> {
+        self.add_requirement(ContributingHandler(handler))
+    }
+
     pub fn require_role(self, role: String) -> AuthorizationPolicyBuilder<(Requirement, IsInRoleRequirement)> {
         self.add_requirement(IsInRoleRequirement(role))
     }
 
+    pub fn require_claim_at_least(
+        self,
+        claim_type: impl Into<String>,
+        min: impl Into<f64>,
+    ) -> AuthorizationPolicyBuilder<(Requirement, ClaimRangeRequirement)> {
+        self.add_requirement(ClaimRangeRequirement {
+            claim_type: claim_type.into(),
+            min: Some(min.into()),
+            max: None,
+        })
+    }
+
+    pub fn require_claim_in_range(
+        self,
+        claim_type: impl Into<String>,
+        min: impl Into<f64>,
+        max: impl Into<f64>,
+    ) -> AuthorizationPolicyBuilder<(Requirement, ClaimRangeRequirement)> {
+        self.add_requirement(ClaimRangeRequirement {
+            claim_type: claim_type.into(),
+            min: Some(min.into()),
+            max: Some(max.into()),
+        })
+    }
+
+    pub fn require_fresh_credentials(self) -> AuthorizationPolicyBuilder<(Requirement, FreshCredentialRequirement)> {
+        self.add_requirement(FreshCredentialRequirement)
+    }
+
+    pub fn require_verified(self) -> AuthorizationPolicyBuilder<(Requirement, VerifiedRequirement)> {
+        self.add_requirement(VerifiedRequirement)
+    }
+
+    pub fn require_minimum_age(self, min_age: u32) -> AuthorizationPolicyBuilder<(Requirement, MinimumAgeRequirement)> {
+        self.add_requirement(MinimumAgeRequirement::new(min_age))
+    }
+
+    /// Adds `requirement`, evaluating it only after everything added so far has been satisfied.
+    pub fn then_require<R: AuthorizationRequirement>(
+        self,
+        requirement: R,
+    ) -> AuthorizationPolicyBuilder<Sequential<Requirement, R>> {
+        AuthorizationPolicyBuilder {
+            requirement: Sequential(self.requirement, requirement),
+        }
+    }
+
+    pub fn require_not<R: AuthorizationRequirement>(
+        self,
+        requirement: R,
+    ) -> AuthorizationPolicyBuilder<(Requirement, Not<R>)> {
+        self.add_requirement(Not(requirement))
+    }
+
+    /// Requires everything `other` requires on top of this builder's requirements.
+    pub fn combine<R: AuthorizationRequirement>(
+        self,
+        other: AuthorizationPolicyBuilder<R>,
+    ) -> AuthorizationPolicyBuilder<(Requirement, R)> {
+        self.add_requirement(other.requirement)
+    }
+
+    pub fn boxed(self) -> AuthorizationPolicyBuilder<BoxedRequirement> {
+        AuthorizationPolicyBuilder {
+            requirement: BoxedRequirement::new(self.requirement),
+        }
+    }
+
     pub fn build<Handler: CompoundAuthenticationHandler>(
         self,
         auth_service: Arc<AuthenticationService<Handler>>,
@@ -130,6 +1186,10 @@ where
         AuthorizationPolicy {
             auth_service,
             requirement: self.requirement,
+            trace: false,
+            report_only: false,
+            audit_sink: None,
+            not_found: false,
         }
     }
 }
@@ -139,3 +1199,279 @@ impl Default for AuthorizationPolicyBuilder<()> {
         Self::new()
     }
 }
+
+impl<Requirement> Clone for AuthorizationPolicyBuilder<Requirement>
+where
+    Requirement: AuthorizationRequirement,
+{
+    fn clone(&self) -> Self {
+        Self {
+            requirement: self.requirement.clone(),
+        }
+    }
+}
+
+type EvaluateFut<'a> = Pin<Box<dyn Future<Output = Result<(), AuthorizationFailure>> + Send + 'a>>;
+
+trait DynAuthorizationRequirement: Send + Sync + 'static {
+    fn evaluate<'a>(&'a self, principal: &'a UserPrincipal, request: &'a RequestData) -> EvaluateFut<'a>;
+}
+
+impl<R: AuthorizationRequirement> DynAuthorizationRequirement for R {
+    fn evaluate<'a>(&'a self, principal: &'a UserPrincipal, request: &'a RequestData) -> EvaluateFut<'a> {
+        Box::pin(evaluate_requirement(self, principal, request))
+    }
+}
+
+/// A policy registered under a name, usable as a requirement of other policies.
+#[derive(Clone)]
+pub struct PolicyRequirement {
+    name: String,
+    requirement: Arc<dyn DynAuthorizationRequirement>,
+}
+
+impl PolicyRequirement {
+    pub fn extend(&self) -> AuthorizationPolicyBuilder<PolicyRequirement> {
+        AuthorizationPolicyBuilder {
+            requirement: self.clone(),
+        }
+    }
+}
+
+impl AuthorizationRequirement for PolicyRequirement {
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("Policy({})", self.name))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        Box::pin(async move {
+            match self.requirement.evaluate(context.principal(), &context.request).await {
+                Ok(()) => context.succeed(&self.name()),
+                Err(failure) if failure.failed_explicitly => {
+                    context.fail();
+                    for message in failure.messages {
+                        context.fail_with_message(message);
+                    }
+                }
+                Err(_) => {}
+            }
+        })
+    }
+}
+
+fn registered_policy(
+    policies: &HashMap<String, Arc<dyn DynAuthorizationRequirement>>,
+    name: &str,
+) -> Result<PolicyRequirement, AuthorizationError> {
+    Ok(PolicyRequirement {
+        name: name.to_owned(),
+        requirement: policies
+            .get(name)
+            .ok_or_else(|| AuthorizationError::UnknownPolicy(name.to_owned()))?
+            .clone(),
+    })
+}
+
+type Policies = HashMap<String, Arc<dyn DynAuthorizationRequirement>>;
+
+pub struct AuthorizationService {
+    policies: RwLock<Arc<Policies>>,
+}
+
+impl AuthorizationService {
+    /// Atomically swaps the whole policy set; evaluations already running finish with the old policies.
+    /// Policies derived with [`Self::policy`] keep the version they were created from.
+    pub fn replace_policies(&self, builder: AuthorizationServiceBuilder) {
+        *self.policies.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(builder.policies);
+    }
+
+    fn policies(&self) -> Arc<Policies> {
+        self.policies.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub async fn authorize(&self, principal: &UserPrincipal, policy: &str) -> Result<(), AuthorizationError> {
+        self.evaluate_policy(principal, policy, RequestData::default()).await
+    }
+
+    /// Like [`Self::authorize`], but requirements also see the request's route parameters and headers.
+    pub fn authorize_request<'a>(
+        &'a self,
+        principal: &'a UserPrincipal,
+        policy: &'a str,
+        request: &impl Request,
+    ) -> impl Future<Output = Result<(), AuthorizationError>> + Send + 'a {
+        self.evaluate_policy(principal, policy, RequestData::from_request(request))
+    }
+
+    async fn evaluate_policy(
+        &self,
+        principal: &UserPrincipal,
+        policy: &str,
+        request: RequestData,
+    ) -> Result<(), AuthorizationError> {
+        let requirement = self
+            .policies()
+            .get(policy)
+            .ok_or_else(|| AuthorizationError::UnknownPolicy(policy.to_owned()))?
+            .clone();
+        Ok(requirement.evaluate(principal, &request).await?)
+    }
+
+    pub async fn authorize_requirement<R: AuthorizationRequirement>(
+        &self,
+        principal: &UserPrincipal,
+        requirement: &R,
+    ) -> Result<(), AuthorizationFailure> {
+        evaluate_requirement(requirement, principal, &RequestData::default()).await
+    }
+
+    /// For checks made from handlers, sharing loaded values with the policies that already ran, e.g. with
+    /// the [`AuthorizationCache`] request extension.
+    pub async fn authorize_requirement_with_cache<R: AuthorizationRequirement>(
+        &self,
+        principal: &UserPrincipal,
+        requirement: &R,
+        cache: &AuthorizationCache,
+    ) -> Result<(), AuthorizationFailure> {
+        let request = RequestData {
+            cache: cache.clone(),
+            ..RequestData::default()
+        };
+        evaluate_requirement(requirement, principal, &request).await
+    }
+
+    pub fn has_policy(&self, policy: &str) -> bool {
+        self.policies().contains_key(policy)
+    }
+
+    pub fn policy(&self, policy: &str) -> Result<PolicyRequirement, AuthorizationError> {
+        registered_policy(&self.policies(), policy)
+    }
+
+    /// A registered policy, ready to be passed to the authorization middleware.
+    pub fn boxed_policy<Handler: CompoundAuthenticationHandler>(
+        &self,
+        policy: &str,
+        auth_service: Arc<AuthenticationService<Handler>>,
+    ) -> Result<BoxedPolicy<Handler>, AuthorizationError> {
+        Ok(self.policy(policy)?.extend().boxed().build(auth_service))
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct AuthorizationServiceBuilder {
+    policies: HashMap<String, Arc<dyn DynAuthorizationRequirement>>,
+}
+
+impl AuthorizationServiceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_policy<R: AuthorizationRequirement>(
+        mut self,
+        name: String,
+        policy: AuthorizationPolicyBuilder<R>,
+    ) -> Self {
+        self.policies.insert(name, Arc::new(policy.requirement));
+        self
+    }
+
+    pub fn has_policy(&self, policy: &str) -> bool {
+        self.policies.contains_key(policy)
+    }
+
+    /// A policy added earlier, e.g. to derive `policy("Base").extend().require_role(...)` from it.
+    pub fn policy(&self, policy: &str) -> Result<PolicyRequirement, AuthorizationError> {
+        registered_policy(&self.policies, policy)
+    }
+
+    pub fn build(self) -> AuthorizationService {
+        AuthorizationService {
+            policies: RwLock::new(Arc::new(self.policies)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{ready, Ready};
+
+    use http::{HeaderMap, Method, StatusCode};
+
+    use super::*;
+    use crate::core::{
+        authentication::{
+            AuthenticationError, AuthenticationHandler, AuthenticationResult, AuthenticationServiceBuilder,
+        },
+        testing::TestRequest,
+    };
+
+    /// Authenticates requests with an `x-user` header as a user without roles.
+    struct HeaderHandler;
+
+    impl AuthenticationHandler for HeaderHandler {
+        type AuthFut = Ready<AuthenticationResult>;
+
+        type ChallengeFut = Ready<AuthResponse>;
+
+        type ForbidFut = Ready<AuthResponse>;
+
+        fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+            ready(match request.get_header(&http::HeaderName::from_static("x-user")) {
+                Some(_) => Ok(UserPrincipal {
+                    claims: Default::default(),
+                }),
+                None => Err(AuthenticationError::NoResult),
+            })
+        }
+
+        fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+            ready(status_response(StatusCode::UNAUTHORIZED))
+        }
+
+        fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+            ready(status_response(StatusCode::FORBIDDEN))
+        }
+    }
+
+    fn status_response(status_code: StatusCode) -> AuthResponse {
+        AuthResponse {
+            status_code,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        }
+    }
+
+    async fn authorize_report_only(headers: &[(&str, &str)]) -> Result<(), AuthResponse> {
+        let auth_service = AuthenticationServiceBuilder::new()
+            .add_authentication_handler("test", HeaderHandler)
+            .set_default_scheme("test")
+            .build()
+            .ok()
+            .unwrap();
+        let auth_service = Arc::new(auth_service);
+        let policy = AuthorizationPolicyBuilder::new()
+            .require_role("admin".to_owned())
+            .build(auth_service.clone())
+            .report_only(true);
+
+        let mut request = TestRequest::new(Method::GET, "/admin", headers);
+        auth_service.authenticate(&mut request).await;
+        policy.authorize(&mut request).await
+    }
+
+    #[tokio::test]
+    async fn report_only_lets_forbidden_request_through() {
+        assert!(authorize_report_only(&[("x-user", "alice")]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn report_only_still_challenges_anonymous_request() {
+        let response = authorize_report_only(&[]).await.err().unwrap();
+
+        assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,361 @@
+use std::{borrow::Cow, fmt::Display, sync::Arc};
+
+use http::Method;
+use percent_encoding::percent_decode_str;
+
+use crate::core::{
+    authentication::{AuthenticationService, CompoundAuthenticationHandler, SuccessAuthenticationResult},
+    authorization::{
+        ensure_authorization_cache, AuthorizationError, AuthorizationFailure, AuthorizationService, IsInRoleRequirement,
+    },
+    http::{AuthResponse, Request, RequestExtensions},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteAccess {
+    Anonymous,
+    Authenticated,
+    Role(String),
+    Policy(String),
+}
+
+impl Display for RouteAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RouteAccess::Anonymous => write!(f, "anonymous"),
+            RouteAccess::Authenticated => write!(f, "authenticated"),
+            RouteAccess::Role(role) => write!(f, "role {role}"),
+            RouteAccess::Policy(policy) => write!(f, "policy {policy}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// `*`, exactly one segment.
+    Any,
+    /// `**`, any number of segments including none.
+    AnyDepth,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutePattern {
+    source: String,
+    method: Option<Method>,
+    segments: Vec<Segment>,
+}
+
+impl RoutePattern {
+    /// Parses `"/admin/**"` or `"GET /public/*/file"`.
+    pub fn parse(pattern: &str) -> Result<Self, AuthorizationMapError> {
+        let (method, path) = match pattern.trim().split_once(' ') {
+            Some((method, path)) => (
+                Some(
+                    Method::from_bytes(method.as_bytes())
+                        .map_err(|_| AuthorizationMapError::InvalidPattern(pattern.to_owned()))?,
+                ),
+                path.trim(),
+            ),
+            None => (None, pattern.trim()),
+        };
+        let Some(path) = path.strip_prefix('/') else {
+            return Err(AuthorizationMapError::InvalidPattern(pattern.to_owned()));
+        };
+
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| match segment {
+                "*" => Segment::Any,
+                "**" => Segment::AnyDepth,
+                segment => Segment::Literal(segment.to_owned()),
+            })
+            .collect();
+
+        Ok(Self {
+            source: pattern.trim().to_owned(),
+            method,
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Matches the segments of `path` decoded the way the routers do, so `/%61dmin` matches `/admin`. An encoded
+    /// `/` stays part of its segment, and `.`/`..` segments are matched literally, as routers don't resolve them.
+    pub fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().is_some_and(|m| m != method) {
+            return false;
+        }
+
+        let path = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy())
+            .collect::<Vec<_>>();
+        segments_match(&self.segments, &path)
+    }
+}
+
+fn segments_match(pattern: &[Segment], path: &[Cow<'_, str>]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, _) => path.is_empty(),
+        (Some(Segment::AnyDepth), _) => (0..=path.len()).any(|skip| segments_match(&pattern[1..], &path[skip..])),
+        (Some(_), None) => false,
+        (Some(Segment::Any), Some(_)) => segments_match(&pattern[1..], &path[1..]),
+        (Some(Segment::Literal(literal)), Some(segment)) => {
+            literal.as_str() == segment && segments_match(&pattern[1..], &path[1..])
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthorizationMapError {
+    InvalidPattern(String),
+    UnknownPolicy { pattern: String, policy: String },
+}
+
+impl Display for AuthorizationMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthorizationMapError::InvalidPattern(pattern) => write!(f, "{pattern} is not a valid route pattern"),
+            AuthorizationMapError::UnknownPolicy { pattern, policy } => {
+                write!(f, "Route {pattern} uses policy {policy} which is not configured")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthorizationMapError {}
+
+/// A central table of which routes require which policy. Rules are checked in the order they were added
+/// and the first matching one applies; requests matching no rule use the fallback, which requires an
+/// authenticated user unless changed.
+pub struct AuthorizationMap<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    auth_service: Arc<AuthenticationService<Handler>>,
+    authorization_service: Arc<AuthorizationService>,
+    rules: Vec<(RoutePattern, RouteAccess)>,
+    fallback: RouteAccess,
+    not_found: Vec<RoutePattern>,
+}
+
+impl<Handler> AuthorizationMap<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub fn rules(&self) -> impl Iterator<Item = (&RoutePattern, &RouteAccess)> {
+        self.rules.iter().map(|(pattern, access)| (pattern, access))
+    }
+
+    pub fn fallback(&self) -> &RouteAccess {
+        &self.fallback
+    }
+
+    pub fn access_for(&self, method: &Method, path: &str) -> &RouteAccess {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(method, path))
+            .map_or(&self.fallback, |(_, access)| access)
+    }
+
+    pub async fn authorize(&self, request: &mut impl Request) -> Result<(), AuthResponse> {
+        let access = self.access_for(request.get_method(), request.get_uri().path());
+        self.authorize_access(request, access).await
+    }
+
+    /// Applies `access` regardless of the route, e.g. for access declared on the endpoint itself. Routes set to
+    /// respond with `404 Not Found` still do.
+    pub async fn authorize_access(&self, request: &mut impl Request, access: &RouteAccess) -> Result<(), AuthResponse> {
+        let result = self
+            .authorize_route(request, access)
+            .await
+            .map_err(|response| self.auth_service.problem_details(request, response));
+        let (method, path) = (request.get_method(), request.get_uri().path());
+        let result = match result {
+            Err(response) if self.not_found.iter().any(|pattern| pattern.matches(method, path)) => {
+                Err(response.into_not_found())
+            }
+            result => result,
+        };
+        result.map_err(|response| self.auth_service.cors_response(request, response))
+    }
+
+    async fn authorize_route(&self, request: &mut impl Request, access: &RouteAccess) -> Result<(), AuthResponse> {
+        if *access == RouteAccess::Anonymous {
+            return Ok(());
+        }
+
+        if let Some(response) = self.auth_service.layer_order_error(request) {
+            return Err(response);
+        }
+        self.auth_service.ensure_authenticated(request).await;
+        if let Some(response) = self.auth_service.maintenance_response(request) {
+            return Err(response);
+        }
+        ensure_authorization_cache(request);
+        let result = match (request.get_extensions().get::<SuccessAuthenticationResult>(), access) {
+            (None, _) => {
+                return Err(self
+                    .auth_service
+                    .challenge(None, request)
+                    .await
+                    .unwrap_or_else(|err| err.response()))
+            }
+            (Some(_), RouteAccess::Anonymous | RouteAccess::Authenticated) => Ok(()),
+            (Some(auth_result), RouteAccess::Role(role)) => {
+                self.authorization_service
+                    .authorize_requirement(&auth_result.principal, &IsInRoleRequirement(role.clone()))
+                    .await
+            }
+            (Some(auth_result), RouteAccess::Policy(policy)) => self
+                .authorization_service
+                .authorize_request(&auth_result.principal, policy, request)
+                .await
+                .map_err(|error| match error {
+                    AuthorizationError::Failed(failure) => failure,
+                    AuthorizationError::UnknownPolicy(_) => AuthorizationFailure {
+                        failed_explicitly: true,
+                        messages: vec![error.to_string()],
+                        ..AuthorizationFailure::default()
+                    },
+                }),
+        };
+
+        if let Err(failure) = result {
+            let response = self
+                .auth_service
+                .forbid(None, Some(&failure))
+                .await
+                .unwrap_or_else(|err| err.response());
+            request.get_extensions_mut().insert(failure);
+            return Err(response);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AuthorizationMapBuilder {
+    rules: Vec<(String, RouteAccess)>,
+    fallback: RouteAccess,
+    not_found: Vec<String>,
+}
+
+impl AuthorizationMapBuilder {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            fallback: RouteAccess::Authenticated,
+            not_found: Vec::new(),
+        }
+    }
+
+    pub fn route(mut self, pattern: impl Into<String>, access: RouteAccess) -> Self {
+        self.rules.push((pattern.into(), access));
+        self
+    }
+
+    pub fn anonymous(self, pattern: impl Into<String>) -> Self {
+        self.route(pattern, RouteAccess::Anonymous)
+    }
+
+    pub fn authenticated(self, pattern: impl Into<String>) -> Self {
+        self.route(pattern, RouteAccess::Authenticated)
+    }
+
+    pub fn role(self, pattern: impl Into<String>, role: impl Into<String>) -> Self {
+        self.route(pattern, RouteAccess::Role(role.into()))
+    }
+
+    pub fn policy(self, pattern: impl Into<String>, policy: impl Into<String>) -> Self {
+        self.route(pattern, RouteAccess::Policy(policy.into()))
+    }
+
+    pub fn fallback(self, fallback: RouteAccess) -> Self {
+        Self { fallback, ..self }
+    }
+
+    /// Answers challenged and forbidden requests to routes matching `pattern` with `404 Not Found`, hiding that
+    /// they exist. Applies whichever rule grants access to the route.
+    pub fn respond_not_found(mut self, pattern: impl Into<String>) -> Self {
+        self.not_found.push(pattern.into());
+        self
+    }
+
+    /// Fails on malformed patterns and on policies missing from `authorization_service`, so a typo in the
+    /// table is caught at startup rather than on the first request to that route.
+    pub fn build<Handler: CompoundAuthenticationHandler>(
+        self,
+        auth_service: Arc<AuthenticationService<Handler>>,
+        authorization_service: Arc<AuthorizationService>,
+    ) -> Result<AuthorizationMap<Handler>, AuthorizationMapError> {
+        let mut rules = Vec::with_capacity(self.rules.len());
+        for (pattern, access) in self.rules {
+            if let RouteAccess::Policy(policy) = &access {
+                if !authorization_service.has_policy(policy) {
+                    return Err(AuthorizationMapError::UnknownPolicy {
+                        pattern,
+                        policy: policy.clone(),
+                    });
+                }
+            }
+            rules.push((RoutePattern::parse(&pattern)?, access));
+        }
+        if let RouteAccess::Policy(policy) = &self.fallback {
+            if !authorization_service.has_policy(policy) {
+                return Err(AuthorizationMapError::UnknownPolicy {
+                    pattern: "fallback".to_owned(),
+                    policy: policy.clone(),
+                });
+            }
+        }
+
+        let not_found = self
+            .not_found
+            .iter()
+            .map(|pattern| RoutePattern::parse(pattern))
+            .collect::<Result<_, _>>()?;
+
+        Ok(AuthorizationMap {
+            auth_service,
+            authorization_service,
+            rules,
+            fallback: self.fallback,
+            not_found,
+        })
+    }
+}
+
+impl Default for AuthorizationMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encoded_path_matches_decoded_pattern() {
+        let pattern = RoutePattern::parse("/admin/**").unwrap();
+
+        assert!(pattern.matches(&Method::GET, "/%61dmin"));
+        assert!(pattern.matches(&Method::GET, "/%61%64min/users"));
+        assert!(!pattern.matches(&Method::GET, "/public/%61dmin"));
+    }
+
+    #[test]
+    fn encoded_slash_stays_in_its_segment() {
+        let pattern = RoutePattern::parse("/files/*").unwrap();
+
+        assert!(pattern.matches(&Method::GET, "/files/a%2Fb"));
+        assert!(!pattern.matches(&Method::GET, "/files/a/b"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait AuthCache: Send + Sync + 'static {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error>;
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<(), anyhow::Error>;
+
+    async fn set_if_absent(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<bool, anyhow::Error>;
+
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryAuthCache {
+    entries: Mutex<HashMap<String, (Vec<u8>, SystemTime)>>,
+}
+
+impl InMemoryAuthCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AuthCache for InMemoryAuthCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > SystemTime::now())
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<(), anyhow::Error> {
+        let now = SystemTime::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        entries.insert(key.to_owned(), (value, now + ttl));
+        Ok(())
+    }
+
+    async fn set_if_absent(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<bool, anyhow::Error> {
+        let now = SystemTime::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        if entries.contains_key(key) {
+            return Ok(false);
+        }
+
+        entries.insert(key.to_owned(), (value, now + ttl));
+        Ok(true)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.remove(key);
+        Ok(())
+    }
+}
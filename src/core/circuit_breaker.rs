@@ -0,0 +1,254 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode};
+
+use crate::core::{
+    health::{DependencyHealth, HealthReporter, HealthStatus},
+    http::AuthResponse,
+};
+
+/// What requests depending on the guarded component get while its circuit is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenCircuitPolicy {
+    /// Keep using the last data fetched successfully, e.g. cached signing keys.
+    #[default]
+    ServeCached,
+    /// Reject with `503 Service Unavailable` and a `Retry-After` header.
+    FailClosed,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerOptions {
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a single trial call is let through.
+    pub open_duration: Duration,
+    pub open_policy: OpenCircuitPolicy,
+}
+
+impl CircuitBreakerOptions {
+    pub fn new() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            open_policy: OpenCircuitPolicy::ServeCached,
+        }
+    }
+}
+
+impl Default for CircuitBreakerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitOpen {
+    pub name: String,
+    pub retry_after: Duration,
+}
+
+impl CircuitOpen {
+    pub fn response(&self) -> AuthResponse {
+        // Retry-After only takes whole seconds, so round up to not invite retries before the trial call.
+        let seconds = self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0);
+        AuthResponse {
+            status_code: StatusCode::SERVICE_UNAVAILABLE,
+            headers: HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from(seconds.max(1)))]),
+            body: Vec::new(),
+        }
+    }
+}
+
+impl Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Circuit for {} is open", self.name)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+struct CircuitStatus {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+    last_success: Option<SystemTime>,
+    last_error: Option<String>,
+}
+
+/// Stops calling a failing network dependency for a while. After `failure_threshold` consecutive failures
+/// the circuit opens and calls are rejected with [`CircuitOpen`] until `open_duration` passes; then one
+/// trial call is let through, closing the circuit on success and reopening it on failure.
+pub struct CircuitBreaker {
+    name: String,
+    options: CircuitBreakerOptions,
+    status: Mutex<CircuitStatus>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: impl Into<String>, options: CircuitBreakerOptions) -> Self {
+        Self {
+            name: name.into(),
+            options,
+            status: Mutex::new(CircuitStatus {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+                last_success: None,
+                last_error: None,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn options(&self) -> &CircuitBreakerOptions {
+        &self.options
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let status = self.lock_status();
+        match status.state {
+            CircuitState::Open if self.open_elapsed(&status) => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Returns the error to fail requests with when the circuit is open and the policy is
+    /// [`OpenCircuitPolicy::FailClosed`].
+    pub fn fail_closed_error(&self) -> Option<CircuitOpen> {
+        if self.options.open_policy != OpenCircuitPolicy::FailClosed {
+            return None;
+        }
+
+        let status = self.lock_status();
+        (status.state == CircuitState::Open && !self.open_elapsed(&status)).then(|| self.open_error(&status))
+    }
+
+    pub fn try_acquire(&self) -> Result<(), CircuitOpen> {
+        let mut status = self.lock_status();
+        match status.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if self.open_elapsed(&status) => {
+                status.state = CircuitState::HalfOpen;
+                status.trial_in_flight = true;
+                Ok(())
+            }
+            CircuitState::HalfOpen if !status.trial_in_flight => {
+                status.trial_in_flight = true;
+                Ok(())
+            }
+            CircuitState::Open | CircuitState::HalfOpen => Err(self.open_error(&status)),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut status = self.lock_status();
+        status.state = CircuitState::Closed;
+        status.consecutive_failures = 0;
+        status.opened_at = None;
+        status.trial_in_flight = false;
+        status.last_success = Some(SystemTime::now());
+        status.last_error = None;
+    }
+
+    pub fn record_failure(&self, error: &impl Display) {
+        let mut status = self.lock_status();
+        status.consecutive_failures += 1;
+        status.trial_in_flight = false;
+        status.last_error = Some(error.to_string());
+        if status.state == CircuitState::HalfOpen || status.consecutive_failures >= self.options.failure_threshold {
+            status.state = CircuitState::Open;
+            status.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Runs `call` unless the circuit is open and records its outcome. A call that is dropped before it
+    /// completes, e.g. on a timeout, records nothing but lets another call try the half-open circuit.
+    pub async fn call<T>(&self, call: impl Future<Output = Result<T, anyhow::Error>>) -> Result<T, anyhow::Error> {
+        self.try_acquire()?;
+        let guard = CallGuard { breaker: self };
+        let result = call.await;
+        std::mem::forget(guard);
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(err) => self.record_failure(err),
+        }
+
+        result
+    }
+
+    /// Releases the trial of a half-open circuit whose call didn't complete.
+    fn release_trial(&self) {
+        self.lock_status().trial_in_flight = false;
+    }
+
+    fn open_elapsed(&self, status: &CircuitStatus) -> bool {
+        status
+            .opened_at
+            .is_some_and(|opened_at| opened_at.elapsed() >= self.options.open_duration)
+    }
+
+    fn open_error(&self, status: &CircuitStatus) -> CircuitOpen {
+        let remaining = status
+            .opened_at
+            .map(|opened_at| self.options.open_duration.saturating_sub(opened_at.elapsed()))
+            .filter(|remaining| !remaining.is_zero());
+        CircuitOpen {
+            name: self.name.clone(),
+            // A half-open circuit waiting on its trial call has no deadline, so suggest a whole open period.
+            retry_after: remaining.unwrap_or(self.options.open_duration),
+        }
+    }
+
+    fn lock_status(&self) -> std::sync::MutexGuard<'_, CircuitStatus> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Releases the acquired call when the future running it is dropped before the outcome is recorded.
+struct CallGuard<'a> {
+    breaker: &'a CircuitBreaker,
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.breaker.release_trial();
+    }
+}
+
+impl HealthReporter for CircuitBreaker {
+    fn health(&self) -> DependencyHealth {
+        let state = self.state();
+        let status = self.lock_status();
+        DependencyHealth {
+            name: format!("circuit:{}", self.name),
+            status: match (state, self.options.open_policy) {
+                (CircuitState::Closed, _) => HealthStatus::Healthy,
+                (CircuitState::HalfOpen, _) | (CircuitState::Open, OpenCircuitPolicy::ServeCached) => {
+                    HealthStatus::Degraded
+                }
+                (CircuitState::Open, OpenCircuitPolicy::FailClosed) => HealthStatus::Unhealthy,
+            },
+            last_success: status.last_success,
+            latency: None,
+            error: status.last_error.clone(),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ORIGIN, VARY,
+        WWW_AUTHENTICATE,
+    },
+    HeaderName, HeaderValue,
+};
+
+use super::http::{AuthResponse, Request};
+
+/// CORS headers for the responses the authorization layers refuse requests with. These responses don't reach
+/// the CORS layer of the application, so without them browsers hide a challenge from scripts of an allowed
+/// origin behind an opaque network error.
+#[derive(Debug, Clone)]
+pub struct ChallengeCors {
+    allowed_origins: HashSet<String>,
+    allow_credentials: bool,
+    expose_headers: Vec<HeaderName>,
+}
+
+impl ChallengeCors {
+    /// No allowed origins, exposing `WWW-Authenticate`.
+    pub fn new() -> Self {
+        Self {
+            allowed_origins: HashSet::new(),
+            allow_credentials: false,
+            expose_headers: vec![WWW_AUTHENTICATE],
+        }
+    }
+
+    /// An origin such as `https://app.example.com`, compared exactly.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.insert(origin.into());
+        self
+    }
+
+    pub fn allow_credentials(self, allow_credentials: bool) -> Self {
+        Self {
+            allow_credentials,
+            ..self
+        }
+    }
+
+    pub fn expose_headers(self, expose_headers: Vec<HeaderName>) -> Self {
+        Self { expose_headers, ..self }
+    }
+
+    /// Adds the CORS headers to `response` when the request comes from an allowed origin.
+    pub fn apply(&self, request: &impl Request, mut response: AuthResponse) -> AuthResponse {
+        let Some(origin) = request.get_header(&ORIGIN) else {
+            return response;
+        };
+        if !origin
+            .to_str()
+            .is_ok_and(|origin| self.allowed_origins.contains(origin))
+        {
+            return response;
+        }
+
+        response.headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        response.headers.append(VARY, HeaderValue::from(ORIGIN));
+        if self.allow_credentials {
+            response
+                .headers
+                .insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        let expose_headers = self
+            .expose_headers
+            .iter()
+            .map(HeaderName::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Ok(expose_headers) = HeaderValue::try_from(expose_headers) {
+            if !expose_headers.is_empty() {
+                response.headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers);
+            }
+        }
+
+        response
+    }
+}
+
+impl Default for ChallengeCors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
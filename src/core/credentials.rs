@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+
+use super::principal::UserPrincipal;
+
+#[async_trait]
+pub trait CredentialValidator: Send + Sync + 'static {
+    async fn validate(&self, username: &str, password: &str) -> Result<Option<UserPrincipal>, anyhow::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockoutStatus {
+    Unlocked,
+    LockedUntil(SystemTime),
+}
+
+#[async_trait]
+pub trait LockoutStore: Send + Sync + 'static {
+    async fn status(&self, identity: &str) -> Result<LockoutStatus, anyhow::Error>;
+
+    async fn record_failure(&self, identity: &str) -> Result<LockoutStatus, anyhow::Error>;
+
+    async fn record_success(&self, identity: &str) -> Result<(), anyhow::Error>;
+
+    async fn unlock(&self, identity: &str) -> Result<(), anyhow::Error>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutOptions {
+    pub max_failed_attempts: u32,
+    pub lockout_duration: Duration,
+}
+
+impl Default for LockoutOptions {
+    fn default() -> Self {
+        Self {
+            max_failed_attempts: 5,
+            lockout_duration: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LockoutEntry {
+    failed_attempts: u32,
+    locked_until: Option<SystemTime>,
+}
+
+pub struct InMemoryLockoutStore {
+    options: LockoutOptions,
+    entries: Mutex<HashMap<String, LockoutEntry>>,
+}
+
+impl InMemoryLockoutStore {
+    pub fn new(options: LockoutOptions) -> Self {
+        Self {
+            options,
+            entries: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LockoutStore for InMemoryLockoutStore {
+    async fn status(&self, identity: &str) -> Result<LockoutStatus, anyhow::Error> {
+        let now = SystemTime::now();
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(match entries.get(identity).and_then(|e| e.locked_until) {
+            Some(locked_until) if locked_until > now => LockoutStatus::LockedUntil(locked_until),
+            _ => LockoutStatus::Unlocked,
+        })
+    }
+
+    async fn record_failure(&self, identity: &str) -> Result<LockoutStatus, anyhow::Error> {
+        let now = SystemTime::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = entries.entry(identity.to_owned()).or_default();
+        if let Some(locked_until) = entry.locked_until {
+            if locked_until > now {
+                return Ok(LockoutStatus::LockedUntil(locked_until));
+            }
+
+            entry.locked_until = None;
+        }
+
+        entry.failed_attempts += 1;
+        if entry.failed_attempts < self.options.max_failed_attempts {
+            return Ok(LockoutStatus::Unlocked);
+        }
+
+        let locked_until = now + self.options.lockout_duration;
+        entry.failed_attempts = 0;
+        entry.locked_until = Some(locked_until);
+        Ok(LockoutStatus::LockedUntil(locked_until))
+    }
+
+    async fn record_success(&self, identity: &str) -> Result<(), anyhow::Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.remove(identity);
+        Ok(())
+    }
+
+    async fn unlock(&self, identity: &str) -> Result<(), anyhow::Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.remove(identity);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct AccountLockedOut {
+    pub locked_until: SystemTime,
+}
+
+impl Display for AccountLockedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Account is locked out")
+    }
+}
+
+impl std::error::Error for AccountLockedOut {}
+
+pub struct LockoutCredentialValidator {
+    pub validator: Arc<dyn CredentialValidator>,
+    pub lockout_store: Arc<dyn LockoutStore>,
+}
+
+#[async_trait]
+impl CredentialValidator for LockoutCredentialValidator {
+    async fn validate(&self, username: &str, password: &str) -> Result<Option<UserPrincipal>, anyhow::Error> {
+        if let LockoutStatus::LockedUntil(locked_until) = self.lockout_store.status(username).await? {
+            return Err(AccountLockedOut { locked_until }.into());
+        }
+
+        match self.validator.validate(username, password).await? {
+            Some(principal) => {
+                self.lockout_store.record_success(username).await?;
+                Ok(Some(principal))
+            }
+            None => match self.lockout_store.record_failure(username).await? {
+                LockoutStatus::LockedUntil(locked_until) => Err(AccountLockedOut { locked_until }.into()),
+                LockoutStatus::Unlocked => Ok(None),
+            },
+        }
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,335 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Uri};
+
+use crate::core::{
+    authentication::{
+        AuthenticateOptions, AuthenticationProperties, AuthenticationResult, BearerToken,
+        CompoundAuthenticationHandler, CompoundAuthenticationResult, ExpiredCredential, InitializationFailure,
+        SchemeInfo, SignInResult,
+    },
+    authorization::AuthorizationFailure,
+    http::{AuthResponse, PeerCertificate, Request, RequestBody, RequestExtensions, RouteParams},
+    principal::UserPrincipal,
+    response_headers::PendingResponseHeaders,
+};
+
+pub type DynFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// An object-safe [`CompoundAuthenticationHandler`]. Handlers behind `Arc<dyn DynCompoundAuthenticationHandler>`
+/// are compiled once for [`DynRequest`] instead of once per framework request type and handler tuple.
+pub trait DynCompoundAuthenticationHandler: Send + Sync + 'static {
+    fn authenticate(
+        &self,
+        request: &mut DynRequest<'_>,
+        options: AuthenticateOptions,
+    ) -> DynFuture<CompoundAuthenticationResult>;
+
+    fn authenticate_scheme(
+        &self,
+        scheme: &str,
+        request: &mut DynRequest<'_>,
+    ) -> DynFuture<Option<AuthenticationResult>>;
+
+    fn challenge(&self, scheme: &str, request: &DynRequest<'_>) -> DynFuture<Option<AuthResponse>>;
+
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> DynFuture<Option<AuthResponse>>;
+
+    fn sign_in(
+        &self,
+        scheme: &str,
+        user: &UserPrincipal,
+        properties: &AuthenticationProperties,
+    ) -> DynFuture<Option<SignInResult>>;
+
+    fn sign_out(&self, scheme: &str, request: &DynRequest<'_>) -> DynFuture<Option<AuthResponse>>;
+
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>);
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>);
+}
+
+impl<H> DynCompoundAuthenticationHandler for H
+where
+    H: CompoundAuthenticationHandler,
+    H::AuthFut: Send + 'static,
+    H::ChallengeFut: Send + 'static,
+    H::ForbidFut: Send + 'static,
+    H::SignInFut: Send + 'static,
+    H::SignOutFut: Send + 'static,
+    H::SchemeAuthFut: Send + 'static,
+{
+    fn authenticate(
+        &self,
+        request: &mut DynRequest<'_>,
+        options: AuthenticateOptions,
+    ) -> DynFuture<CompoundAuthenticationResult> {
+        Box::pin(CompoundAuthenticationHandler::authenticate(self, request, options))
+    }
+
+    fn authenticate_scheme(
+        &self,
+        scheme: &str,
+        request: &mut DynRequest<'_>,
+    ) -> DynFuture<Option<AuthenticationResult>> {
+        Box::pin(CompoundAuthenticationHandler::authenticate_scheme(
+            self, scheme, request,
+        ))
+    }
+
+    fn challenge(&self, scheme: &str, request: &DynRequest<'_>) -> DynFuture<Option<AuthResponse>> {
+        Box::pin(CompoundAuthenticationHandler::challenge(self, scheme, request))
+    }
+
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> DynFuture<Option<AuthResponse>> {
+        Box::pin(CompoundAuthenticationHandler::forbid(self, scheme, failure))
+    }
+
+    fn sign_in(
+        &self,
+        scheme: &str,
+        user: &UserPrincipal,
+        properties: &AuthenticationProperties,
+    ) -> DynFuture<Option<SignInResult>> {
+        Box::pin(CompoundAuthenticationHandler::sign_in(self, scheme, user, properties))
+    }
+
+    fn sign_out(&self, scheme: &str, request: &DynRequest<'_>) -> DynFuture<Option<AuthResponse>> {
+        Box::pin(CompoundAuthenticationHandler::sign_out(self, scheme, request))
+    }
+
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>) {
+        CompoundAuthenticationHandler::collect_schemes(self, schemes)
+    }
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>) {
+        CompoundAuthenticationHandler::validate_configuration(self, failures)
+    }
+}
+
+impl CompoundAuthenticationHandler for Arc<dyn DynCompoundAuthenticationHandler> {
+    type AuthFut = DynFuture<CompoundAuthenticationResult>;
+
+    type ChallengeFut = DynFuture<Option<AuthResponse>>;
+
+    type ForbidFut = DynFuture<Option<AuthResponse>>;
+
+    type SignInFut = DynFuture<Option<SignInResult>>;
+
+    type SignOutFut = DynFuture<Option<AuthResponse>>;
+
+    type SchemeAuthFut = DynFuture<Option<AuthenticationResult>>;
+
+    fn authenticate(&self, request: &mut impl Request, options: AuthenticateOptions) -> Self::AuthFut {
+        DynRequest::scope(request, |request| self.as_ref().authenticate(request, options))
+    }
+
+    fn authenticate_scheme(&self, scheme: &str, request: &mut impl Request) -> Self::SchemeAuthFut {
+        DynRequest::scope(request, |request| self.as_ref().authenticate_scheme(scheme, request))
+    }
+
+    fn challenge(&self, scheme: &str, request: &impl Request) -> Self::ChallengeFut {
+        self.as_ref().challenge(scheme, &DynRequest::shared(request))
+    }
+
+    fn forbid(&self, scheme: &str, failure: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        self.as_ref().forbid(scheme, failure)
+    }
+
+    fn sign_in(&self, scheme: &str, user: &UserPrincipal, properties: &AuthenticationProperties) -> Self::SignInFut {
+        self.as_ref().sign_in(scheme, user, properties)
+    }
+
+    fn sign_out(&self, scheme: &str, request: &impl Request) -> Self::SignOutFut {
+        self.as_ref().sign_out(scheme, &DynRequest::shared(request))
+    }
+
+    fn collect_schemes(&self, schemes: &mut Vec<SchemeInfo>) {
+        self.as_ref().collect_schemes(schemes)
+    }
+
+    fn validate_configuration(&self, failures: &mut Vec<InitializationFailure>) {
+        self.as_ref().validate_configuration(failures)
+    }
+}
+
+/// The request type dyn handlers see. Extensions can't be looked up by type through an erased request, so
+/// handlers get their own extensions, kept on the framework request between `authenticate` and `challenge`.
+/// [`RequestBody`] and [`PendingResponseHeaders`] are copied in before authentication, [`ExpiredCredential`] and
+/// `NegotiateMutualAuthToken` are copied out after it;
+/// anything else a handler inserts is only visible to dyn handlers.
+pub struct DynRequest<'a> {
+    request: &'a dyn RequestParts,
+    extensions: Arc<Mutex<http::Extensions>>,
+}
+
+#[derive(Clone)]
+struct DynExtensions(Arc<Mutex<http::Extensions>>);
+
+impl<'a> DynRequest<'a> {
+    /// For calls that don't authenticate, with the extensions dyn handlers kept on the request.
+    fn shared<R: Request>(request: &'a R) -> Self {
+        let extensions = request
+            .get_extensions()
+            .get::<DynExtensions>()
+            .map(|e| e.0.clone())
+            .unwrap_or_default();
+        DynRequest { request, extensions }
+    }
+
+    fn scope<R: Request, T>(request: &mut R, f: impl FnOnce(&mut DynRequest<'_>) -> T) -> T {
+        let extensions = match request.get_extensions().get::<DynExtensions>() {
+            Some(extensions) => extensions.0.clone(),
+            None => Arc::default(),
+        };
+        request.get_extensions_mut().insert(DynExtensions(extensions.clone()));
+
+        {
+            let mut dyn_extensions = extensions.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(body) = request.get_extensions().get::<RequestBody>() {
+                dyn_extensions.insert(body.clone());
+            }
+            if let Some(headers) = request.get_extensions().get::<PendingResponseHeaders>() {
+                dyn_extensions.insert(headers.clone());
+            }
+        }
+
+        let result = f(&mut DynRequest {
+            request: &*request,
+            extensions: extensions.clone(),
+        });
+
+        let expired = extensions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get::<ExpiredCredential>()
+            .cloned();
+        if let Some(expired) = expired {
+            request.get_extensions_mut().insert(expired);
+        }
+        let token = extensions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get::<BearerToken>()
+            .cloned();
+        if let Some(token) = token {
+            request.get_extensions_mut().insert(token);
+        }
+
+        #[cfg(feature = "negotiate")]
+        {
+            let token = extensions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get::<crate::negotiate::NegotiateMutualAuthToken>()
+                .cloned();
+            if let Some(token) = token {
+                request.get_extensions_mut().insert(token);
+            }
+        }
+
+        result
+    }
+}
+
+impl Request for DynRequest<'_> {
+    type RequestExtensions = http::Extensions;
+
+    type RequestExtensionsDeref<'b>
+        = MutexGuard<'b, http::Extensions>
+    where
+        Self: 'b;
+
+    type RequestExtensionsDerefMut<'b>
+        = MutexGuard<'b, http::Extensions>
+    where
+        Self: 'b;
+
+    fn get_method(&self) -> &Method {
+        self.request.method()
+    }
+
+    fn get_uri(&self) -> &Uri {
+        self.request.uri()
+    }
+
+    fn get_header(&self, header: &HeaderName) -> Option<&HeaderValue> {
+        self.request.header(header)
+    }
+
+    fn get_headers(&self) -> HeaderMap {
+        self.request.headers()
+    }
+
+    fn get_peer_certificate(&self) -> Option<PeerCertificate> {
+        self.request.peer_certificate()
+    }
+
+    fn get_peer_address(&self) -> Option<SocketAddr> {
+        self.request.peer_address()
+    }
+
+    fn get_route_params(&self) -> RouteParams {
+        self.request.route_params()
+    }
+
+    fn get_extensions(&self) -> Self::RequestExtensionsDeref<'_> {
+        self.extensions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_extensions_mut(&mut self) -> Self::RequestExtensionsDerefMut<'_> {
+        self.extensions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The object-safe part of [`Request`].
+trait RequestParts {
+    fn method(&self) -> &Method;
+
+    fn uri(&self) -> &Uri;
+
+    fn header(&self, header: &HeaderName) -> Option<&HeaderValue>;
+
+    fn headers(&self) -> HeaderMap;
+
+    fn peer_certificate(&self) -> Option<PeerCertificate>;
+
+    fn peer_address(&self) -> Option<SocketAddr>;
+
+    fn route_params(&self) -> RouteParams;
+}
+
+impl<R: Request> RequestParts for R {
+    fn method(&self) -> &Method {
+        self.get_method()
+    }
+
+    fn uri(&self) -> &Uri {
+        self.get_uri()
+    }
+
+    fn header(&self, header: &HeaderName) -> Option<&HeaderValue> {
+        self.get_header(header)
+    }
+
+    fn headers(&self) -> HeaderMap {
+        self.get_headers()
+    }
+
+    fn peer_certificate(&self) -> Option<PeerCertificate> {
+        self.get_peer_certificate()
+    }
+
+    fn peer_address(&self) -> Option<SocketAddr> {
+        self.get_peer_address()
+    }
+
+    fn route_params(&self) -> RouteParams {
+        self.get_route_params()
+    }
+}
@@ -0,0 +1,117 @@
+use std::{future::Future, marker::PhantomData, ops::Deref, pin::Pin, sync::Arc};
+
+use crate::core::{
+    authentication::CompoundAuthenticationHandler,
+    authorization_map::{AuthorizationMap, RouteAccess},
+    http::AuthResponse,
+    principal::UserPrincipal,
+};
+
+/// Access declared on a handler, usually generated by the `#[authorize]` and `#[allow_anonymous]` attributes.
+pub trait EndpointMetadata: Send + Sync + 'static {
+    fn access() -> RouteAccess;
+}
+
+/// Extractor that enforces `M`'s access before the handler runs. It needs an [`EndpointAuthorization`]
+/// extension, which the authorization map layers insert.
+pub struct EndpointAuthorized<M: EndpointMetadata>(pub(crate) PhantomData<fn() -> M>);
+
+pub trait RoleName: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+/// Declares a [`RoleName`] type for [`InRole`]: `role!(pub Admin = "admin");`.
+#[macro_export]
+macro_rules! role {
+    ($vis:vis $name:ident = $role:literal) => {
+        $vis struct $name;
+
+        impl $crate::core::endpoint::RoleName for $name {
+            const NAME: &'static str = $role;
+        }
+    };
+}
+
+/// Extractor for the current user, rejecting requests from users without role `R` with the forbid response
+/// of the default scheme. Like [`EndpointAuthorized`], it relies on the authorization map layers.
+pub struct InRole<R: RoleName>(pub UserPrincipal, pub PhantomData<fn() -> R>);
+
+impl<R: RoleName> InRole<R> {
+    pub fn into_inner(self) -> UserPrincipal {
+        self.0
+    }
+}
+
+impl<R: RoleName> Deref for InRole<R> {
+    type Target = UserPrincipal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type EndpointAuthorizeFut<'a> = Pin<Box<dyn Future<Output = Result<(), AuthResponse>> + Send + 'a>>;
+
+#[cfg(feature = "actix")]
+pub type LocalEndpointAuthorizeFut<'a> = Pin<Box<dyn Future<Output = Result<(), AuthResponse>> + 'a>>;
+
+pub trait EndpointAuthorizer: Send + Sync + 'static {
+    #[cfg(feature = "tower")]
+    fn authorize_http<'a>(
+        &'a self,
+        request: &'a mut http::Request<()>,
+        access: RouteAccess,
+    ) -> EndpointAuthorizeFut<'a>;
+
+    #[cfg(feature = "actix")]
+    fn authorize_actix<'a>(
+        &'a self,
+        request: &'a mut actix_web::dev::ServiceRequest,
+        access: RouteAccess,
+    ) -> LocalEndpointAuthorizeFut<'a>;
+}
+
+impl<Handler> EndpointAuthorizer for AuthorizationMap<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+    Handler::AuthFut: Send,
+    Handler::ChallengeFut: Send,
+    Handler::ForbidFut: Send,
+{
+    #[cfg(feature = "tower")]
+    fn authorize_http<'a>(
+        &'a self,
+        request: &'a mut http::Request<()>,
+        access: RouteAccess,
+    ) -> EndpointAuthorizeFut<'a> {
+        Box::pin(async move { self.authorize_access(request, &access).await })
+    }
+
+    #[cfg(feature = "actix")]
+    fn authorize_actix<'a>(
+        &'a self,
+        request: &'a mut actix_web::dev::ServiceRequest,
+        access: RouteAccess,
+    ) -> LocalEndpointAuthorizeFut<'a> {
+        Box::pin(async move { self.authorize_access(request, &access).await })
+    }
+}
+
+#[derive(Clone)]
+pub struct EndpointAuthorization(pub Arc<dyn EndpointAuthorizer>);
+
+/// Rejects requests reaching the extractors without [`EndpointAuthorization`], which is a misconfiguration rather
+/// than the client's fault. `hint` tells how to add it in the framework at hand.
+#[cfg(any(feature = "axum", feature = "actix"))]
+pub(crate) fn missing_endpoint_authorization(hint: &str) -> AuthResponse {
+    #[cfg(feature = "tracing")]
+    tracing::error!("EndpointAuthorization extension is missing, {hint}");
+    #[cfg(not(feature = "tracing"))]
+    let _ = hint;
+
+    AuthResponse {
+        status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
+        headers: http::HeaderMap::default(),
+        body: Vec::new(),
+    }
+}
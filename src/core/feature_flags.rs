@@ -0,0 +1,118 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+
+use super::{
+    authorization::{AuthorizationHandlerContext, AuthorizationRequirement},
+    principal::{claim_types, UserPrincipal},
+};
+
+#[async_trait]
+pub trait FlagProvider: Send + Sync + 'static {
+    async fn is_enabled(&self, flag: &str, principal: &UserPrincipal) -> Result<bool, anyhow::Error>;
+}
+
+/// Who a flag of a [`StaticFlagProvider`] is enabled for.
+#[derive(Debug, Clone, Default)]
+pub struct FlagTargets {
+    pub everyone: bool,
+    /// Values of the `sub` claim.
+    pub subjects: HashSet<String>,
+    /// Segments of users, as roles.
+    pub roles: Vec<String>,
+}
+
+/// Flags fixed at startup, e.g. from config. Unknown flags are disabled.
+#[derive(Debug, Clone, Default)]
+pub struct StaticFlagProvider {
+    flags: HashMap<String, FlagTargets>,
+}
+
+impl StaticFlagProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn flag(mut self, flag: impl Into<String>, targets: FlagTargets) -> Self {
+        self.flags.insert(flag.into(), targets);
+        self
+    }
+}
+
+#[async_trait]
+impl FlagProvider for StaticFlagProvider {
+    async fn is_enabled(&self, flag: &str, principal: &UserPrincipal) -> Result<bool, anyhow::Error> {
+        let Some(targets) = self.flags.get(flag) else {
+            return Ok(false);
+        };
+
+        Ok(targets.everyone
+            || principal
+                .claim_strs(claim_types::SUBJECT)
+                .any(|subject| targets.subjects.contains(subject))
+            || targets.roles.iter().any(|role| principal.is_in_role(role)))
+    }
+}
+
+/// Succeeds when the flag is enabled for the user, locking endpoints of unreleased features to flagged users.
+/// Flags are cached in the request's [`AuthorizationCache`](super::authorization::AuthorizationCache), so
+/// several policies checking the same flag ask the provider once.
+pub struct FeatureFlagRequirement<T: FlagProvider> {
+    provider: Arc<T>,
+    flag: String,
+}
+
+impl<T: FlagProvider> FeatureFlagRequirement<T> {
+    pub fn new(provider: Arc<T>, flag: impl Into<String>) -> Self {
+        Self {
+            provider,
+            flag: flag.into(),
+        }
+    }
+}
+
+impl<T: FlagProvider> Clone for FeatureFlagRequirement<T> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            flag: self.flag.clone(),
+        }
+    }
+}
+
+impl<T: FlagProvider> AuthorizationRequirement for FeatureFlagRequirement<T> {
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("FeatureFlag({})", self.flag))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        Box::pin(async move {
+            let cache_key = format!("flag:{}", self.flag);
+            let is_enabled = match context.cache().get::<bool>(&cache_key) {
+                Some(is_enabled) => is_enabled,
+                None => match self.provider.is_enabled(&self.flag, context.principal()).await {
+                    Ok(is_enabled) => {
+                        context.cache().insert(cache_key, is_enabled);
+                        is_enabled
+                    }
+                    Err(err) => {
+                        context.fail_with_message(format!("Failed to check feature flag {}: {err}", self.flag));
+                        return;
+                    }
+                },
+            };
+
+            if is_enabled {
+                context.succeed(&self.name());
+            }
+        })
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures::{Future, TryFuture};
+use futures::Future;
 use pin_project::pin_project;
 
 enum SelectSeqState {
@@ -12,36 +12,59 @@ enum SelectSeqState {
 }
 
 #[pin_project]
-pub struct SelectSeqOk<Fut1, Fut2> {
+pub struct SelectSeqOk<Fut1, Fut2, T, E> {
     #[pin]
     fut1: Fut1,
     #[pin]
     fut2: Fut2,
-    state: SelectSeqState,
+    result1: Option<Result<T, Vec<E>>>,
+    result2: Option<Result<T, Vec<E>>>,
+    stop_on_error: bool,
+    concurrent: bool,
 }
 
-impl<Fut1, Fut2> Future for SelectSeqOk<Fut1, Fut2>
+impl<T, E, Fut1, Fut2> Future for SelectSeqOk<Fut1, Fut2, T, E>
 where
-    Fut1: TryFuture<Ok = Fut2::Ok, Error = Fut2::Error>,
-    Fut2: TryFuture,
+    Fut1: Future<Output = Result<T, Vec<E>>>,
+    Fut2: Future<Output = Result<T, Vec<E>>>,
 {
-    type Output = Result<Fut1::Ok, Fut1::Error>;
+    type Output = Result<T, Vec<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        match this.state {
-            SelectSeqState::PollFirst => match this.fut1.try_poll(cx) {
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(result) => {
-                    if result.is_ok() {
-                        Poll::Ready(result)
-                    } else {
-                        *this.state = SelectSeqState::PollSecond;
-                        this.fut2.try_poll(cx)
-                    }
+        if this.result1.is_none() {
+            if let Poll::Ready(result) = this.fut1.poll(cx) {
+                *this.result1 = Some(result);
+            }
+        }
+
+        let needs_second = match this.result1 {
+            Some(Ok(_)) => false,
+            Some(Err(errors)) => !*this.stop_on_error || errors.is_empty(),
+            None => *this.concurrent,
+        };
+        if this.result2.is_none() && needs_second {
+            if let Poll::Ready(result) = this.fut2.poll(cx) {
+                *this.result2 = Some(result);
+            }
+        }
+
+        // The first future always has priority, so a result of the second one is only used after the first failed.
+        match this.result1.take() {
+            Some(Ok(value)) => Poll::Ready(Ok(value)),
+            Some(Err(errors)) if *this.stop_on_error && !errors.is_empty() => Poll::Ready(Err(errors)),
+            Some(Err(mut errors)) => match this.result2.take() {
+                Some(Ok(value)) => Poll::Ready(Ok(value)),
+                Some(Err(errors2)) => {
+                    errors.extend(errors2);
+                    Poll::Ready(Err(errors))
+                }
+                None => {
+                    *this.result1 = Some(Err(errors));
+                    Poll::Pending
                 }
             },
-            SelectSeqState::PollSecond => this.fut2.try_poll(cx),
+            None => Poll::Pending,
         }
     }
 }
@@ -82,36 +105,44 @@ where
 }
 
 #[pin_project]
-pub struct MergeBoolAnd<Fut> {
+pub struct MergeUnit<Fut> {
     #[pin]
     fut: Fut,
 }
 
-impl<Fut> Future for MergeBoolAnd<Fut>
+impl<Fut> Future for MergeUnit<Fut>
 where
-    Fut: Future<Output = (bool, bool)>,
+    Fut: Future<Output = ((), ())>,
 {
-    type Output = bool;
+    type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
         match this.fut.poll(cx) {
             Poll::Pending => Poll::Pending,
-            Poll::Ready((b1, b2)) => Poll::Ready(b1 && b2),
+            Poll::Ready(((), ())) => Poll::Ready(()),
         }
     }
 }
 
-pub fn select_seq_ok<Fut1, Fut2>(fut1: Fut1, fut2: Fut2) -> SelectSeqOk<Fut1, Fut2>
+pub fn select_seq_ok<T, E, Fut1, Fut2>(
+    fut1: Fut1,
+    fut2: Fut2,
+    stop_on_error: bool,
+    concurrent: bool,
+) -> SelectSeqOk<Fut1, Fut2, T, E>
 where
-    Fut1: TryFuture<Ok = Fut2::Ok, Error = Fut2::Error>,
-    Fut2: TryFuture,
+    Fut1: Future<Output = Result<T, Vec<E>>>,
+    Fut2: Future<Output = Result<T, Vec<E>>>,
 {
     SelectSeqOk {
         fut1,
         fut2,
-        state: SelectSeqState::PollFirst,
+        result1: None,
+        result2: None,
+        stop_on_error,
+        concurrent,
     }
 }
 
@@ -127,9 +158,9 @@ where
     }
 }
 
-pub fn merge_bool_and<Fut>(fut: Fut) -> MergeBoolAnd<Fut>
+pub fn merge_unit<Fut>(fut: Fut) -> MergeUnit<Fut>
 where
-    Fut: Future<Output = (bool, bool)>,
+    Fut: Future<Output = ((), ())>,
 {
-    MergeBoolAnd { fut }
+    MergeUnit { fut }
 }
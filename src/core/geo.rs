@@ -0,0 +1,123 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    future::{ready, Ready},
+};
+
+use http::HeaderName;
+
+use super::authorization::{AuthorizationHandlerContext, AuthorizationRequirement};
+
+pub const CF_IP_COUNTRY_HEADER: HeaderName = HeaderName::from_static("cf-ipcountry");
+pub const GEO_COUNTRY_HEADER: HeaderName = HeaderName::from_static("x-geo-country");
+
+/// Which source of the country is consulted first; the other is used when it has none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CountryPrecedence {
+    /// The country the request comes from according to the CDN, so a user can't pick it.
+    #[default]
+    Header,
+    Claim,
+}
+
+/// Restricts requests by country, e.g. for export-controlled endpoints. The country comes from a claim of the
+/// user or from headers set by a CDN, which must strip them from client requests. Countries are compared
+/// case-insensitively, and requests whose country is unknown are refused.
+#[derive(Clone)]
+pub struct CountryRequirement {
+    claim_type: Option<String>,
+    headers: Vec<HeaderName>,
+    precedence: CountryPrecedence,
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+}
+
+impl CountryRequirement {
+    /// Reads the `country` claim and the [`CF_IP_COUNTRY_HEADER`] and [`GEO_COUNTRY_HEADER`] headers, allowing
+    /// every country.
+    pub fn new() -> Self {
+        Self {
+            claim_type: Some("country".to_owned()),
+            headers: vec![CF_IP_COUNTRY_HEADER, GEO_COUNTRY_HEADER],
+            precedence: CountryPrecedence::default(),
+            allow: HashSet::new(),
+            deny: HashSet::new(),
+        }
+    }
+
+    pub fn claim(self, claim_type: Option<String>) -> Self {
+        Self { claim_type, ..self }
+    }
+
+    /// Headers in the order they're looked at.
+    pub fn headers(self, headers: Vec<HeaderName>) -> Self {
+        Self { headers, ..self }
+    }
+
+    pub fn precedence(self, precedence: CountryPrecedence) -> Self {
+        Self { precedence, ..self }
+    }
+
+    /// Only these countries are allowed. Without an allow list every country that isn't denied is.
+    pub fn allow<C: AsRef<str>>(mut self, countries: impl IntoIterator<Item = C>) -> Self {
+        self.allow.extend(
+            countries
+                .into_iter()
+                .map(|country| country.as_ref().to_ascii_uppercase()),
+        );
+        self
+    }
+
+    /// These countries are refused even when they're allowed.
+    pub fn deny<C: AsRef<str>>(mut self, countries: impl IntoIterator<Item = C>) -> Self {
+        self.deny.extend(
+            countries
+                .into_iter()
+                .map(|country| country.as_ref().to_ascii_uppercase()),
+        );
+        self
+    }
+
+    fn claim_country(&self, context: &AuthorizationHandlerContext<'_>) -> Option<String> {
+        let claim_type = self.claim_type.as_deref()?;
+        context.principal().claim_strs(claim_type).next().map(str::to_owned)
+    }
+
+    fn header_country(&self, context: &AuthorizationHandlerContext<'_>) -> Option<String> {
+        self.headers
+            .iter()
+            .find_map(|header| context.headers().get(header)?.to_str().ok())
+            .map(str::to_owned)
+    }
+}
+
+impl Default for CountryRequirement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthorizationRequirement for CountryRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Country")
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let country = match self.precedence {
+            CountryPrecedence::Header => self.header_country(context).or_else(|| self.claim_country(context)),
+            CountryPrecedence::Claim => self.claim_country(context).or_else(|| self.header_country(context)),
+        };
+        let Some(country) = country.map(|country| country.trim().to_ascii_uppercase()) else {
+            context.fail_with_message("Request's country is unknown");
+            return ready(());
+        };
+
+        if (self.allow.is_empty() || self.allow.contains(&country)) && !self.deny.contains(&country) {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
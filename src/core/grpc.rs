@@ -0,0 +1,211 @@
+use http::{
+    header::{ACCESS_CONTROL_EXPOSE_HEADERS, CONTENT_TYPE},
+    HeaderName, HeaderValue, Method, StatusCode,
+};
+
+use super::http::{AuthResponse, Request};
+
+const GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+const GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+const CONNECT_PROTOCOL_VERSION: HeaderName = HeaderName::from_static("connect-protocol-version");
+
+/// RPC protocols whose clients can't make sense of a bare 401/403 and expect the failure in their own format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcProtocol {
+    /// Plain gRPC, as served by tonic.
+    Grpc { content_type: HeaderValue },
+    /// gRPC-Web, binary or base64 (`-text`) encoded, as sent by browsers through a grpc-web proxy.
+    GrpcWeb { content_type: HeaderValue },
+    /// A Connect unary call, including `GET` calls with the message in the query.
+    ConnectUnary,
+    /// A Connect streaming call, which reports errors in an end-of-stream message.
+    ConnectStreaming { content_type: HeaderValue },
+}
+
+impl RpcProtocol {
+    pub fn detect(request: &impl Request) -> Option<Self> {
+        let content_type = request.get_header(&CONTENT_TYPE);
+        let media_type = content_type
+            .and_then(|c| c.to_str().ok())
+            .map(|c| c.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let protocol = match media_type.as_str() {
+            // Also covers `application/grpc-web-text` and the `+proto`/`+json` variants.
+            t if t.starts_with("application/grpc-web") => RpcProtocol::GrpcWeb {
+                content_type: content_type?.clone(),
+            },
+            t if t == "application/grpc" || t.starts_with("application/grpc+") => RpcProtocol::Grpc {
+                content_type: content_type?.clone(),
+            },
+            t if t.starts_with("application/connect+") => RpcProtocol::ConnectStreaming {
+                content_type: content_type?.clone(),
+            },
+            _ if request.get_header(&CONNECT_PROTOCOL_VERSION).is_some() => RpcProtocol::ConnectUnary,
+            _ if request.get_method() == Method::GET && is_connect_get(request.get_uri().query()) => {
+                RpcProtocol::ConnectUnary
+            }
+            _ => return None,
+        };
+
+        Some(protocol)
+    }
+
+    /// Rewrites an authentication or authorization response into the protocol's error format, keeping its
+    /// headers (e.g. `WWW-Authenticate`) and using a UTF-8 body as the error message.
+    pub fn map_response(&self, response: AuthResponse) -> AuthResponse {
+        let code = RpcCode::from_status(response.status_code);
+        let message = std::str::from_utf8(&response.body)
+            .ok()
+            .filter(|m| !m.is_empty())
+            .unwrap_or(code.default_message())
+            .to_owned();
+        let mut headers = response.headers;
+
+        match self {
+            RpcProtocol::Grpc { content_type } | RpcProtocol::GrpcWeb { content_type } => {
+                // A trailers-only response: the status travels in the headers and there is no body.
+                headers.insert(CONTENT_TYPE, content_type.clone());
+                headers.insert(GRPC_STATUS, HeaderValue::from(code.grpc_code()));
+                headers.insert(
+                    GRPC_MESSAGE,
+                    HeaderValue::try_from(grpc_percent_encode(&message))
+                        .expect("percent encoded message is a valid header value"),
+                );
+                if matches!(self, RpcProtocol::GrpcWeb { .. }) {
+                    headers.append(
+                        ACCESS_CONTROL_EXPOSE_HEADERS,
+                        HeaderValue::from_static("grpc-status, grpc-message, www-authenticate"),
+                    );
+                }
+
+                AuthResponse {
+                    status_code: StatusCode::OK,
+                    headers,
+                    body: Vec::new(),
+                }
+            }
+            RpcProtocol::ConnectUnary => {
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                AuthResponse {
+                    status_code: response.status_code,
+                    headers,
+                    body: connect_error(code, &message).into_bytes(),
+                }
+            }
+            RpcProtocol::ConnectStreaming { content_type } => {
+                let end_stream = format!("{{\"error\":{}}}", connect_error(code, &message));
+                let mut body = Vec::with_capacity(end_stream.len() + 5);
+                body.push(0x02);
+                body.extend_from_slice(&(end_stream.len() as u32).to_be_bytes());
+                body.extend_from_slice(end_stream.as_bytes());
+
+                headers.insert(CONTENT_TYPE, content_type.clone());
+                AuthResponse {
+                    status_code: StatusCode::OK,
+                    headers,
+                    body,
+                }
+            }
+        }
+    }
+}
+
+/// Maps `response` into the RPC protocol of `request`, if it uses one.
+pub fn rpc_auth_response(request: &impl Request, response: AuthResponse) -> AuthResponse {
+    match RpcProtocol::detect(request) {
+        Some(protocol) => protocol.map_response(response),
+        None => response,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RpcCode {
+    Unknown,
+    PermissionDenied,
+    Internal,
+    Unavailable,
+    Unauthenticated,
+}
+
+impl RpcCode {
+    fn from_status(status_code: StatusCode) -> Self {
+        match status_code {
+            StatusCode::UNAUTHORIZED => RpcCode::Unauthenticated,
+            StatusCode::FORBIDDEN => RpcCode::PermissionDenied,
+            StatusCode::SERVICE_UNAVAILABLE => RpcCode::Unavailable,
+            StatusCode::INTERNAL_SERVER_ERROR => RpcCode::Internal,
+            _ => RpcCode::Unknown,
+        }
+    }
+
+    fn grpc_code(self) -> u16 {
+        match self {
+            RpcCode::Unknown => 2,
+            RpcCode::PermissionDenied => 7,
+            RpcCode::Internal => 13,
+            RpcCode::Unavailable => 14,
+            RpcCode::Unauthenticated => 16,
+        }
+    }
+
+    fn connect_code(self) -> &'static str {
+        match self {
+            RpcCode::Unknown => "unknown",
+            RpcCode::PermissionDenied => "permission_denied",
+            RpcCode::Internal => "internal",
+            RpcCode::Unavailable => "unavailable",
+            RpcCode::Unauthenticated => "unauthenticated",
+        }
+    }
+
+    fn default_message(self) -> &'static str {
+        match self {
+            RpcCode::Unknown => "Request failed",
+            RpcCode::PermissionDenied => "Permission denied",
+            RpcCode::Internal => "Internal error",
+            RpcCode::Unavailable => "Service unavailable",
+            RpcCode::Unauthenticated => "Unauthenticated",
+        }
+    }
+}
+
+fn is_connect_get(query: Option<&str>) -> bool {
+    form_urlencoded::parse(query.unwrap_or_default().as_bytes()).any(|(name, value)| name == "connect" && value == "v1")
+}
+
+fn connect_error(code: RpcCode, message: &str) -> String {
+    format!(
+        "{{\"code\":\"{}\",\"message\":\"{}\"}}",
+        code.connect_code(),
+        json_escape(message)
+    )
+}
+
+pub(crate) fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// `grpc-message` is percent encoded, leaving printable ASCII other than `%` as is.
+fn grpc_percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            0x20..=0x7e if b != b'%' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Clone)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    pub last_success: Option<SystemTime>,
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl DependencyHealth {
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        now.duration_since(self.last_success?).ok()
+    }
+}
+
+pub trait HealthReporter: Send + Sync + 'static {
+    fn health(&self) -> DependencyHealth;
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthHealthReport {
+    pub status: HealthStatus,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+#[derive(Clone, Default)]
+pub struct AuthHealth {
+    reporters: Vec<Arc<dyn HealthReporter>>,
+}
+
+impl AuthHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_reporter(mut self, reporter: Arc<dyn HealthReporter>) -> Self {
+        self.reporters.push(reporter);
+        self
+    }
+
+    pub fn report(&self) -> AuthHealthReport {
+        let dependencies = self.reporters.iter().map(|r| r.health()).collect::<Vec<_>>();
+        let status = dependencies
+            .iter()
+            .map(|d| d.status)
+            .max()
+            .unwrap_or(HealthStatus::Healthy);
+
+        AuthHealthReport { status, dependencies }
+    }
+}
@@ -1,9 +1,12 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
+    net::SocketAddr,
     ops::{Deref, DerefMut},
+    sync::Arc,
 };
 
-use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri};
+use http::{header::COOKIE, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
 
 pub trait RequestExtensions {
     fn get<T: Send + Sync + 'static>(&self) -> Option<&T>;
@@ -13,6 +16,20 @@ pub trait RequestExtensions {
     fn insert<T: Send + Sync + 'static>(&mut self, ext: T) -> Option<T>;
 }
 
+impl RequestExtensions for http::Extensions {
+    fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.get()
+    }
+
+    fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.get_mut()
+    }
+
+    fn insert<T: Send + Sync + 'static>(&mut self, ext: T) -> Option<T> {
+        self.insert(ext)
+    }
+}
+
 pub trait Request {
     type RequestExtensions: RequestExtensions;
 
@@ -24,19 +41,66 @@ pub trait Request {
     where
         Self: 'a;
 
+    fn get_method(&self) -> &Method;
+
     fn get_uri(&self) -> &Uri;
 
     fn get_header(&self, header: &HeaderName) -> Option<&HeaderValue>;
 
+    fn get_headers(&self) -> HeaderMap;
+
+    fn get_peer_certificate(&self) -> Option<PeerCertificate>;
+
+    fn get_peer_address(&self) -> Option<SocketAddr>;
+
+    fn get_route_params(&self) -> RouteParams;
+
     fn get_extensions(&self) -> Self::RequestExtensionsDeref<'_>;
 
     fn get_extensions_mut(&mut self) -> Self::RequestExtensionsDerefMut<'_>;
 }
 
+#[derive(Debug, Clone)]
+pub struct PeerCertificate(pub Arc<[u8]>);
+
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddress(pub SocketAddr);
+
+/// Parameters captured by the framework's router, e.g. `tenant_id` from `/tenants/{tenant_id}/...`.
+#[derive(Debug, Clone, Default)]
+pub struct RouteParams(pub HashMap<String, String>);
+
+impl RouteParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// The raw request body, for handlers whose credentials cover it. Insert it before authentication runs.
+#[derive(Debug, Clone)]
+pub struct RequestBody(pub Arc<[u8]>);
+
 #[derive(Debug)]
 pub struct AuthResponse {
     pub status_code: StatusCode,
     pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl AuthResponse {
+    /// `404 Not Found` in place of a challenge or forbid response, hiding that the resource exists from users
+    /// who can't access it. Other responses, e.g. `503` during maintenance, are kept.
+    pub fn into_not_found(self) -> Self {
+        if self.status_code != StatusCode::UNAUTHORIZED && self.status_code != StatusCode::FORBIDDEN {
+            return self;
+        }
+
+        AuthResponse {
+            status_code: StatusCode::NOT_FOUND,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        }
+    }
 }
 
 impl Display for AuthResponse {
@@ -44,3 +108,65 @@ impl Display for AuthResponse {
         write!(f, "{:?}", self)
     }
 }
+
+/// The status and headers of a response produced by the application, for [`super::authentication::ResponseHook`].
+#[derive(Debug, Clone)]
+pub struct ResponseHead {
+    pub status_code: StatusCode,
+    pub headers: HeaderMap,
+}
+
+pub fn parse_auth_params(value: &str) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        let (name, after_name) = rest.split_once('=')?;
+        let name = name.trim().to_owned();
+        let after_name = after_name.trim_start();
+
+        let (value, after_value) = match after_name.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let end = loop {
+                    match chars.next()? {
+                        (_, '\\') => value.push(chars.next()?.1),
+                        (i, '"') => break i,
+                        (_, c) => value.push(c),
+                    }
+                };
+                (value, &quoted[end + 1..])
+            }
+            None => {
+                let end = after_name.find(',').unwrap_or(after_name.len());
+                (after_name[..end].trim().to_owned(), &after_name[end..])
+            }
+        };
+
+        params.insert(name, value);
+        rest = after_value.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    Some(params)
+}
+
+pub fn get_cookie<'a>(request: &'a impl Request, name: &str) -> Option<&'a str> {
+    request
+        .get_header(&COOKIE)?
+        .to_str()
+        .ok()?
+        .split(';')
+        .filter_map(|c| c.trim().split_once('='))
+        .find_map(|(n, v)| (n == name).then_some(v))
+}
+
+/// Whether `host` is `pattern`, or a subdomain of `example.com` for a pattern of `*.example.com`, ignoring case.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .len()
+            .checked_sub(domain.len() + 1)
+            .is_some_and(|dot| host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain)),
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
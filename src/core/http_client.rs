@@ -0,0 +1,115 @@
+use std::future::Future;
+
+use anyhow::bail;
+use async_trait::async_trait;
+
+pub type HttpRequest = http::Request<Vec<u8>>;
+
+pub type HttpResponse = http::Response<Vec<u8>>;
+
+/// Sends the outbound requests of network-backed components (JWKS, discovery, token endpoints). Non-success
+/// statuses are returned as responses; only transport failures are errors.
+#[async_trait]
+pub trait HttpClient: Send + Sync + 'static {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, anyhow::Error>;
+}
+
+#[async_trait]
+impl<F, Fut> HttpClient for F
+where
+    F: Fn(HttpRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<HttpResponse, anyhow::Error>> + Send,
+{
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        self(request).await
+    }
+}
+
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Default)]
+pub struct ReqwestHttpClient(pub reqwest::Client);
+
+/// Connection settings for a [`ReqwestHttpClient`], e.g. to fetch IdP metadata through a corporate proxy that
+/// intercepts TLS with its own root certificate. Without a `proxy` the `HTTP(S)_PROXY` environment variables
+/// are used.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Default)]
+pub struct HttpClientOptions {
+    pub proxy: Option<String>,
+    /// Hosts that bypass `proxy`, in the comma-separated `NO_PROXY` format.
+    pub no_proxy: Option<String>,
+    /// PEM bundles trusted in addition to the built-in roots.
+    pub root_certificates_pem: Vec<Vec<u8>>,
+    pub disable_built_in_roots: bool,
+    /// Accepts any server certificate. Only meant for development.
+    pub danger_accept_invalid_certs: bool,
+    pub user_agent: Option<String>,
+    pub timeout: Option<std::time::Duration>,
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpClientOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn build(&self) -> Result<ReqwestHttpClient, anyhow::Error> {
+        let mut builder = reqwest::Client::builder()
+            .tls_built_in_root_certs(!self.disable_built_in_roots)
+            .danger_accept_invalid_certs(self.danger_accept_invalid_certs);
+        if let Some(proxy) = &self.proxy {
+            let no_proxy = self.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string);
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?.no_proxy(no_proxy));
+        }
+        for pem in &self.root_certificates_pem {
+            for certificate in reqwest::Certificate::from_pem_bundle(pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        Ok(ReqwestHttpClient(builder.build()?))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait]
+impl HttpClient for ReqwestHttpClient {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let response = self.0.execute(request.try_into()?).await?;
+        let mut builder = http::Response::builder()
+            .status(response.status())
+            .version(response.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+
+        Ok(builder.body(response.bytes().await?.to_vec())?)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub fn default_http_client() -> std::sync::Arc<dyn HttpClient> {
+    std::sync::Arc::new(ReqwestHttpClient::default())
+}
+
+#[cfg(feature = "oauth")]
+pub(crate) fn form_body(form: &[(&str, &str)]) -> Vec<u8> {
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish()
+        .into_bytes()
+}
+
+pub fn error_for_status(response: HttpResponse) -> Result<HttpResponse, anyhow::Error> {
+    if !response.status().is_success() {
+        bail!("Request failed with status {}", response.status());
+    }
+
+    Ok(response)
+}
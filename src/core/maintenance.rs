@@ -0,0 +1,74 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode};
+
+use super::{http::AuthResponse, principal::UserPrincipal};
+
+/// Lets only users in the exempt roles, e.g. administrators, through the authorization layers while it's
+/// enabled, refusing everyone else with `503 Service Unavailable`. Clones share the switch, so operators can
+/// toggle it at runtime, e.g. from an admin endpoint, without touching the policies.
+#[derive(Debug, Clone)]
+pub struct MaintenanceGate {
+    enabled: Arc<AtomicBool>,
+    exempt_roles: Vec<String>,
+    retry_after: Duration,
+}
+
+impl MaintenanceGate {
+    /// A disabled gate without exempt roles, asking clients to retry after five minutes.
+    pub fn new() -> Self {
+        Self {
+            enabled: Arc::default(),
+            exempt_roles: Vec::new(),
+            retry_after: Duration::from_secs(5 * 60),
+        }
+    }
+
+    pub fn exempt_role(mut self, role: impl Into<String>) -> Self {
+        self.exempt_roles.push(role.into());
+        self
+    }
+
+    pub fn retry_after(self, retry_after: Duration) -> Self {
+        Self { retry_after, ..self }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// The response refusing `principal`, `None` when it may proceed. Anonymous requests are refused too.
+    pub fn check(&self, principal: Option<&UserPrincipal>) -> Option<AuthResponse> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let is_exempt =
+            principal.is_some_and(|principal| self.exempt_roles.iter().any(|role| principal.is_in_role(role)));
+        (!is_exempt).then(|| self.response())
+    }
+
+    pub fn response(&self) -> AuthResponse {
+        AuthResponse {
+            status_code: StatusCode::SERVICE_UNAVAILABLE,
+            headers: HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from(self.retry_after.as_secs().max(1)))]),
+            body: Vec::new(),
+        }
+    }
+}
+
+impl Default for MaintenanceGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
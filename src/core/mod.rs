@@ -1,5 +1,35 @@
+pub mod auth_context;
 pub mod authentication;
 pub mod authorization;
+pub mod authorization_map;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod cors;
+pub mod credentials;
+pub mod dyn_handler;
+pub mod endpoint;
+pub mod feature_flags;
 pub mod futures;
+pub mod geo;
+pub mod grpc;
+pub mod health;
 pub mod http;
+pub mod http_client;
+pub mod maintenance;
+pub mod nonce;
+pub mod ownership;
 pub mod principal;
+pub mod principal_ext;
+pub mod problem_details;
+pub mod redirect;
+pub mod request_id;
+pub mod response_headers;
+pub mod risk;
+pub mod scheme_filter;
+pub mod session;
+pub mod subject_validation;
+pub mod tenant;
+// Used by the tests of handlers behind features.
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) mod testing;
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+
+use super::cache::AuthCache;
+
+#[async_trait]
+pub trait NonceStore: Send + Sync + 'static {
+    async fn try_use(&self, nonce: &str, expires_at: SystemTime) -> Result<bool, anyhow::Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryNonceStore {
+    nonces: Mutex<HashMap<String, SystemTime>>,
+}
+
+impl InMemoryNonceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl NonceStore for InMemoryNonceStore {
+    async fn try_use(&self, nonce: &str, expires_at: SystemTime) -> Result<bool, anyhow::Error> {
+        let now = SystemTime::now();
+        let mut nonces = self.nonces.lock().unwrap_or_else(PoisonError::into_inner);
+        nonces.retain(|_, expires_at| *expires_at > now);
+
+        if nonces.contains_key(nonce) {
+            return Ok(false);
+        }
+
+        nonces.insert(nonce.to_owned(), expires_at);
+        Ok(true)
+    }
+}
+
+pub struct CacheNonceStore {
+    pub cache: Arc<dyn AuthCache>,
+    pub key_prefix: String,
+}
+
+#[async_trait]
+impl NonceStore for CacheNonceStore {
+    async fn try_use(&self, nonce: &str, expires_at: SystemTime) -> Result<bool, anyhow::Error> {
+        let ttl = expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .max(Duration::from_secs(1));
+
+        self.cache
+            .set_if_absent(&format!("{}{nonce}", self.key_prefix), Vec::new(), ttl)
+            .await
+    }
+}
@@ -0,0 +1,129 @@
+use std::{any::type_name, borrow::Cow, future::Future, pin::Pin, sync::Arc};
+
+use async_trait::async_trait;
+
+use super::{
+    authorization::{AuthorizationHandlerContext, AuthorizationRequirement},
+    principal::claim_types,
+};
+
+#[async_trait]
+pub trait OwnerLoader: Send + Sync + 'static {
+    /// `None` when the resource doesn't exist.
+    async fn load_owner(&self, resource_id: &str) -> Result<Option<String>, anyhow::Error>;
+}
+
+#[async_trait]
+impl<F, Fut> OwnerLoader for F
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<String>, anyhow::Error>> + Send,
+{
+    async fn load_owner(&self, resource_id: &str) -> Result<Option<String>, anyhow::Error> {
+        self(resource_id.to_owned()).await
+    }
+}
+
+#[derive(Clone)]
+enum ResourceId {
+    RouteParam(String),
+    Fixed(String),
+}
+
+/// Succeeds when the user's claim (the subject by default) equals the owner of the addressed resource.
+/// Owners are cached in the request's [`AuthorizationCache`](super::authorization::AuthorizationCache), so
+/// several policies checking the same resource load it once.
+pub struct OwnershipRequirement<T: OwnerLoader> {
+    loader: Arc<T>,
+    resource_id: ResourceId,
+    claim_type: String,
+}
+
+impl<T: OwnerLoader> OwnershipRequirement<T> {
+    /// Takes the resource id from the `id` route parameter.
+    pub fn new(loader: T) -> Self {
+        Self {
+            loader: Arc::new(loader),
+            resource_id: ResourceId::RouteParam("id".to_owned()),
+            claim_type: claim_types::SUBJECT.to_owned(),
+        }
+    }
+
+    pub fn route_param(self, param: impl Into<String>) -> Self {
+        Self {
+            resource_id: ResourceId::RouteParam(param.into()),
+            ..self
+        }
+    }
+
+    pub fn claim_type(self, claim_type: impl Into<String>) -> Self {
+        Self {
+            claim_type: claim_type.into(),
+            ..self
+        }
+    }
+
+    /// A copy checking a specific resource, for use with `AuthorizationService::authorize_requirement`.
+    pub fn for_resource(&self, resource_id: impl Into<String>) -> Self {
+        Self {
+            resource_id: ResourceId::Fixed(resource_id.into()),
+            ..self.clone()
+        }
+    }
+}
+
+impl<T: OwnerLoader> Clone for OwnershipRequirement<T> {
+    fn clone(&self) -> Self {
+        Self {
+            loader: self.loader.clone(),
+            resource_id: self.resource_id.clone(),
+            claim_type: self.claim_type.clone(),
+        }
+    }
+}
+
+impl<T: OwnerLoader> AuthorizationRequirement for OwnershipRequirement<T> {
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("Ownership({})", type_name::<T>()))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        Box::pin(async move {
+            let resource_id = match &self.resource_id {
+                ResourceId::RouteParam(param) => context.route_param(param),
+                ResourceId::Fixed(id) => Some(id.as_str()),
+            };
+            let Some(resource_id) = resource_id else {
+                context.fail_with_message("Request doesn't identify a resource");
+                return;
+            };
+
+            let cache_key = format!("owner:{}:{resource_id}", type_name::<T>());
+            let owner = match context.cache().get::<Option<String>>(&cache_key) {
+                Some(owner) => owner,
+                None => match self.loader.load_owner(resource_id).await {
+                    Ok(owner) => {
+                        context.cache().insert(cache_key, owner.clone());
+                        owner
+                    }
+                    Err(err) => {
+                        context.fail_with_message(format!("Failed to load the owner of {resource_id}: {err}"));
+                        return;
+                    }
+                },
+            };
+
+            let is_owner = owner.is_some_and(|owner| {
+                context
+                    .principal()
+                    .claim(&self.claim_type)
+                    .is_some_and(|claim| claim.iter().any(|value| value.as_str() == Some(owner.as_str())))
+            });
+            if is_owner {
+                context.succeed(&self.name());
+            }
+        })
+    }
+}
@@ -1,12 +1,172 @@
-use std::collections::HashMap;
+use std::{borrow::Borrow, collections::HashMap, fmt::Display, hash::Hash, ops::Deref, sync::Arc};
 
 pub mod claim_types {
     pub const ROLE: &str = "role";
+    pub const SUBJECT: &str = "sub";
+    pub const NAME: &str = "name";
+    pub const EMAIL: &str = "email";
+    pub const EMAIL_VERIFIED: &str = "email_verified";
+    pub const PHONE_NUMBER_VERIFIED: &str = "phone_number_verified";
+    pub const GROUP_SID: &str = "groupsid";
+    pub const ACT: &str = "act";
+    pub const MAY_ACT: &str = "may_act";
+    pub const AMR: &str = "amr";
+    pub const BIRTHDATE: &str = "birthdate";
 }
 
+/// Names that principals are built with on most requests; converting them to a [`ClaimType`] doesn't allocate.
+const WELL_KNOWN_CLAIM_TYPES: &[&str] = &[
+    claim_types::ROLE,
+    claim_types::SUBJECT,
+    claim_types::NAME,
+    claim_types::EMAIL,
+    claim_types::GROUP_SID,
+    claim_types::ACT,
+    claim_types::MAY_ACT,
+    claim_types::AMR,
+    "iss",
+    "aud",
+    "exp",
+    "nbf",
+    "iat",
+    "jti",
+    "azp",
+    "scope",
+    "client_id",
+    "roles",
+    "groups",
+    "tid",
+];
+
+#[derive(Clone)]
+enum ClaimTypeRepr {
+    Static(&'static str),
+    Shared(Arc<str>),
+}
+
+/// A claim name. Well-known names and names from [`ClaimType::from_static`] are stored without allocating, and
+/// clones of other names share one allocation.
+#[derive(Clone)]
+pub struct ClaimType(ClaimTypeRepr);
+
+impl ClaimType {
+    pub const fn from_static(name: &'static str) -> Self {
+        Self(ClaimTypeRepr::Static(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            ClaimTypeRepr::Static(name) => name,
+            ClaimTypeRepr::Shared(name) => name,
+        }
+    }
+
+    fn well_known(name: &str) -> Option<Self> {
+        WELL_KNOWN_CLAIM_TYPES
+            .iter()
+            .find(|&&known| known == name)
+            .map(|&known| Self::from_static(known))
+    }
+}
+
+impl From<&str> for ClaimType {
+    fn from(name: &str) -> Self {
+        Self::well_known(name).unwrap_or_else(|| Self(ClaimTypeRepr::Shared(name.into())))
+    }
+}
+
+impl From<String> for ClaimType {
+    fn from(name: String) -> Self {
+        Self::well_known(&name).unwrap_or_else(|| Self(ClaimTypeRepr::Shared(name.into())))
+    }
+}
+
+impl From<&String> for ClaimType {
+    fn from(name: &String) -> Self {
+        name.as_str().into()
+    }
+}
+
+impl Deref for ClaimType {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ClaimType {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for ClaimType {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for ClaimType {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ClaimType {}
+
+impl PartialEq<str> for ClaimType {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ClaimType {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Hash for ClaimType {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Must hash like `str` for lookups through `Borrow<str>`.
+        self.as_str().hash(state)
+    }
+}
+
+impl Display for ClaimType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for ClaimType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ClaimType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ClaimType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+pub type Claims = HashMap<ClaimType, ClaimValue>;
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
 pub enum ClaimPlainValue {
-    String(String),
+    /// Shared, so cloning a principal or its claims doesn't copy the strings.
+    String(Arc<str>),
     Int(i64),
     Float(f64),
     Bool(bool),
@@ -43,6 +203,7 @@ impl ClaimPlainValue {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
 pub enum ClaimValue {
     PlainValue(ClaimPlainValue),
     Array(Vec<ClaimPlainValue>),
@@ -80,9 +241,11 @@ impl ClaimValue {
     }
 }
 
+/// Serializes as a map of claims, e.g. `{"sub": "alice", "role": ["admin", "user"]}`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct UserPrincipal {
-    pub(crate) claims: HashMap<String, ClaimValue>,
+    pub(crate) claims: Claims,
 }
 
 impl UserPrincipal {
@@ -96,7 +259,45 @@ impl UserPrincipal {
         self.claims.get(claim_type)
     }
 
-    pub fn claims(&self) -> impl Iterator<Item = (&String, &ClaimValue)> {
-        self.claims.iter()
+    /// String values of a claim, skipping non-string ones.
+    pub fn claim_strs<'a>(&'a self, claim_type: &str) -> impl Iterator<Item = &'a str> {
+        self.claim(claim_type)
+            .into_iter()
+            .flat_map(ClaimValue::iter)
+            .filter_map(ClaimPlainValue::as_str)
+    }
+
+    pub fn claims(&self) -> impl Iterator<Item = (&str, &ClaimValue)> {
+        self.claims
+            .iter()
+            .map(|(claim_type, value)| (claim_type.as_str(), value))
     }
+
+    pub fn actor(&self) -> Option<UserPrincipal> {
+        let claims = nested_claims(&self.claims, claim_types::ACT);
+        (!claims.is_empty()).then_some(UserPrincipal { claims })
+    }
+
+    pub fn is_impersonated(&self) -> bool {
+        let prefix = format!("{}.", claim_types::ACT);
+        self.claims.keys().any(|t| t.starts_with(&prefix))
+    }
+
+    pub fn may_be_acted_by(&self, actor: &UserPrincipal) -> bool {
+        let allowed = nested_claims(&self.claims, claim_types::MAY_ACT);
+        !allowed.is_empty()
+            && allowed.iter().all(|(t, allowed_value)| {
+                actor
+                    .claim(t)
+                    .is_some_and(|v| v.iter().any(|v| allowed_value.iter().any(|a| a == v)))
+            })
+    }
+}
+
+fn nested_claims(claims: &Claims, claim_type: &str) -> Claims {
+    let prefix = format!("{claim_type}.");
+    claims
+        .iter()
+        .filter_map(|(t, v)| Some((t.strip_prefix(&prefix)?.into(), v.clone())))
+        .collect()
 }
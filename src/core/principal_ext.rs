@@ -0,0 +1,72 @@
+use super::{
+    authentication::SuccessAuthenticationResult,
+    principal::{claim_types, UserPrincipal},
+};
+
+/// Field names for [`PrincipalView`], for middleware that logs or keys on the principal, e.g. as tracing
+/// fields or rate limiter buckets.
+pub mod keys {
+    pub const SUBJECT: &str = "auth.subject";
+    pub const ROLES: &str = "auth.roles";
+    pub const ACTOR: &str = "auth.actor";
+}
+
+/// The part of a principal other middleware usually needs, independent of how it was authenticated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrincipalView {
+    pub subject: Option<String>,
+    pub roles: Vec<String>,
+    pub actor: Option<String>,
+}
+
+impl PrincipalView {
+    pub fn fields(&self) -> [(&'static str, String); 3] {
+        [
+            (keys::SUBJECT, self.subject.clone().unwrap_or_default()),
+            (keys::ROLES, self.roles.join(",")),
+            (keys::ACTOR, self.actor.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+impl From<&UserPrincipal> for PrincipalView {
+    fn from(principal: &UserPrincipal) -> Self {
+        let subject = |principal: &UserPrincipal| principal.claim_strs(claim_types::SUBJECT).next().map(str::to_owned);
+
+        Self {
+            subject: subject(principal),
+            roles: principal.claim_strs(claim_types::ROLE).map(str::to_owned).collect(),
+            actor: principal.actor().as_ref().and_then(subject),
+        }
+    }
+}
+
+/// Reads the authenticated principal from request extensions. This is the stable contract for other crates;
+/// it only needs the request to have passed through the authentication layer.
+pub trait AuthPrincipalExt {
+    fn auth_principal(&self) -> Option<&UserPrincipal>;
+
+    fn auth_principal_view(&self) -> Option<PrincipalView> {
+        self.auth_principal().map(PrincipalView::from)
+    }
+}
+
+impl AuthPrincipalExt for http::Extensions {
+    fn auth_principal(&self) -> Option<&UserPrincipal> {
+        self.get::<SuccessAuthenticationResult>().map(|r| &r.principal)
+    }
+}
+
+impl<Body> AuthPrincipalExt for http::Request<Body> {
+    fn auth_principal(&self) -> Option<&UserPrincipal> {
+        self.extensions().auth_principal()
+    }
+}
+
+#[cfg(feature = "actix")]
+impl AuthPrincipalExt for actix_web::dev::Extensions {
+    fn auth_principal(&self) -> Option<&UserPrincipal> {
+        self.get::<SuccessAuthenticationResult>().map(|r| &r.principal)
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, sync::Arc};
+
+use http::{
+    header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_TYPE},
+    HeaderValue, StatusCode,
+};
+
+use super::{
+    authentication::{AuthError, AuthenticationFailure},
+    grpc::{json_escape, RpcProtocol},
+    http::{AuthResponse, Request, RequestExtensions},
+};
+
+/// Why a request was refused, for a [`MessageResolver`] to describe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// No credentials were presented.
+    Unauthenticated,
+    InvalidCredentials,
+    ExpiredCredentials,
+    Forbidden,
+}
+
+impl FailureKind {
+    pub fn default_message(self) -> &'static str {
+        match self {
+            FailureKind::Unauthenticated => "Authentication is required",
+            FailureKind::InvalidCredentials => "Credentials are invalid",
+            FailureKind::ExpiredCredentials => "Credentials have expired",
+            FailureKind::Forbidden => "Access is denied",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizedMessage {
+    pub language: String,
+    pub text: String,
+}
+
+pub trait MessageResolver: Send + Sync + 'static {
+    /// The message for `kind` in the first of `languages`, lowercase tags in order of preference, that's
+    /// supported. `None` falls back to [`FailureKind::default_message`].
+    fn resolve(&self, kind: FailureKind, languages: &[String]) -> Option<LocalizedMessage>;
+}
+
+/// Messages fixed at startup. A regional tag such as `de-ch` falls back to its language, `de`.
+#[derive(Debug, Clone, Default)]
+pub struct StaticMessageResolver {
+    messages: HashMap<String, HashMap<FailureKind, String>>,
+}
+
+impl StaticMessageResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn message(mut self, language: &str, kind: FailureKind, text: impl Into<String>) -> Self {
+        self.messages
+            .entry(language.to_ascii_lowercase())
+            .or_default()
+            .insert(kind, text.into());
+        self
+    }
+}
+
+impl MessageResolver for StaticMessageResolver {
+    fn resolve(&self, kind: FailureKind, languages: &[String]) -> Option<LocalizedMessage> {
+        languages.iter().find_map(|language| {
+            let primary = language.split('-').next().unwrap_or(language);
+            [language.as_str(), primary].into_iter().find_map(|language| {
+                let text = self.messages.get(language)?.get(&kind)?;
+                Some(LocalizedMessage {
+                    language: language.to_owned(),
+                    text: text.clone(),
+                })
+            })
+        })
+    }
+}
+
+/// Gives challenge and forbid responses without a body an RFC 9457 problem details body, with a title in the
+/// language the client asked for through `Accept-Language`. Requests of RPC protocols are left to
+/// [`rpc_auth_response`](super::grpc::rpc_auth_response).
+#[derive(Clone, Default)]
+pub struct ProblemDetails {
+    resolver: Option<Arc<dyn MessageResolver>>,
+}
+
+impl ProblemDetails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resolver(self, resolver: Arc<dyn MessageResolver>) -> Self {
+        Self {
+            resolver: Some(resolver),
+        }
+    }
+
+    pub fn apply(&self, request: &impl Request, mut response: AuthResponse) -> AuthResponse {
+        let kind = match response.status_code {
+            StatusCode::UNAUTHORIZED => authentication_failure_kind(request.get_extensions().get()),
+            StatusCode::FORBIDDEN => FailureKind::Forbidden,
+            _ => return response,
+        };
+        if !response.body.is_empty() || RpcProtocol::detect(request).is_some() {
+            return response;
+        }
+
+        let message = self
+            .resolver
+            .as_ref()
+            .and_then(|resolver| resolver.resolve(kind, &accepted_languages(request)));
+        let title = message.as_ref().map_or(kind.default_message(), |message| &message.text);
+        response.body = format!(
+            "{{\"type\":\"about:blank\",\"title\":\"{}\",\"status\":{}}}",
+            json_escape(title),
+            response.status_code.as_u16()
+        )
+        .into_bytes();
+        response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if let Some(language) = message.and_then(|message| HeaderValue::try_from(message.language).ok()) {
+            response.headers.insert(CONTENT_LANGUAGE, language);
+        }
+
+        response
+    }
+}
+
+fn authentication_failure_kind(failure: Option<&AuthenticationFailure>) -> FailureKind {
+    let Some(failure) = failure else {
+        return FailureKind::Unauthenticated;
+    };
+
+    if failure
+        .errors
+        .iter()
+        .any(|error| matches!(error.error, AuthError::Expired))
+    {
+        FailureKind::ExpiredCredentials
+    } else {
+        FailureKind::InvalidCredentials
+    }
+}
+
+/// The languages of the `Accept-Language` header, lowercase and most preferred first.
+pub fn accepted_languages(request: &impl Request) -> Vec<String> {
+    let Some(header) = request.get_header(&ACCEPT_LANGUAGE).and_then(|h| h.to_str().ok()) else {
+        return Vec::new();
+    };
+
+    let mut languages = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut params = entry.split(';');
+            let language = params.next()?.trim();
+            let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(quality) => quality.trim().parse::<f32>().ok()?,
+                None => 1.0,
+            };
+            (!language.is_empty() && language != "*" && quality > 0.0).then(|| (language.to_ascii_lowercase(), quality))
+        })
+        .collect::<Vec<_>>();
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    languages.into_iter().map(|(language, _)| language).collect()
+}
@@ -0,0 +1,109 @@
+use http::{header::LOCATION, HeaderMap, HeaderValue, StatusCode, Uri};
+
+use super::http::{AuthResponse, Request};
+
+pub const DEFAULT_RETURN_URL_PARAMETER: &str = "ReturnUrl";
+
+#[derive(Debug, Clone)]
+pub struct ReturnUrlValidator {
+    pub parameter_name: String,
+    pub allowed_origins: Vec<String>,
+}
+
+impl ReturnUrlValidator {
+    pub fn new() -> Self {
+        Self {
+            parameter_name: DEFAULT_RETURN_URL_PARAMETER.to_owned(),
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    pub fn parameter_name(self, parameter_name: impl Into<String>) -> Self {
+        Self {
+            parameter_name: parameter_name.into(),
+            ..self
+        }
+    }
+
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.push(origin.into());
+        self
+    }
+
+    pub fn original_url(request: &impl Request) -> String {
+        request
+            .get_uri()
+            .path_and_query()
+            .map(|p| p.as_str().to_owned())
+            .unwrap_or_else(|| "/".to_owned())
+    }
+
+    pub fn login_redirect(&self, login_path: &str, request: &impl Request) -> AuthResponse {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair(&self.parameter_name, &Self::original_url(request))
+            .finish();
+        let separator = if login_path.contains('?') { '&' } else { '?' };
+
+        redirect(&format!("{login_path}{separator}{query}"))
+    }
+
+    pub fn return_url(&self, request: &impl Request) -> Option<String> {
+        form_urlencoded::parse(request.get_uri().query()?.as_bytes())
+            .find_map(|(name, value)| (name == self.parameter_name.as_str()).then_some(value))
+            .filter(|url| self.is_allowed(url))
+            .map(|url| url.into_owned())
+    }
+
+    pub fn is_allowed(&self, url: &str) -> bool {
+        if url.chars().any(|c| c.is_control() || c == '\\') {
+            return false;
+        }
+
+        if is_local(url) {
+            return true;
+        }
+
+        let Ok(uri) = url.parse::<Uri>() else {
+            return false;
+        };
+        let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+            return false;
+        };
+        if authority.as_str().contains('@') {
+            return false;
+        }
+
+        let origin = format!("{scheme}://{authority}");
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(&origin))
+    }
+
+    pub fn redirect_after_sign_in(&self, return_url: Option<&str>, fallback: &str) -> AuthResponse {
+        let location = return_url.filter(|url| self.is_allowed(url)).unwrap_or(fallback);
+        redirect(location)
+    }
+}
+
+impl Default for ReturnUrlValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_local(url: &str) -> bool {
+    url.starts_with('/') && !url.starts_with("//")
+}
+
+pub(crate) fn redirect(location: &str) -> AuthResponse {
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::try_from(location) {
+        headers.insert(LOCATION, location);
+    }
+
+    AuthResponse {
+        status_code: StatusCode::FOUND,
+        headers,
+        body: Vec::new(),
+    }
+}
@@ -0,0 +1,46 @@
+use std::{fmt::Display, sync::Arc};
+
+use http::HeaderName;
+
+use super::http::{Request, RequestExtensions};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Correlates the crate's audit events, `tracing` events and failure extensions with the rest of a request's
+/// logs. Insert it into the request extensions before authentication runs, or let the authentication service
+/// read it from a header, see [`super::authentication::AuthenticationServiceBuilder::set_request_id_header`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(pub Arc<str>);
+
+impl RequestId {
+    pub fn of(request: &impl Request) -> Option<Self> {
+        request.get_extensions().get::<RequestId>().cloned()
+    }
+
+    pub fn from_header(request: &impl Request, header: &HeaderName) -> Option<Self> {
+        let id = request.get_header(header)?.to_str().ok()?;
+        (!id.is_empty()).then(|| id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for RequestId {
+    fn from(id: &str) -> Self {
+        Self(id.into())
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(id: String) -> Self {
+        Self(id.into())
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use http::{
+    header::{CACHE_CONTROL, VARY},
+    HeaderMap, HeaderName, HeaderValue,
+};
+
+use super::{
+    authentication::SuccessAuthenticationResult,
+    principal::{claim_types, ClaimPlainValue},
+};
+
+pub const AUTHENTICATED_SUBJECT_HEADER: HeaderName = HeaderName::from_static("x-authenticated-subject");
+pub const TOKEN_EXPIRES_HEADER: HeaderName = HeaderName::from_static("x-token-expires");
+
+/// Headers added to responses of requests a scheme authenticated. Headers the response already has are kept.
+#[derive(Debug, Clone, Default)]
+pub struct SuccessResponseHeaders {
+    /// Header carrying the `sub` claim, e.g. [`AUTHENTICATED_SUBJECT_HEADER`].
+    pub subject: Option<HeaderName>,
+    /// Header carrying the `exp` claim as seconds since the epoch, e.g. [`TOKEN_EXPIRES_HEADER`].
+    pub expires: Option<HeaderName>,
+    /// Adds `Cache-Control: private` and `Vary: Authorization`, so shared caches don't serve the response to
+    /// other users.
+    pub private: bool,
+    pub extra: HeaderMap,
+}
+
+impl SuccessResponseHeaders {
+    pub fn headers_for(&self, result: &SuccessAuthenticationResult) -> HeaderMap {
+        let mut headers = self.extra.clone();
+        let principal = &result.principal;
+
+        let subject = principal.claim_strs(claim_types::SUBJECT).next();
+        if let Some((name, value)) = self.subject.clone().zip(subject.and_then(|s| s.parse().ok())) {
+            headers.insert(name, value);
+        }
+
+        let expires = principal.claim("exp").and_then(|exp| match exp.iter().next()? {
+            ClaimPlainValue::Int(exp) => Some(HeaderValue::from(*exp)),
+            ClaimPlainValue::Float(exp) => Some(HeaderValue::from(*exp as i64)),
+            _ => None,
+        });
+        if let Some((name, value)) = self.expires.clone().zip(expires) {
+            headers.insert(name, value);
+        }
+
+        if self.private {
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static("private"));
+            headers.insert(VARY, HeaderValue::from_static("Authorization"));
+        }
+
+        headers
+    }
+}
+
+/// Headers handlers add to the response while the request is processed, e.g. a renewed session cookie. The
+/// authentication service inserts it into the request extensions and the framework layers append it to the
+/// response, so it's shared with clones taken before the request moves on.
+#[derive(Debug, Clone, Default)]
+pub struct PendingResponseHeaders(Arc<Mutex<HeaderMap>>);
+
+impl PendingResponseHeaders {
+    pub fn append(&self, name: HeaderName, value: HeaderValue) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .append(name, value);
+    }
+
+    pub fn take(&self) -> HeaderMap {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Adds `headers` to a response, skipping names the response already has.
+pub fn merge_response_headers(response: &mut HeaderMap, headers: HeaderMap) {
+    let mut name = None;
+    let mut skip = false;
+    for (next_name, value) in headers {
+        if let Some(next_name) = next_name {
+            skip = response.contains_key(&next_name);
+            name = Some(next_name);
+        }
+
+        if !skip {
+            response.append(name.clone().expect("The first header always has a name"), value);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{any::type_name, borrow::Cow, future::Future, pin::Pin, sync::Arc};
+
+use async_trait::async_trait;
+use http::HeaderMap;
+
+use super::{
+    authorization::{AuthorizationFailure, AuthorizationHandlerContext, AuthorizationRequirement},
+    http::RouteParams,
+    principal::UserPrincipal,
+    request_id::RequestId,
+};
+
+/// The message of failures where the user has to authenticate again with stronger methods, see
+/// [`requires_step_up`].
+pub const STEP_UP_REQUIRED_MESSAGE: &str = "Step-up authentication is required";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskDecision {
+    Allow,
+    StepUp,
+    Deny,
+}
+
+/// What a [`RiskEvaluator`] is told about the request.
+pub struct RequestSummary<'a> {
+    pub headers: &'a HeaderMap,
+    pub route_params: &'a RouteParams,
+    pub request_id: Option<&'a RequestId>,
+}
+
+/// Consults an external fraud detection or conditional access system during authorization.
+#[async_trait]
+pub trait RiskEvaluator: Send + Sync + 'static {
+    async fn evaluate(
+        &self,
+        principal: &UserPrincipal,
+        request: &RequestSummary<'_>,
+    ) -> Result<RiskDecision, anyhow::Error>;
+}
+
+/// Succeeds when the evaluator allows the request. Step-up decisions fail with [`STEP_UP_REQUIRED_MESSAGE`],
+/// so a [`ForbidResponseCustomizer`](super::authentication::ForbidResponseCustomizer) can ask for stronger
+/// authentication instead of refusing. Decisions are cached in the request's
+/// [`AuthorizationCache`](super::authorization::AuthorizationCache), so several policies consult the evaluator
+/// once, and evaluator errors refuse the request.
+pub struct RiskRequirement<T: RiskEvaluator> {
+    evaluator: Arc<T>,
+}
+
+impl<T: RiskEvaluator> RiskRequirement<T> {
+    pub fn new(evaluator: T) -> Self {
+        Self {
+            evaluator: Arc::new(evaluator),
+        }
+    }
+}
+
+impl<T: RiskEvaluator> Clone for RiskRequirement<T> {
+    fn clone(&self) -> Self {
+        Self {
+            evaluator: self.evaluator.clone(),
+        }
+    }
+}
+
+impl<T: RiskEvaluator> AuthorizationRequirement for RiskRequirement<T> {
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("Risk({})", type_name::<T>()))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        Box::pin(async move {
+            let cache_key = format!("risk:{}", type_name::<T>());
+            let decision = match context.cache().get::<RiskDecision>(&cache_key) {
+                Some(decision) => decision,
+                None => {
+                    let request = RequestSummary {
+                        headers: context.headers(),
+                        route_params: context.route_params(),
+                        request_id: context.request_id(),
+                    };
+                    match self.evaluator.evaluate(context.principal(), &request).await {
+                        Ok(decision) => {
+                            context.cache().insert(cache_key, decision);
+                            decision
+                        }
+                        Err(err) => {
+                            context.fail_with_message(format!("Failed to evaluate the risk of the request: {err}"));
+                            return;
+                        }
+                    }
+                }
+            };
+
+            match decision {
+                RiskDecision::Allow => context.succeed(&self.name()),
+                RiskDecision::StepUp => context.fail_with_message(STEP_UP_REQUIRED_MESSAGE),
+                RiskDecision::Deny => context.fail_with_message("Request is denied by risk evaluation"),
+            }
+        })
+    }
+}
+
+/// Whether `failure` asks the user to authenticate again with stronger methods.
+pub fn requires_step_up(failure: &AuthorizationFailure) -> bool {
+    failure
+        .messages
+        .iter()
+        .any(|message| message == STEP_UP_REQUIRED_MESSAGE)
+}
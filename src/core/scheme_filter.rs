@@ -0,0 +1,64 @@
+use http::header::HOST;
+
+use super::{
+    authorization_map::{AuthorizationMapError, RoutePattern},
+    http::{host_matches, Request},
+};
+
+/// Which requests a scheme looks at, see
+/// [`AuthenticationServiceBuilder::restrict_to`](super::authentication::AuthenticationServiceBuilder::restrict_to).
+/// Other requests skip the scheme as if they had no credentials for it, so e.g. UI routes don't parse bearer
+/// tokens and API routes don't look up cookie sessions. Explicitly authenticating with the scheme ignores the
+/// filter.
+#[derive(Debug, Clone, Default)]
+pub struct SchemeFilter {
+    paths: Vec<RoutePattern>,
+    hosts: Vec<String>,
+}
+
+impl SchemeFilter {
+    /// Matches every request until paths or hosts are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a route pattern such as `"/api/**"`, see [`RoutePattern::parse`].
+    pub fn path(mut self, pattern: &str) -> Result<Self, AuthorizationMapError> {
+        self.paths.push(RoutePattern::parse(pattern)?);
+        Ok(self)
+    }
+
+    /// Adds a host such as `api.example.com`, or its subdomains with `*.example.com`.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.hosts.push(host.into());
+        self
+    }
+
+    /// Whether the request matches one of the paths, if any, and one of the hosts, if any.
+    pub fn matches(&self, request: &impl Request) -> bool {
+        let path_matches = self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| pattern.matches(request.get_method(), request.get_uri().path()));
+
+        path_matches
+            && (self.hosts.is_empty()
+                || request_host(request)
+                    .is_some_and(|host| self.hosts.iter().any(|pattern| host_matches(pattern, host))))
+    }
+}
+
+/// The host of the `Host` header, or of the URI for HTTP/2 requests, without the port.
+fn request_host(request: &impl Request) -> Option<&str> {
+    let host = match request.get_header(&HOST) {
+        Some(host) => host.to_str().ok()?,
+        None => request.get_uri().host()?,
+    };
+
+    // `[::1]:8080` keeps the brackets of the IPv6 address.
+    Some(match host.find(']') {
+        Some(end) => &host[..=end],
+        None => host.split(':').next().unwrap_or(host),
+    })
+}
@@ -0,0 +1,430 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+#[cfg(feature = "serde")]
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+
+#[cfg(feature = "serde")]
+use super::cache::AuthCache;
+use super::principal::UserPrincipal;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Session {
+    pub id: String,
+    pub subject: String,
+    pub provider_session_id: Option<String>,
+    pub principal: UserPrincipal,
+    pub created_at: SystemTime,
+    pub validated_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    pub is_persistent: bool,
+}
+
+impl Session {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.map(|expires_at| expires_at <= now).unwrap_or(false)
+    }
+}
+
+#[async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    async fn store(&self, session: Session) -> Result<(), anyhow::Error>;
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>, anyhow::Error>;
+
+    async fn load_by_subject(&self, subject: &str) -> Result<Vec<Session>, anyhow::Error>;
+
+    async fn remove(&self, session_id: &str) -> Result<(), anyhow::Error>;
+
+    async fn remove_by_subject(&self, subject: &str) -> Result<(), anyhow::Error>;
+
+    async fn remove_by_provider_session(&self, provider_session_id: &str) -> Result<(), anyhow::Error>;
+}
+
+#[derive(Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn store(&self, session: Session) -> Result<(), anyhow::Error> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>, anyhow::Error> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        match sessions.get(session_id) {
+            Some(session) if session.is_expired(SystemTime::now()) => {
+                sessions.remove(session_id);
+                Ok(None)
+            }
+            session => Ok(session.cloned()),
+        }
+    }
+
+    async fn load_by_subject(&self, subject: &str) -> Result<Vec<Session>, anyhow::Error> {
+        let now = SystemTime::now();
+        let sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(sessions
+            .values()
+            .filter(|s| s.subject == subject && !s.is_expired(now))
+            .cloned()
+            .collect())
+    }
+
+    async fn remove(&self, session_id: &str) -> Result<(), anyhow::Error> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.remove(session_id);
+        Ok(())
+    }
+
+    async fn remove_by_subject(&self, subject: &str) -> Result<(), anyhow::Error> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.retain(|_, s| s.subject != subject);
+        Ok(())
+    }
+
+    async fn remove_by_provider_session(&self, provider_session_id: &str) -> Result<(), anyhow::Error> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.retain(|_, s| s.provider_session_id.as_deref() != Some(provider_session_id));
+        Ok(())
+    }
+}
+
+/// Sessions kept in an [`AuthCache`], so instances sharing the cache share the sessions. Sessions without an
+/// expiry are kept for `ttl`. The lists of sessions per subject and per provider session are updated
+/// read-modify-write, so concurrently stored sessions of the same user can be missing from them; such
+/// sessions still load by id and expire with the cache entry.
+#[cfg(feature = "serde")]
+pub struct CacheSessionStore {
+    pub cache: Arc<dyn AuthCache>,
+    pub key_prefix: String,
+    pub ttl: Duration,
+}
+
+#[cfg(feature = "serde")]
+impl CacheSessionStore {
+    fn session_key(&self, session_id: &str) -> String {
+        format!("{}id:{session_id}", self.key_prefix)
+    }
+
+    fn subject_key(&self, subject: &str) -> String {
+        format!("{}sub:{subject}", self.key_prefix)
+    }
+
+    fn provider_session_key(&self, provider_session_id: &str) -> String {
+        format!("{}sid:{provider_session_id}", self.key_prefix)
+    }
+
+    async fn session_ids(&self, key: &str) -> Result<Vec<String>, anyhow::Error> {
+        match self.cache.get(key).await? {
+            Some(ids) => Ok(serde_json::from_slice(&ids)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn update_session_ids(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: impl FnOnce(&mut Vec<String>),
+    ) -> Result<(), anyhow::Error> {
+        let mut ids = self.session_ids(key).await?;
+        update(&mut ids);
+        match ids.is_empty() {
+            true => self.cache.remove(key).await,
+            false => self.cache.set(key, serde_json::to_vec(&ids)?, ttl).await,
+        }
+    }
+
+    async fn remove_sessions(&self, key: &str) -> Result<(), anyhow::Error> {
+        for session_id in self.session_ids(key).await? {
+            self.cache.remove(&self.session_key(&session_id)).await?;
+        }
+
+        self.cache.remove(key).await
+    }
+}
+
+#[cfg(feature = "serde")]
+#[async_trait]
+impl SessionStore for CacheSessionStore {
+    async fn store(&self, session: Session) -> Result<(), anyhow::Error> {
+        let ttl = match session.expires_at {
+            Some(expires_at) => expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+                .max(Duration::from_secs(1)),
+            None => self.ttl,
+        };
+        // The lists outlive every session in them.
+        let list_ttl = ttl.max(self.ttl);
+
+        let add = |ids: &mut Vec<String>| {
+            if !ids.contains(&session.id) {
+                ids.push(session.id.clone());
+            }
+        };
+        self.update_session_ids(&self.subject_key(&session.subject), list_ttl, add)
+            .await?;
+        if let Some(provider_session_id) = &session.provider_session_id {
+            self.update_session_ids(&self.provider_session_key(provider_session_id), list_ttl, add)
+                .await?;
+        }
+
+        self.cache
+            .set(&self.session_key(&session.id), serde_json::to_vec(&session)?, ttl)
+            .await
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>, anyhow::Error> {
+        let Some(session) = self.cache.get(&self.session_key(session_id)).await? else {
+            return Ok(None);
+        };
+
+        let session = serde_json::from_slice::<Session>(&session)?;
+        Ok(Some(session).filter(|session| !session.is_expired(SystemTime::now())))
+    }
+
+    async fn load_by_subject(&self, subject: &str) -> Result<Vec<Session>, anyhow::Error> {
+        let mut sessions = Vec::new();
+        for session_id in self.session_ids(&self.subject_key(subject)).await? {
+            if let Some(session) = self.load(&session_id).await? {
+                sessions.push(session);
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    async fn remove(&self, session_id: &str) -> Result<(), anyhow::Error> {
+        if let Some(session) = self.load(session_id).await? {
+            let remove = |ids: &mut Vec<String>| ids.retain(|id| id != session_id);
+            self.update_session_ids(&self.subject_key(&session.subject), self.ttl, remove)
+                .await?;
+            if let Some(provider_session_id) = &session.provider_session_id {
+                self.update_session_ids(&self.provider_session_key(provider_session_id), self.ttl, remove)
+                    .await?;
+            }
+        }
+
+        self.cache.remove(&self.session_key(session_id)).await
+    }
+
+    async fn remove_by_subject(&self, subject: &str) -> Result<(), anyhow::Error> {
+        self.remove_sessions(&self.subject_key(subject)).await
+    }
+
+    async fn remove_by_provider_session(&self, provider_session_id: &str) -> Result<(), anyhow::Error> {
+        self.remove_sessions(&self.provider_session_key(provider_session_id))
+            .await
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionEviction {
+    #[default]
+    EvictOldest,
+    RejectNew,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionPolicy {
+    pub max_sessions_per_user: Option<usize>,
+    pub eviction: SessionEviction,
+}
+
+impl SessionPolicy {
+    pub async fn enforce(&self, store: &dyn SessionStore, subject: &str) -> Result<bool, anyhow::Error> {
+        let Some(max_sessions) = self.max_sessions_per_user else {
+            return Ok(true);
+        };
+
+        let mut sessions = store.load_by_subject(subject).await?;
+        if sessions.len() < max_sessions {
+            return Ok(true);
+        }
+
+        match self.eviction {
+            SessionEviction::RejectNew => Ok(false),
+            SessionEviction::EvictOldest => {
+                sessions.sort_by_key(|s| s.created_at);
+                let evict_count = sessions.len() + 1 - max_sessions.max(1);
+                for session in &sessions[..evict_count] {
+                    store.remove(&session.id).await?;
+                }
+
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignOutEvent {
+    Session(String),
+    Subject(String),
+    ProviderSession(String),
+}
+
+#[async_trait]
+pub trait SignOutNotifier: Send + Sync + 'static {
+    async fn notify(&self, event: &SignOutEvent) -> Result<(), anyhow::Error>;
+}
+
+/// Notifies other instances of sessions removed through it, e.g. by cookie sign-out or back-channel logout.
+/// Events received from other instances are passed to [`apply_remote_sign_outs`](Self::apply_remote_sign_outs),
+/// which removes the sessions without notifying again.
+pub struct NotifyingSessionStore<Store: SessionStore, Notifier: SignOutNotifier> {
+    pub store: Store,
+    pub notifier: Notifier,
+}
+
+impl<Store, Notifier> NotifyingSessionStore<Store, Notifier>
+where
+    Store: SessionStore,
+    Notifier: SignOutNotifier,
+{
+    pub async fn apply_remote_sign_out(&self, event: &SignOutEvent) -> Result<(), anyhow::Error> {
+        match event {
+            SignOutEvent::Session(session_id) => self.store.remove(session_id).await,
+            SignOutEvent::Subject(subject) => self.store.remove_by_subject(subject).await,
+            SignOutEvent::ProviderSession(provider_session_id) => {
+                self.store.remove_by_provider_session(provider_session_id).await
+            }
+        }
+    }
+
+    /// Applies the events other instances published until `events` ends, e.g. run on a task subscribed to the
+    /// channel the notifier publishes to. Events that fail to apply are skipped.
+    pub async fn apply_remote_sign_outs(&self, events: impl Stream<Item = SignOutEvent>) {
+        let mut events = std::pin::pin!(events);
+        while let Some(event) = events.next().await {
+            match self.apply_remote_sign_out(&event).await {
+                Ok(()) => {}
+                #[cfg(feature = "tracing")]
+                Err(err) => tracing::warn!(?event, "Failed to apply remote sign-out: {err}"),
+                #[cfg(not(feature = "tracing"))]
+                Err(_) => {}
+            }
+        }
+    }
+
+    async fn sign_out(&self, event: SignOutEvent) -> Result<(), anyhow::Error> {
+        self.apply_remote_sign_out(&event).await?;
+        self.notifier.notify(&event).await
+    }
+}
+
+#[async_trait]
+impl<Store, Notifier> SessionStore for NotifyingSessionStore<Store, Notifier>
+where
+    Store: SessionStore,
+    Notifier: SignOutNotifier,
+{
+    async fn store(&self, session: Session) -> Result<(), anyhow::Error> {
+        self.store.store(session).await
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>, anyhow::Error> {
+        self.store.load(session_id).await
+    }
+
+    async fn load_by_subject(&self, subject: &str) -> Result<Vec<Session>, anyhow::Error> {
+        self.store.load_by_subject(subject).await
+    }
+
+    async fn remove(&self, session_id: &str) -> Result<(), anyhow::Error> {
+        self.sign_out(SignOutEvent::Session(session_id.to_owned())).await
+    }
+
+    async fn remove_by_subject(&self, subject: &str) -> Result<(), anyhow::Error> {
+        self.sign_out(SignOutEvent::Subject(subject.to_owned())).await
+    }
+
+    async fn remove_by_provider_session(&self, provider_session_id: &str) -> Result<(), anyhow::Error> {
+        self.sign_out(SignOutEvent::ProviderSession(provider_session_id.to_owned()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingNotifier(Mutex<Vec<SignOutEvent>>);
+
+    #[async_trait]
+    impl SignOutNotifier for RecordingNotifier {
+        async fn notify(&self, event: &SignOutEvent) -> Result<(), anyhow::Error> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn session(id: &str, subject: &str) -> Session {
+        let now = SystemTime::now();
+        Session {
+            id: id.to_owned(),
+            subject: subject.to_owned(),
+            provider_session_id: None,
+            principal: UserPrincipal {
+                claims: Default::default(),
+            },
+            created_at: now,
+            validated_at: now,
+            expires_at: None,
+            is_persistent: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn local_removal_is_notified() {
+        let store = NotifyingSessionStore {
+            store: InMemorySessionStore::new(),
+            notifier: RecordingNotifier::default(),
+        };
+        store.store(session("s1", "alice")).await.unwrap();
+
+        store.remove("s1").await.unwrap();
+
+        assert!(store.load("s1").await.unwrap().is_none());
+        assert_eq!(
+            *store.notifier.0.lock().unwrap(),
+            [SignOutEvent::Session("s1".to_owned())]
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_events_are_applied_without_notifying() {
+        let store = NotifyingSessionStore {
+            store: InMemorySessionStore::new(),
+            notifier: RecordingNotifier::default(),
+        };
+        store.store(session("s1", "alice")).await.unwrap();
+        store.store(session("s2", "bob")).await.unwrap();
+
+        let events = futures::stream::iter([SignOutEvent::Subject("alice".to_owned())]);
+        store.apply_remote_sign_outs(events).await;
+
+        assert!(store.load("s1").await.unwrap().is_none());
+        assert!(store.load("s2").await.unwrap().is_some());
+        assert!(store.notifier.0.lock().unwrap().is_empty());
+    }
+}
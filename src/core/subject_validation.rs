@@ -0,0 +1,81 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    sync::{PoisonError, RwLock},
+};
+
+use async_trait::async_trait;
+
+use super::principal::{claim_types, UserPrincipal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubjectValidation {
+    Allow,
+    Deny,
+}
+
+/// Runs after a scheme authenticated a request and before the result is inserted into the request
+/// extensions, so accounts can be cut off centrally while their credentials are still valid.
+#[async_trait]
+pub trait SubjectValidator: Send + Sync + 'static {
+    async fn validate(&self, principal: &UserPrincipal) -> Result<SubjectValidation, anyhow::Error>;
+}
+
+/// The scheme error of requests a [`SubjectValidator`] denied.
+#[derive(Debug)]
+pub struct SubjectDenied;
+
+impl Display for SubjectDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Subject is not allowed to authenticate")
+    }
+}
+
+impl std::error::Error for SubjectDenied {}
+
+/// Denies principals whose `sub` claim is blocked. Share it through an `Arc` to block subjects at runtime.
+#[derive(Debug, Default)]
+pub struct SubjectBlocklist {
+    subjects: RwLock<HashSet<String>>,
+}
+
+impl SubjectBlocklist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn block(&self, subject: impl Into<String>) {
+        self.subjects
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(subject.into());
+    }
+
+    pub fn unblock(&self, subject: &str) {
+        self.subjects
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(subject);
+    }
+
+    pub fn is_blocked(&self, subject: &str) -> bool {
+        self.subjects
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(subject)
+    }
+}
+
+#[async_trait]
+impl SubjectValidator for SubjectBlocklist {
+    async fn validate(&self, principal: &UserPrincipal) -> Result<SubjectValidation, anyhow::Error> {
+        let blocked = principal
+            .claim_strs(claim_types::SUBJECT)
+            .any(|subject| self.is_blocked(subject));
+        Ok(if blocked {
+            SubjectValidation::Deny
+        } else {
+            SubjectValidation::Allow
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    borrow::Cow,
+    future::{ready, Ready},
+    sync::Arc,
+};
+
+use http::HeaderName;
+
+use super::authorization::{AuthorizationHandlerContext, AuthorizationRequirement};
+
+/// Finds the tenant a request is addressed to.
+pub trait TenantResolver: Send + Sync + 'static {
+    fn resolve(&self, context: &AuthorizationHandlerContext<'_>) -> Option<String>;
+}
+
+pub enum TenantSource {
+    RouteParam(String),
+    Header(HeaderName),
+    /// The first label of the `Host` header, e.g. `acme` for `acme.example.com`.
+    Subdomain,
+}
+
+impl TenantResolver for TenantSource {
+    fn resolve(&self, context: &AuthorizationHandlerContext<'_>) -> Option<String> {
+        match self {
+            TenantSource::RouteParam(name) => context.route_param(name).map(str::to_owned),
+            TenantSource::Header(header) => Some(context.headers().get(header)?.to_str().ok()?.to_owned()),
+            TenantSource::Subdomain => {
+                let host = context.headers().get(http::header::HOST)?.to_str().ok()?;
+                let (subdomain, _) = host.split_once('.')?;
+                Some(subdomain.to_owned())
+            }
+        }
+    }
+}
+
+/// Succeeds when the tenant the request is addressed to is one of the values of the user's tenant claim.
+#[derive(Clone)]
+pub struct TenantMatchRequirement {
+    claim_type: String,
+    resolver: Arc<dyn TenantResolver>,
+}
+
+impl TenantMatchRequirement {
+    pub fn new(claim_type: impl Into<String>, resolver: impl TenantResolver) -> Self {
+        Self {
+            claim_type: claim_type.into(),
+            resolver: Arc::new(resolver),
+        }
+    }
+
+    pub fn route_param(claim_type: impl Into<String>, param: impl Into<String>) -> Self {
+        Self::new(claim_type, TenantSource::RouteParam(param.into()))
+    }
+
+    pub fn header(claim_type: impl Into<String>, header: HeaderName) -> Self {
+        Self::new(claim_type, TenantSource::Header(header))
+    }
+}
+
+impl AuthorizationRequirement for TenantMatchRequirement {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("TenantMatch({})", self.claim_type))
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let Some(tenant) = self.resolver.resolve(context) else {
+            context.fail_with_message("Request doesn't identify a tenant");
+            return ready(());
+        };
+
+        let is_member = context
+            .principal()
+            .claim(&self.claim_type)
+            .is_some_and(|claim| claim.iter().any(|value| value.as_str() == Some(tenant.as_str())));
+        if is_member {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
@@ -0,0 +1,61 @@
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, Uri};
+
+use super::http::{PeerCertificate, Request, RouteParams};
+
+/// A request for handler tests, independent of the framework features.
+pub(crate) struct TestRequest(pub http::Request<()>);
+
+impl TestRequest {
+    pub fn new(method: Method, uri: &str, headers: &[(&str, &str)]) -> Self {
+        let mut request = http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        Self(request.body(()).expect("Test request is valid"))
+    }
+}
+
+impl Request for TestRequest {
+    type RequestExtensions = Extensions;
+
+    type RequestExtensionsDeref<'a> = &'a Extensions;
+
+    type RequestExtensionsDerefMut<'a> = &'a mut Extensions;
+
+    fn get_method(&self) -> &Method {
+        self.0.method()
+    }
+
+    fn get_uri(&self) -> &Uri {
+        self.0.uri()
+    }
+
+    fn get_header(&self, header: &HeaderName) -> Option<&HeaderValue> {
+        self.0.headers().get(header)
+    }
+
+    fn get_headers(&self) -> HeaderMap {
+        self.0.headers().clone()
+    }
+
+    fn get_peer_certificate(&self) -> Option<PeerCertificate> {
+        None
+    }
+
+    fn get_peer_address(&self) -> Option<std::net::SocketAddr> {
+        None
+    }
+
+    fn get_route_params(&self) -> RouteParams {
+        RouteParams::default()
+    }
+
+    fn get_extensions(&self) -> Self::RequestExtensionsDeref<'_> {
+        self.0.extensions()
+    }
+
+    fn get_extensions_mut(&mut self) -> Self::RequestExtensionsDerefMut<'_> {
+        self.0.extensions_mut()
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+use http::HeaderValue;
+
+use crate::{
+    core::{
+        http::{get_cookie, Request},
+        nonce::NonceStore,
+    },
+    data_protection::DataProtector,
+};
+
+const PROTECTION_PURPOSE: &str = "correlation";
+
+#[derive(Debug, Clone)]
+pub struct CorrelationState {
+    pub state: String,
+    pub nonce: String,
+    pub return_url: Option<String>,
+}
+
+pub struct CorrelationCookies {
+    pub cookie_prefix: String,
+    pub path: String,
+    pub lifetime: Duration,
+    pub same_site_none: bool,
+    pub protector: Arc<dyn DataProtector>,
+    pub nonce_store: Option<Arc<dyn NonceStore>>,
+}
+
+impl CorrelationCookies {
+    pub fn new(protector: Arc<dyn DataProtector>) -> Self {
+        Self {
+            cookie_prefix: ".correlation.".to_owned(),
+            path: "/".to_owned(),
+            lifetime: Duration::from_secs(15 * 60),
+            same_site_none: false,
+            protector,
+            nonce_store: None,
+        }
+    }
+
+    pub fn create(&self, return_url: Option<&str>) -> Result<(CorrelationState, HeaderValue), anyhow::Error> {
+        let correlation = CorrelationState {
+            state: random_token()?,
+            nonce: random_token()?,
+            return_url: return_url.map(str::to_owned),
+        };
+
+        let expires_at = unix_now() + self.lifetime.as_secs();
+        let payload = format!(
+            "{expires_at}\n{}\n{}",
+            correlation.nonce,
+            correlation.return_url.as_deref().unwrap_or_default()
+        );
+        let value = self
+            .protector
+            .protect(&self.purpose(&correlation.state), payload.as_bytes());
+        let cookie = self.cookie_header(&correlation.state, &value, self.lifetime)?;
+
+        Ok((correlation, cookie))
+    }
+
+    pub async fn validate(
+        &self,
+        request: &impl Request,
+        state: &str,
+    ) -> Result<(CorrelationState, HeaderValue), anyhow::Error> {
+        let name = format!("{}{state}", self.cookie_prefix);
+        let value = get_cookie(request, &name).ok_or_else(|| anyhow!("Correlation cookie is missing"))?;
+        let payload = String::from_utf8(self.protector.unprotect(&self.purpose(state), value)?)?;
+
+        let mut parts = payload.splitn(3, '\n');
+        let (Some(expires_at), Some(nonce), Some(return_url)) = (parts.next(), parts.next(), parts.next()) else {
+            bail!("Malformed correlation cookie");
+        };
+
+        let expires_at = expires_at.parse::<u64>()?;
+        if expires_at <= unix_now() {
+            bail!("Correlation cookie has expired");
+        }
+
+        if let Some(nonce_store) = &self.nonce_store {
+            let expires_at = UNIX_EPOCH + Duration::from_secs(expires_at);
+            if !nonce_store.try_use(&self.purpose(state), expires_at).await? {
+                bail!("Correlation state has already been used");
+            }
+        }
+
+        let correlation = CorrelationState {
+            state: state.to_owned(),
+            nonce: nonce.to_owned(),
+            return_url: (!return_url.is_empty()).then(|| return_url.to_owned()),
+        };
+
+        Ok((correlation, self.cookie_header(state, "", Duration::ZERO)?))
+    }
+
+    fn purpose(&self, state: &str) -> String {
+        format!("{PROTECTION_PURPOSE}:{state}")
+    }
+
+    fn cookie_header(&self, state: &str, value: &str, max_age: Duration) -> Result<HeaderValue, anyhow::Error> {
+        // Responses to form_post callbacks arrive as cross-site POSTs, which only carry SameSite=None cookies,
+        // and browsers reject SameSite=None without Secure.
+        let same_site = if self.same_site_none { "None" } else { "Lax" };
+        let cookie = format!(
+            "{}{state}={value}; Path={}; Max-Age={}; HttpOnly; Secure; SameSite={same_site}",
+            self.cookie_prefix,
+            self.path,
+            max_age.as_secs()
+        );
+
+        Ok(HeaderValue::try_from(cookie)?)
+    }
+}
+
+fn random_token() -> Result<String, anyhow::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use argon2::{
+    password_hash::{Salt, SaltString},
+    Argon2, PasswordHash, PasswordVerifier,
+};
+use async_trait::async_trait;
+
+use crate::core::{credentials::CredentialValidator, principal::UserPrincipal};
+
+#[async_trait]
+pub trait PasswordHasher: Send + Sync + 'static {
+    async fn hash(&self, password: &str) -> Result<String, anyhow::Error>;
+
+    async fn verify(&self, password: &str, hash: &str) -> Result<bool, anyhow::Error>;
+}
+
+#[derive(Default)]
+pub struct Argon2idHasher {
+    argon2: Argon2<'static>,
+}
+
+impl Argon2idHasher {
+    pub fn new(params: argon2::Params) -> Self {
+        Self {
+            argon2: Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params),
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordHasher for Argon2idHasher {
+    async fn hash(&self, password: &str) -> Result<String, anyhow::Error> {
+        let mut salt = [0u8; Salt::RECOMMENDED_LENGTH];
+        getrandom::getrandom(&mut salt)?;
+        let salt = SaltString::encode_b64(&salt).map_err(|err| anyhow!("Failed to encode salt: {err}"))?;
+        argon2::PasswordHasher::hash_password(&self.argon2, password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| anyhow!("Failed to hash password: {err}"))
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> Result<bool, anyhow::Error> {
+        let hash = PasswordHash::new(hash).map_err(|err| anyhow!("Invalid password hash: {err}"))?;
+        match self.argon2.verify_password(password.as_bytes(), &hash) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(anyhow!("Failed to verify password: {err}")),
+        }
+    }
+}
+
+pub struct BcryptHasher {
+    pub cost: u32,
+}
+
+impl Default for BcryptHasher {
+    fn default() -> Self {
+        Self {
+            cost: bcrypt::DEFAULT_COST,
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordHasher for BcryptHasher {
+    async fn hash(&self, password: &str) -> Result<String, anyhow::Error> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+
+    async fn verify(&self, password: &str, hash: &str) -> Result<bool, anyhow::Error> {
+        Ok(bcrypt::verify(password, hash)?)
+    }
+}
+
+#[async_trait]
+pub trait PasswordHashStore: Send + Sync + 'static {
+    async fn find(&self, username: &str) -> Result<Option<(String, UserPrincipal)>, anyhow::Error>;
+}
+
+pub struct HashedPasswordValidator {
+    pub store: Arc<dyn PasswordHashStore>,
+    pub hasher: Arc<dyn PasswordHasher>,
+}
+
+#[async_trait]
+impl CredentialValidator for HashedPasswordValidator {
+    async fn validate(&self, username: &str, password: &str) -> Result<Option<UserPrincipal>, anyhow::Error> {
+        let Some((hash, principal)) = self.store.find(username).await? else {
+            return Ok(None);
+        };
+
+        Ok(self.hasher.verify(password, &hash).await?.then_some(principal))
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::anyhow;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub trait DataProtector: Send + Sync + 'static {
+    fn protect(&self, purpose: &str, data: &[u8]) -> String;
+
+    fn unprotect(&self, purpose: &str, protected: &str) -> Result<Vec<u8>, anyhow::Error>;
+}
+
+pub struct HmacDataProtector {
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl HmacDataProtector {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self { keys: vec![key] }
+    }
+
+    fn signature(key: &[u8], purpose: &str, data: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(purpose.as_bytes());
+        mac.update(b".");
+        mac.update(data.as_bytes());
+        mac
+    }
+}
+
+impl DataProtector for HmacDataProtector {
+    fn protect(&self, purpose: &str, data: &[u8]) -> String {
+        let data = URL_SAFE_NO_PAD.encode(data);
+        let key = self.keys.first().map(Vec::as_slice).unwrap_or_default();
+        let signature = Self::signature(key, purpose, &data).finalize().into_bytes();
+
+        format!("{data}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn unprotect(&self, purpose: &str, protected: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let (data, signature) = protected
+            .split_once('.')
+            .ok_or_else(|| anyhow!("Malformed protected data"))?;
+        let signature = URL_SAFE_NO_PAD.decode(signature)?;
+
+        // Every key is tried so that data protected before a key rotation stays readable.
+        let valid = self
+            .keys
+            .iter()
+            .any(|key| Self::signature(key, purpose, data).verify_slice(&signature).is_ok());
+        if !valid {
+            return Err(anyhow!("Protected data has been tampered with"));
+        }
+
+        Ok(URL_SAFE_NO_PAD.decode(data)?)
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    credentials::constant_time_eq,
+    http::{parse_auth_params, AuthResponse, Request, RequestExtensions},
+    nonce::NonceStore,
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    pub fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 => hex::encode(Md5::digest(data.as_bytes())),
+            DigestAlgorithm::Sha256 => hex::encode(Sha256::digest(data.as_bytes())),
+        }
+    }
+
+    pub fn ha1(&self, username: &str, realm: &str, password: &str) -> String {
+        self.hash(&format!("{username}:{realm}:{password}"))
+    }
+}
+
+#[async_trait]
+pub trait Ha1Store: Send + Sync + 'static {
+    async fn ha1(
+        &self,
+        username: &str,
+        realm: &str,
+        algorithm: DigestAlgorithm,
+    ) -> Result<Option<String>, anyhow::Error>;
+}
+
+pub struct DigestAuthHandler {
+    pub realm: String,
+    pub algorithm: DigestAlgorithm,
+    pub opaque: String,
+    pub nonce_secret: Vec<u8>,
+    pub nonce_lifetime: Duration,
+    pub ha1_store: Arc<dyn Ha1Store>,
+    pub nonce_store: Arc<dyn NonceStore>,
+}
+
+struct StaleNonce;
+
+struct DigestRequest {
+    username: String,
+    response_data: String,
+    response: String,
+    nonce_key: String,
+    nonce_expires_at: SystemTime,
+}
+
+impl DigestAuthHandler {
+    fn create_nonce(&self) -> String {
+        let timestamp = unix_now().to_string();
+        let signature = self.nonce_signature(&timestamp).finalize().into_bytes();
+        format!("{timestamp}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn validate_nonce(&self, nonce: &str) -> Result<SystemTime, anyhow::Error> {
+        let (timestamp, signature) = nonce.split_once('.').ok_or_else(|| anyhow!("Malformed nonce"))?;
+        self.nonce_signature(timestamp)
+            .verify_slice(&URL_SAFE_NO_PAD.decode(signature)?)
+            .map_err(|_| anyhow!("Invalid nonce"))?;
+
+        Ok(UNIX_EPOCH + Duration::from_secs(timestamp.parse()?) + self.nonce_lifetime)
+    }
+
+    fn nonce_signature(&self, timestamp: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.nonce_secret).expect("HMAC accepts keys of any length");
+        mac.update(timestamp.as_bytes());
+        mac
+    }
+
+    fn parse_request(&self, request: &mut impl Request) -> Result<Option<DigestRequest>, anyhow::Error> {
+        let Some(header) = request
+            .get_header(&AUTHORIZATION)
+            .and_then(|h| h.to_str().ok()?.strip_prefix("Digest "))
+        else {
+            return Ok(None);
+        };
+
+        let params = parse_auth_params(header).ok_or_else(|| anyhow!("Invalid Digest header"))?;
+        let param = |name: &str| {
+            params
+                .get(name)
+                .map(String::as_str)
+                .ok_or_else(|| anyhow!("Digest header doesn't contain {name}"))
+        };
+
+        if param("realm")? != self.realm {
+            bail!("Digest realm doesn't match");
+        }
+        if params.get("algorithm").map(String::as_str).unwrap_or("MD5") != self.algorithm.name() {
+            bail!("Digest algorithm doesn't match");
+        }
+        if param("qop")? != "auth" {
+            bail!("Only qop=auth is supported");
+        }
+        if params.get("opaque").map(String::as_str).unwrap_or_default() != self.opaque {
+            bail!("Digest opaque doesn't match");
+        }
+
+        let digest_uri = param("uri")?;
+        let request_uri = request.get_uri();
+        let request_uri = request_uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        if digest_uri != request_uri {
+            bail!("Digest uri doesn't match the request uri");
+        }
+
+        let nonce = param("nonce")?;
+        let nonce_expires_at = self.validate_nonce(nonce)?;
+        if nonce_expires_at <= SystemTime::now() {
+            request.get_extensions_mut().insert(StaleNonce);
+            bail!("Digest nonce is stale");
+        }
+
+        let nc = param("nc")?;
+        let cnonce = param("cnonce")?;
+        let ha2 = self.algorithm.hash(&format!("{}:{digest_uri}", request.get_method()));
+
+        Ok(Some(DigestRequest {
+            username: param("username")?.to_owned(),
+            response_data: format!("{nonce}:{nc}:{cnonce}:auth:{ha2}"),
+            response: param("response")?.to_lowercase(),
+            nonce_key: format!("{nonce}:{nc}"),
+            nonce_expires_at,
+        }))
+    }
+
+    fn challenge_header(&self, stale: bool) -> HeaderValue {
+        let mut challenge = format!(
+            "Digest realm=\"{}\", qop=\"auth\", algorithm={}, nonce=\"{}\", opaque=\"{}\"",
+            self.realm,
+            self.algorithm.name(),
+            self.create_nonce(),
+            self.opaque,
+        );
+        if stale {
+            challenge.push_str(", stale=true");
+        }
+
+        HeaderValue::try_from(challenge).unwrap_or_else(|_| HeaderValue::from_static("Digest"))
+    }
+}
+
+impl AuthenticationHandler for DigestAuthHandler {
+    type AuthFut = Pin<Box<dyn Future<Output = AuthenticationResult> + Send>>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let digest_request = match self.parse_request(request) {
+            Ok(Some(digest_request)) => digest_request,
+            Ok(None) => return Box::pin(ready(Err(AuthenticationError::NoResult))),
+            Err(err) => return Box::pin(ready(Err(AuthenticationError::fail(err)))),
+        };
+
+        let ha1_store = self.ha1_store.clone();
+        let nonce_store = self.nonce_store.clone();
+        let realm = self.realm.clone();
+        let algorithm = self.algorithm;
+        Box::pin(async move {
+            let ha1 = match ha1_store.ha1(&digest_request.username, &realm, algorithm).await {
+                Ok(Some(ha1)) => ha1,
+                Ok(None) => {
+                    return Err(AuthenticationError::Fail(AuthError::InvalidCredentials(
+                        "Unknown user".to_owned(),
+                    )))
+                }
+                Err(err) => return Err(AuthenticationError::fail(err)),
+            };
+
+            let expected = algorithm.hash(&format!("{ha1}:{}", digest_request.response_data));
+            if !constant_time_eq(expected.as_bytes(), digest_request.response.as_bytes()) {
+                return Err(AuthenticationError::Fail(AuthError::InvalidCredentials(
+                    "Digest response doesn't match".to_owned(),
+                )));
+            }
+
+            match nonce_store
+                .try_use(&digest_request.nonce_key, digest_request.nonce_expires_at)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(AuthenticationError::Fail(AuthError::InvalidToken(
+                        "Digest nonce count was replayed".to_owned(),
+                    )))
+                }
+                Err(err) => return Err(AuthenticationError::fail(err)),
+            }
+
+            Ok(UserPrincipal {
+                claims: [(
+                    claim_types::SUBJECT.into(),
+                    ClaimValue::PlainValue(ClaimPlainValue::String(digest_request.username.into())),
+                )]
+                .into(),
+            })
+        })
+    }
+
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut {
+        let stale = request.get_extensions().get::<StaleNonce>().is_some();
+
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, self.challenge_header(stale))]),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+    use crate::core::{nonce::InMemoryNonceStore, testing::TestRequest};
+
+    // The example of RFC 7616, section 3.9.1.
+    const USERNAME: &str = "Mufasa";
+    const PASSWORD: &str = "Circle of Life";
+    const REALM: &str = "http-auth@example.org";
+    const URI: &str = "/dir/index.html";
+    const NONCE: &str = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+    const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn response(algorithm: DigestAlgorithm, password: &str, method: &str, uri: &str, nonce: &str) -> String {
+        let ha1 = algorithm.ha1(USERNAME, REALM, password);
+        let ha2 = algorithm.hash(&format!("{method}:{uri}"));
+        algorithm.hash(&format!("{ha1}:{nonce}:00000001:{CNONCE}:auth:{ha2}"))
+    }
+
+    #[test]
+    fn rfc_7616_md5_response() {
+        assert_eq!(
+            response(DigestAlgorithm::Md5, PASSWORD, "GET", URI, NONCE),
+            "8ca523f5e9506fed4657c9700eebdbec"
+        );
+    }
+
+    #[test]
+    fn rfc_7616_sha256_response() {
+        assert_eq!(
+            response(DigestAlgorithm::Sha256, PASSWORD, "GET", URI, NONCE),
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+    }
+
+    struct Passwords;
+
+    #[async_trait]
+    impl Ha1Store for Passwords {
+        async fn ha1(
+            &self,
+            username: &str,
+            realm: &str,
+            algorithm: DigestAlgorithm,
+        ) -> Result<Option<String>, anyhow::Error> {
+            Ok((username == USERNAME).then(|| algorithm.ha1(username, realm, PASSWORD)))
+        }
+    }
+
+    fn handler() -> DigestAuthHandler {
+        DigestAuthHandler {
+            realm: REALM.to_owned(),
+            algorithm: DigestAlgorithm::Sha256,
+            opaque: "FQhe/qaDtGqoSkBqnTKsCSyLmLcdnXUj".to_owned(),
+            nonce_secret: b"nonce secret".to_vec(),
+            nonce_lifetime: Duration::from_secs(300),
+            ha1_store: Arc::new(Passwords),
+            nonce_store: Arc::new(InMemoryNonceStore::new()),
+        }
+    }
+
+    fn request(handler: &DigestAuthHandler, uri: &str, nonce: &str, response: &str) -> TestRequest {
+        let authorization = format!(
+            "Digest username=\"{USERNAME}\", realm=\"{REALM}\", uri=\"{uri}\", algorithm=SHA-256, \
+             nonce=\"{nonce}\", nc=00000001, cnonce=\"{CNONCE}\", qop=auth, response=\"{response}\", \
+             opaque=\"{}\"",
+            handler.opaque,
+        );
+        TestRequest::new(Method::GET, uri, &[("authorization", &authorization)])
+    }
+
+    #[tokio::test]
+    async fn valid_response_is_accepted() {
+        let handler = handler();
+        let nonce = handler.create_nonce();
+        let response = response(DigestAlgorithm::Sha256, PASSWORD, "GET", URI, &nonce);
+
+        let principal = handler
+            .authenticate(&mut request(&handler, URI, &nonce, &response))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(principal.claim_strs(claim_types::SUBJECT).next(), Some(USERNAME));
+    }
+
+    #[tokio::test]
+    async fn response_for_another_uri_is_rejected() {
+        let handler = handler();
+        let nonce = handler.create_nonce();
+        let response = response(DigestAlgorithm::Sha256, PASSWORD, "GET", URI, &nonce);
+
+        let result = handler
+            .authenticate(&mut request(&handler, "/dir/other.html", &nonce, &response))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidCredentials(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn wrong_password_is_rejected() {
+        let handler = handler();
+        let nonce = handler.create_nonce();
+        let response = response(DigestAlgorithm::Sha256, "wrong password", "GET", URI, &nonce);
+
+        let result = handler
+            .authenticate(&mut request(&handler, URI, &nonce, &response))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidCredentials(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn forged_nonce_is_rejected() {
+        let handler = handler();
+        let response = response(DigestAlgorithm::Sha256, PASSWORD, "GET", URI, NONCE);
+
+        let result = handler
+            .authenticate(&mut request(&handler, URI, NONCE, &response))
+            .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -1,21 +1,28 @@
 use std::{
     cell::{Ref, RefMut},
     future::{ready, Future, Ready},
+    marker::PhantomData,
     pin::Pin,
     rc::Rc,
     sync::Arc,
 };
 
 use actix_web::{
+    body::BoxBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpMessage, ResponseError,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder, ResponseError,
 };
 use http::HeaderName;
 
 use crate::core::{
-    authentication::{AuthenticationService, CompoundAuthenticationHandler},
+    authentication::{AuthenticationService, CompoundAuthenticationHandler, SuccessAuthenticationResult},
     authorization::{AuthorizationPolicy, AuthorizationRequirement},
-    http::{AuthResponse, RequestExtensions},
+    authorization_map::{AuthorizationMap, RouteAccess},
+    endpoint::{
+        missing_endpoint_authorization, EndpointAuthorization, EndpointAuthorized, EndpointMetadata, InRole, RoleName,
+    },
+    http::{AuthResponse, PeerCertificate, RequestExtensions, ResponseHead, RouteParams},
+    response_headers::PendingResponseHeaders,
 };
 
 impl RequestExtensions for actix_web::dev::Extensions {
@@ -39,6 +46,10 @@ impl crate::core::http::Request for ServiceRequest {
 
     type RequestExtensionsDerefMut<'a> = RefMut<'a, Self::RequestExtensions>;
 
+    fn get_method(&self) -> &http::Method {
+        self.method()
+    }
+
     fn get_uri(&self) -> &http::Uri {
         self.uri()
     }
@@ -47,6 +58,32 @@ impl crate::core::http::Request for ServiceRequest {
         self.headers().get(header)
     }
 
+    fn get_headers(&self) -> http::HeaderMap {
+        self.headers()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    fn get_peer_certificate(&self) -> Option<PeerCertificate> {
+        self.conn_data::<PeerCertificate>()
+            .cloned()
+            .or_else(|| self.extensions().get::<PeerCertificate>().cloned())
+    }
+
+    fn get_peer_address(&self) -> Option<std::net::SocketAddr> {
+        self.peer_addr()
+    }
+
+    fn get_route_params(&self) -> RouteParams {
+        RouteParams(
+            self.match_info()
+                .iter()
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+        )
+    }
+
     fn get_extensions(&self) -> Self::RequestExtensionsDeref<'_> {
         self.extensions()
     }
@@ -61,13 +98,43 @@ impl ResponseError for AuthResponse {
         self.status_code
     }
 
-    fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        let mut res = actix_web::HttpResponse::new(self.status_code());
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let mut res = HttpResponse::new(self.status_code());
         for (name, value) in self.headers.iter() {
-            res.headers_mut().insert(name.clone(), value.clone());
+            res.headers_mut().append(name.clone(), value.clone());
         }
 
-        res
+        res.set_body(BoxBody::new(self.body.clone()))
+    }
+}
+
+impl Responder for AuthResponse {
+    type Body = BoxBody;
+
+    fn respond_to(self, _: &HttpRequest) -> HttpResponse<Self::Body> {
+        self.error_response()
+    }
+}
+
+#[cfg(feature = "login")]
+impl<Handler> crate::login::LoginEndpoint<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub fn actix_handler(
+        self: Arc<Self>,
+    ) -> impl Fn(HttpRequest, actix_web::web::Bytes) -> Pin<Box<dyn Future<Output = AuthResponse>>> + Clone + 'static
+    {
+        move |request, body| {
+            let endpoint = self.clone();
+            Box::pin(async move {
+                let content_type = request
+                    .headers()
+                    .get(http::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok());
+                endpoint.handle(content_type, &body).await
+            })
+        }
     }
 }
 
@@ -120,8 +187,48 @@ where
         let inner = self.inner.clone();
 
         Box::pin(async move {
-            auth_service.authenticate(&mut req).await;
-            inner.call(req).await
+            auth_service.handle_request(&mut req).await;
+            let response_headers = auth_service.success_response_headers(&req);
+            let pending_headers = req.extensions().get::<PendingResponseHeaders>().cloned();
+            let authentication = auth_service
+                .response_hook()
+                .and_then(|_| req.extensions().get::<SuccessAuthenticationResult>().cloned());
+            let mut response = inner.call(req).await?;
+            if let Some(headers) = response_headers {
+                let missing = headers
+                    .iter()
+                    .filter(|(name, _)| !response.headers().contains_key(*name))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect::<Vec<_>>();
+                for (name, value) in missing {
+                    response.headers_mut().append(name, value);
+                }
+            }
+            if let Some(pending_headers) = pending_headers {
+                for (name, value) in pending_headers.take().iter() {
+                    response.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+
+            if let Some(hook) = auth_service.response_hook() {
+                let mut head = ResponseHead {
+                    status_code: response.status(),
+                    headers: response
+                        .headers()
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect(),
+                };
+                hook.on_response(authentication.as_ref(), &mut head).await;
+
+                *response.response_mut().status_mut() = head.status_code;
+                response.headers_mut().clear();
+                for (name, value) in head.headers.iter() {
+                    response.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+
+            Ok(response)
         })
     }
 }
@@ -196,3 +303,118 @@ where
         })
     }
 }
+
+pub struct AuthorizeMap<Handler: CompoundAuthenticationHandler>(pub Arc<AuthorizationMap<Handler>>);
+
+impl<S, B, Handler> Transform<S, ServiceRequest> for AuthorizeMap<Handler>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    Handler: CompoundAuthenticationHandler,
+    Handler::AuthFut: Send,
+    Handler::ChallengeFut: Send,
+    Handler::ForbidFut: Send,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthorizeMapMiddleware<S, Handler>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthorizeMapMiddleware {
+            inner: Rc::new(service),
+            map: self.0.clone(),
+        }))
+    }
+}
+
+pub struct AuthorizeMapMiddleware<S, Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    inner: Rc<S>,
+    map: Arc<AuthorizationMap<Handler>>,
+}
+
+impl<S, B, Handler> Service<ServiceRequest> for AuthorizeMapMiddleware<S, Handler>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    Handler: CompoundAuthenticationHandler,
+    Handler::AuthFut: Send,
+    Handler::ChallengeFut: Send,
+    Handler::ForbidFut: Send,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(inner);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let map = self.map.clone();
+        let inner = self.inner.clone();
+        req.extensions_mut().insert(EndpointAuthorization(map.clone()));
+        Box::pin(async move {
+            match map.authorize(&mut req).await {
+                Ok(()) => inner.call(req).await,
+                Err(response) => Err(response.into()),
+            }
+        })
+    }
+}
+
+fn authorize_request(
+    req: &HttpRequest,
+    access: RouteAccess,
+) -> impl Future<Output = Result<ServiceRequest, Error>> + 'static {
+    let authorization = req
+        .extensions()
+        .get::<EndpointAuthorization>()
+        .cloned()
+        .ok_or_else(|| missing_endpoint_authorization("wrap the app in AuthorizeMap"));
+    let mut request = ServiceRequest::from_request(req.clone());
+
+    async move {
+        let authorization = authorization?;
+        match authorization.0.authorize_actix(&mut request, access).await {
+            Ok(()) => Ok(request),
+            Err(response) => Err(response.into()),
+        }
+    }
+}
+
+impl<M: EndpointMetadata> FromRequest for EndpointAuthorized<M> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let authorized = authorize_request(req, M::access());
+        Box::pin(async move {
+            authorized.await?;
+            Ok(EndpointAuthorized(PhantomData))
+        })
+    }
+}
+
+impl<R: RoleName> FromRequest for InRole<R> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let authorized = authorize_request(req, RouteAccess::Role(R::NAME.to_owned()));
+        Box::pin(async move {
+            let request = authorized.await?;
+            let principal = request
+                .extensions()
+                .get::<SuccessAuthenticationResult>()
+                .map(|result| result.principal.clone())
+                .expect("Role access is only granted to authenticated users");
+
+            Ok(InRole(principal, PhantomData))
+        })
+    }
+}
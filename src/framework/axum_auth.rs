@@ -1,12 +1,248 @@
-use axum_core::response::IntoResponse;
+use std::{collections::HashMap, future::Future, marker::PhantomData, pin::Pin};
 
-use crate::core::http::AuthResponse;
+use axum::extract::Path;
+use axum_core::{extract::FromRequestParts, response::IntoResponse};
+use http::{header::CONTENT_TYPE, request::Parts, Request};
+use tower::{Layer, Service};
+
+use crate::core::{
+    authentication::SuccessAuthenticationResult,
+    authorization_map::RouteAccess,
+    endpoint::{
+        missing_endpoint_authorization, EndpointAuthorization, EndpointAuthorized, EndpointMetadata, InRole, RoleName,
+    },
+    grpc::rpc_auth_response,
+    http::{AuthResponse, RouteParams},
+};
 
 impl IntoResponse for AuthResponse {
     fn into_response(self) -> axum_core::response::Response {
-        let mut response = self.status_code.into_response();
+        let has_body = !self.body.is_empty();
+        let mut response = (self.status_code, self.body).into_response();
+        let default_content_type = response.headers_mut().remove(CONTENT_TYPE);
         *response.headers_mut() = self.headers;
 
+        if let Some(content_type) = default_content_type.filter(|_| has_body) {
+            response.headers_mut().entry(CONTENT_TYPE).or_insert(content_type);
+        }
+
         response
     }
 }
+
+#[cfg(feature = "login")]
+impl<Handler> crate::login::LoginEndpoint<Handler>
+where
+    Handler: crate::core::authentication::CompoundAuthenticationHandler,
+    Handler::SignInFut: Send,
+{
+    pub fn axum_handler(
+        self: std::sync::Arc<Self>,
+    ) -> impl Fn(http::HeaderMap, String) -> std::pin::Pin<Box<dyn std::future::Future<Output = AuthResponse> + Send>>
+           + Clone
+           + Send
+           + Sync
+           + 'static {
+        move |headers, body| {
+            let endpoint = self.clone();
+            Box::pin(async move {
+                let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+                endpoint.handle(content_type, body.as_bytes()).await
+            })
+        }
+    }
+}
+
+async fn path_params(parts: &mut Parts) -> RouteParams {
+    Path::<HashMap<String, String>>::from_request_parts(parts, &())
+        .await
+        .map(|Path(params)| RouteParams(params))
+        .unwrap_or_default()
+}
+
+async fn authorize_parts(parts: &mut Parts, access: RouteAccess) -> Result<(), AuthResponse> {
+    let authorization = parts
+        .extensions
+        .get::<EndpointAuthorization>()
+        .cloned()
+        .ok_or_else(|| missing_endpoint_authorization("add AuthorizationMapLayer"))?;
+
+    // Path reads its parameters from the extensions, so they are extracted before the extensions are moved.
+    let route_params = match parts.extensions.get::<RouteParams>() {
+        Some(_) => None,
+        None => Some(path_params(parts).await),
+    };
+
+    // The authentication result lives in the extensions, so they are moved into the request and back.
+    let mut request = http::Request::new(());
+    *request.method_mut() = parts.method.clone();
+    *request.uri_mut() = parts.uri.clone();
+    *request.headers_mut() = parts.headers.clone();
+    *request.extensions_mut() = std::mem::take(&mut parts.extensions);
+    if let Some(route_params) = route_params {
+        request.extensions_mut().insert(route_params);
+    }
+
+    let result = authorization.0.authorize_http(&mut request, access).await;
+    let result = result.map_err(|response| rpc_auth_response(&request, response));
+    parts.extensions = std::mem::take(request.extensions_mut());
+
+    result
+}
+
+#[async_trait::async_trait]
+impl<M, S> FromRequestParts<S> for EndpointAuthorized<M>
+where
+    M: EndpointMetadata,
+    S: Send + Sync,
+{
+    type Rejection = AuthResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        authorize_parts(parts, M::access()).await?;
+        Ok(EndpointAuthorized(PhantomData))
+    }
+}
+
+#[async_trait::async_trait]
+impl<R, S> FromRequestParts<S> for InRole<R>
+where
+    R: RoleName,
+    S: Send + Sync,
+{
+    type Rejection = AuthResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        authorize_parts(parts, RouteAccess::Role(R::NAME.to_owned())).await?;
+        let principal = parts
+            .extensions
+            .get::<SuccessAuthenticationResult>()
+            .map(|result| result.principal.clone())
+            .expect("Role access is only granted to authenticated users");
+
+        Ok(InRole(principal, PhantomData))
+    }
+}
+
+/// Exposes axum's path parameters to authorization requirements. Add it to routes with
+/// `route_layer`/`MethodRouter::layer` so it runs after routing, outside of the authorize layer.
+#[derive(Clone, Copy, Default)]
+pub struct RouteParamsLayer;
+
+impl<S> Layer<S> for RouteParamsLayer {
+    type Service = RouteParamsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RouteParamsService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RouteParamsService<S> {
+    inner: S,
+}
+
+impl<S, Body> Service<Request<Body>> for RouteParamsService<S>
+where
+    S: Service<Request<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+    Body: Send + 'static,
+{
+    type Response = S::Response;
+
+    type Error = S::Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let route_params = path_params(&mut parts).await;
+            parts.extensions.insert(route_params);
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Extension, Router};
+    use http::StatusCode;
+
+    use super::*;
+    use crate::core::endpoint::{EndpointAuthorizeFut, EndpointAuthorizer};
+
+    struct OwnItemOnly;
+
+    impl EndpointAuthorizer for OwnItemOnly {
+        fn authorize_http<'a>(
+            &'a self,
+            request: &'a mut http::Request<()>,
+            _: RouteAccess,
+        ) -> EndpointAuthorizeFut<'a> {
+            let is_own = request
+                .extensions()
+                .get::<RouteParams>()
+                .and_then(|params| params.0.get("id"))
+                .is_some_and(|id| id == "42");
+            Box::pin(async move {
+                match is_own {
+                    true => Ok(()),
+                    false => Err(AuthResponse {
+                        status_code: StatusCode::FORBIDDEN,
+                        headers: Default::default(),
+                        body: Vec::new(),
+                    }),
+                }
+            })
+        }
+
+        #[cfg(feature = "actix")]
+        fn authorize_actix<'a>(
+            &'a self,
+            _: &'a mut actix_web::dev::ServiceRequest,
+            _: RouteAccess,
+        ) -> crate::core::endpoint::LocalEndpointAuthorizeFut<'a> {
+            unreachable!()
+        }
+    }
+
+    struct Authenticated;
+
+    impl EndpointMetadata for Authenticated {
+        fn access() -> RouteAccess {
+            RouteAccess::Authenticated
+        }
+    }
+
+    fn router() -> Router {
+        Router::new().route("/items/:id", get(|_: EndpointAuthorized<Authenticated>| async {}))
+    }
+
+    async fn status(uri: &str) -> StatusCode {
+        let router = router().layer(Extension(EndpointAuthorization(std::sync::Arc::new(OwnItemOnly))));
+        call(router, uri).await
+    }
+
+    async fn call(mut router: Router, uri: &str) -> StatusCode {
+        let request = Request::builder().uri(uri).body(axum::body::Body::empty()).unwrap();
+
+        router.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn extractor_sees_route_params_without_layer() {
+        assert_eq!(status("/items/42").await, StatusCode::OK);
+        assert_eq!(status("/items/7").await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn missing_authorization_map_is_server_error() {
+        assert_eq!(call(router(), "/items/42").await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
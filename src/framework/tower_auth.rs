@@ -1,28 +1,27 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
-use http::{HeaderName, Request};
+use http::{HeaderMap, HeaderName, Request, Response};
+use pin_project::pin_project;
 use tower::{Layer, Service};
 
 use crate::core::{
-    authentication::{AuthenticationResult, AuthenticationService, CompoundAuthenticationHandler},
+    authentication::{
+        complete_authentication, AuthenticationService, CompoundAuthenticationHandler, CompoundAuthenticationResult,
+        ResponseHook, SuccessAuthenticationResult, ValidateSubject,
+    },
     authorization::{AuthorizationPolicy, AuthorizationRequirement},
-    http::{AuthResponse, RequestExtensions},
+    authorization_map::{AuthorizationMap, RouteAccess},
+    endpoint::EndpointAuthorization,
+    grpc::rpc_auth_response,
+    http::{AuthResponse, PeerAddress, PeerCertificate, ResponseHead, RouteParams},
+    response_headers::{merge_response_headers, PendingResponseHeaders},
 };
 
-impl RequestExtensions for http::Extensions {
-    fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
-        self.get()
-    }
-
-    fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
-        self.get_mut()
-    }
-
-    fn insert<T: Send + Sync + 'static>(&mut self, ext: T) -> Option<T> {
-        self.insert(ext)
-    }
-}
-
 impl<Body: Send + 'static> crate::core::http::Request for Request<Body> {
     type RequestExtensions = http::Extensions;
 
@@ -30,6 +29,10 @@ impl<Body: Send + 'static> crate::core::http::Request for Request<Body> {
 
     type RequestExtensionsDerefMut<'a> = &'a mut http::Extensions;
 
+    fn get_method(&self) -> &http::Method {
+        self.method()
+    }
+
     fn get_uri(&self) -> &http::Uri {
         self.uri()
     }
@@ -38,6 +41,22 @@ impl<Body: Send + 'static> crate::core::http::Request for Request<Body> {
         self.headers().get(header)
     }
 
+    fn get_headers(&self) -> http::HeaderMap {
+        self.headers().clone()
+    }
+
+    fn get_peer_certificate(&self) -> Option<PeerCertificate> {
+        self.extensions().get::<PeerCertificate>().cloned()
+    }
+
+    fn get_peer_address(&self) -> Option<std::net::SocketAddr> {
+        self.extensions().get::<PeerAddress>().map(|address| address.0)
+    }
+
+    fn get_route_params(&self) -> RouteParams {
+        self.extensions().get::<RouteParams>().cloned().unwrap_or_default()
+    }
+
     fn get_extensions(&self) -> Self::RequestExtensionsDeref<'_> {
         self.extensions()
     }
@@ -100,30 +119,208 @@ where
     }
 }
 
-impl<S, Handler, Body, AuthFut> Service<Request<Body>> for Authentication<S, Handler>
+impl<S, Handler, Body, ResBody> Service<Request<Body>> for Authentication<S, Handler>
 where
-    S: Service<Request<Body>> + Clone + Send + 'static,
-    S::Future: Send,
-    Handler: CompoundAuthenticationHandler<AuthFut = AuthFut>,
+    S: Service<Request<Body>, Response = Response<ResBody>> + Clone,
+    Handler: CompoundAuthenticationHandler,
     Body: Send + 'static,
-    AuthFut: Future<Output = AuthenticationResult> + Send,
 {
     type Response = S::Response;
 
     type Error = S::Error;
 
-    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+    type Future = AuthenticationFuture<S, Body, Handler>;
 
-    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, mut req: Request<Body>) -> Self::Future {
-        let mut this = self.clone();
-        Box::pin(async move {
-            this.service.authenticate(&mut req).await;
-            this.inner.call(req).await
-        })
+        let authentication = self.service.start_request(&mut req);
+        let decoration = ResponseDecoration {
+            headers: None,
+            pending_headers: req.extensions().get::<PendingResponseHeaders>().cloned(),
+            hook: self.service.response_hook().cloned(),
+            authentication: None,
+        };
+        match authentication {
+            Some(authentication) => AuthenticationFuture::Authenticating {
+                authentication,
+                request: Some(req),
+                inner: take_ready(&mut self.inner),
+                service: self.service.clone(),
+                decoration: Some(decoration),
+            },
+            None => AuthenticationFuture::Calling {
+                fut: self.inner.call(req),
+                decoration: Some(decoration),
+            },
+        }
+    }
+}
+
+/// The service that was polled ready is the one that has to handle the request, so it's taken out and a clone
+/// is left behind for the next request.
+fn take_ready<S: Clone>(inner: &mut S) -> S {
+    let clone = inner.clone();
+    std::mem::replace(inner, clone)
+}
+
+/// What the authentication layer adds to the response once the inner service responded.
+pub struct ResponseDecoration {
+    headers: Option<HeaderMap>,
+    pending_headers: Option<PendingResponseHeaders>,
+    hook: Option<Arc<dyn ResponseHook>>,
+    authentication: Option<SuccessAuthenticationResult>,
+}
+
+type HookFuture = Pin<Box<dyn Future<Output = ResponseHead> + Send>>;
+
+// Boxing the request to even out the variants would bring back the allocation this future avoids.
+#[allow(clippy::large_enum_variant)]
+#[pin_project(project = AuthenticationFutureProj)]
+pub enum AuthenticationFuture<S, Body, Handler>
+where
+    S: Service<Request<Body>>,
+    Handler: CompoundAuthenticationHandler,
+{
+    Authenticating {
+        #[pin]
+        authentication: ValidateSubject<Handler::AuthFut>,
+        request: Option<Request<Body>>,
+        inner: S,
+        service: Arc<AuthenticationService<Handler>>,
+        decoration: Option<ResponseDecoration>,
+    },
+    Calling {
+        #[pin]
+        fut: S::Future,
+        decoration: Option<ResponseDecoration>,
+    },
+    /// Only the response hook is boxed, and only when one is set.
+    Hooking {
+        hook: HookFuture,
+        response: Option<S::Response>,
+    },
+}
+
+impl<S, Body, ResBody, Handler> Future for AuthenticationFuture<S, Body, Handler>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    Body: Send + 'static,
+    Handler: CompoundAuthenticationHandler,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                AuthenticationFutureProj::Authenticating {
+                    authentication,
+                    request,
+                    inner,
+                    service,
+                    decoration,
+                } => {
+                    let result = futures::ready!(authentication.poll(cx));
+                    let mut request = request.take().expect("Future is polled after completion");
+                    complete_authentication(&mut request, result);
+                    let mut decoration = decoration.take().expect("Future is polled after completion");
+                    decoration.headers = service.success_response_headers(&request);
+                    if decoration.hook.is_some() {
+                        decoration.authentication = request.extensions().get::<SuccessAuthenticationResult>().cloned();
+                    }
+
+                    let fut = inner.call(request);
+                    self.set(AuthenticationFuture::Calling {
+                        fut,
+                        decoration: Some(decoration),
+                    });
+                }
+                AuthenticationFutureProj::Calling { fut, decoration } => {
+                    let mut response = match futures::ready!(fut.poll(cx)) {
+                        Ok(response) => response,
+                        Err(error) => return Poll::Ready(Err(error)),
+                    };
+
+                    let decoration = decoration.take().expect("Future is polled after completion");
+                    if let Some(headers) = decoration.headers {
+                        merge_response_headers(response.headers_mut(), headers);
+                    }
+                    if let Some(pending_headers) = decoration.pending_headers {
+                        for (name, value) in pending_headers.take().iter() {
+                            response.headers_mut().append(name, value.clone());
+                        }
+                    }
+
+                    let Some(hook) = decoration.hook else {
+                        return Poll::Ready(Ok(response));
+                    };
+
+                    let mut head = ResponseHead {
+                        status_code: response.status(),
+                        headers: std::mem::take(response.headers_mut()),
+                    };
+                    let authentication = decoration.authentication;
+                    self.set(AuthenticationFuture::Hooking {
+                        hook: Box::pin(async move {
+                            hook.on_response(authentication.as_ref(), &mut head).await;
+                            head
+                        }),
+                        response: Some(response),
+                    });
+                }
+                AuthenticationFutureProj::Hooking { hook, response } => {
+                    let head = futures::ready!(hook.as_mut().poll(cx));
+                    let mut response = response.take().expect("Future is polled after completion");
+                    *response.status_mut() = head.status_code;
+                    *response.headers_mut() = head.headers;
+                    return Poll::Ready(Ok(response));
+                }
+            }
+        }
+    }
+}
+
+type Authorization<Body> = Pin<Box<dyn Future<Output = (Request<Body>, Result<(), AuthResponse>)> + Send>>;
+
+/// Only the authorization step is boxed; the inner service's future is polled in place.
+#[pin_project(project = AuthorizationFutureProj)]
+pub enum AuthorizationFuture<S, Body>
+where
+    S: Service<Request<Body>>,
+{
+    Authorizing {
+        authorization: Authorization<Body>,
+        inner: Option<S>,
+    },
+    Calling {
+        #[pin]
+        fut: S::Future,
+    },
+}
+
+impl<S, Body> Future for AuthorizationFuture<S, Body>
+where
+    S: Service<Request<Body>>,
+{
+    type Output = Result<Result<S::Response, AuthResponse>, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                AuthorizationFutureProj::Authorizing { authorization, inner } => {
+                    let (request, result) = futures::ready!(authorization.as_mut().poll(cx));
+                    if let Err(response) = result {
+                        return Poll::Ready(Ok(Err(response)));
+                    }
+
+                    let fut = inner.take().expect("Future is polled after completion").call(request);
+                    self.set(AuthorizationFuture::Calling { fut });
+                }
+                AuthorizationFutureProj::Calling { fut } => return fut.poll(cx).map(|result| result.map(Ok)),
+            }
+        }
     }
 }
 
@@ -188,35 +385,120 @@ where
     }
 }
 
-impl<S, Handler, Requirement, Body, ChallengeFut, ForbidFut, AuthorizeFut> Service<Request<Body>>
+impl<S, Handler, Requirement, Body, AuthFut, ChallengeFut, ForbidFut> Service<Request<Body>>
     for Authorize<S, Handler, Requirement>
 where
-    S: Service<Request<Body>> + Clone + Send + 'static,
-    S::Future: Send,
-    Handler: CompoundAuthenticationHandler<ChallengeFut = ChallengeFut, ForbidFut = ForbidFut>,
-    Requirement: AuthorizationRequirement<AuthorizeFut = AuthorizeFut>,
+    S: Service<Request<Body>> + Clone,
+    Handler: CompoundAuthenticationHandler<AuthFut = AuthFut, ChallengeFut = ChallengeFut, ForbidFut = ForbidFut>,
+    Requirement: AuthorizationRequirement,
     Body: Send + 'static,
+    AuthFut: Future<Output = CompoundAuthenticationResult> + Send,
     ChallengeFut: Future<Output = Option<AuthResponse>> + Send,
     ForbidFut: Future<Output = Option<AuthResponse>> + Send,
-    AuthorizeFut: Future<Output = bool> + Send,
 {
     type Response = Result<S::Response, AuthResponse>;
 
     type Error = S::Error;
 
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+    type Future = AuthorizationFuture<S, Body>;
 
-    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, mut req: Request<Body>) -> Self::Future {
-        let mut this = self.clone();
-        Box::pin(async move {
-            match this.policy.authorize(&mut req).await {
-                Ok(()) => this.inner.call(req).await.map(Ok),
-                Err(response) => Ok(Err(response)),
-            }
-        })
+        let policy = self.policy.clone();
+        AuthorizationFuture::Authorizing {
+            authorization: Box::pin(async move {
+                let result = policy.authorize(&mut req).await;
+                let result = result.map_err(|response| rpc_auth_response(&req, response));
+                (req, result)
+            }),
+            inner: Some(take_ready(&mut self.inner)),
+        }
+    }
+}
+
+pub struct AuthorizationMapLayer<Handler: CompoundAuthenticationHandler>(pub Arc<AuthorizationMap<Handler>>);
+
+impl<Handler> Clone for AuthorizationMapLayer<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<S, Handler> Layer<S> for AuthorizationMapLayer<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    type Service = AuthorizeMap<S, Handler>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthorizeMap {
+            inner,
+            map: self.0.clone(),
+        }
+    }
+}
+
+pub struct AuthorizeMap<S, Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    inner: S,
+    map: Arc<AuthorizationMap<Handler>>,
+}
+
+impl<S: Clone, Handler> Clone for AuthorizeMap<S, Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<S, Handler, Body, AuthFut, ChallengeFut, ForbidFut> Service<Request<Body>> for AuthorizeMap<S, Handler>
+where
+    S: Service<Request<Body>> + Clone,
+    Handler: CompoundAuthenticationHandler<AuthFut = AuthFut, ChallengeFut = ChallengeFut, ForbidFut = ForbidFut>,
+    Body: Send + 'static,
+    AuthFut: Future<Output = CompoundAuthenticationResult> + Send,
+    ChallengeFut: Future<Output = Option<AuthResponse>> + Send,
+    ForbidFut: Future<Output = Option<AuthResponse>> + Send,
+{
+    type Response = Result<S::Response, AuthResponse>;
+
+    type Error = S::Error;
+
+    type Future = AuthorizationFuture<S, Body>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        req.extensions_mut().insert(EndpointAuthorization(self.map.clone()));
+        if *self.map.access_for(req.method(), req.uri().path()) == RouteAccess::Anonymous {
+            return AuthorizationFuture::Calling {
+                fut: self.inner.call(req),
+            };
+        }
+
+        let map = self.map.clone();
+        AuthorizationFuture::Authorizing {
+            authorization: Box::pin(async move {
+                let result = map.authorize(&mut req).await;
+                let result = result.map_err(|response| rpc_auth_response(&req, response));
+                (req, result)
+            }),
+            inner: Some(take_ready(&mut self.inner)),
+        }
     }
 }
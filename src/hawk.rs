@@ -0,0 +1,351 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use http::{
+    header::{AUTHORIZATION, HOST, WWW_AUTHENTICATE},
+    uri::Authority,
+    HeaderMap, HeaderValue, StatusCode,
+};
+use sha2::Sha256;
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    http::{parse_auth_params, AuthResponse, Request, RequestExtensions},
+    nonce::NonceStore,
+    principal::{claim_types, ClaimPlainValue, ClaimValue, Claims, UserPrincipal},
+};
+
+pub struct HawkCredentials {
+    pub key: Vec<u8>,
+    pub claims: Claims,
+}
+
+#[async_trait]
+pub trait HawkCredentialsProvider: Send + Sync + 'static {
+    async fn credentials(&self, id: &str) -> Result<Option<HawkCredentials>, anyhow::Error>;
+}
+
+pub struct HawkHandler {
+    pub credentials_provider: Arc<dyn HawkCredentialsProvider>,
+    pub nonce_store: Arc<dyn NonceStore>,
+    pub max_clock_skew: Duration,
+}
+
+/// Set to the credentials id once the MAC of a request with a stale timestamp has been verified, so only
+/// holders of the key get a `tsm` for it.
+#[derive(Clone, Default)]
+struct StaleTimestamp(Arc<Mutex<Option<String>>>);
+
+struct HawkRequest {
+    id: String,
+    nonce_key: String,
+    mac: Vec<u8>,
+    normalized: String,
+    stale: Option<StaleTimestamp>,
+}
+
+impl HawkHandler {
+    fn parse_request(&self, request: &mut impl Request) -> Result<Option<HawkRequest>, anyhow::Error> {
+        let Some(header) = request
+            .get_header(&AUTHORIZATION)
+            .and_then(|h| h.to_str().ok()?.strip_prefix("Hawk "))
+        else {
+            return Ok(None);
+        };
+
+        let params = parse_auth_params(header).ok_or_else(|| anyhow!("Invalid Hawk header"))?;
+        let param = |name: &str| {
+            params
+                .get(name)
+                .ok_or_else(|| anyhow!("Hawk header doesn't contain {name}"))
+        };
+        let id = param("id")?.clone();
+        let ts = param("ts")?.parse::<u64>()?;
+        let nonce = param("nonce")?;
+        let mac = STANDARD.decode(param("mac")?)?;
+
+        let uri = request.get_uri();
+        let authority = match request.get_header(&HOST) {
+            Some(host) => Authority::try_from(host.as_bytes())?,
+            None => uri
+                .authority()
+                .cloned()
+                .ok_or_else(|| anyhow!("Request doesn't contain a host"))?,
+        };
+        let port = authority
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+
+        let mut normalized = format!(
+            "hawk.1.header\n{ts}\n{nonce}\n{}\n{}\n{}\n{port}\n{}\n{}\n",
+            request.get_method(),
+            uri.path_and_query().map(|p| p.as_str()).unwrap_or("/"),
+            authority.host().to_lowercase(),
+            params.get("hash").map(String::as_str).unwrap_or_default(),
+            params.get("ext").map(String::as_str).unwrap_or_default(),
+        );
+        if let Some(app) = params.get("app") {
+            normalized.push_str(&format!(
+                "{app}\n{}\n",
+                params.get("dlg").map(String::as_str).unwrap_or_default()
+            ));
+        }
+
+        let stale = (unix_now().abs_diff(ts) > self.max_clock_skew.as_secs()).then(|| {
+            let stale = StaleTimestamp::default();
+            request.get_extensions_mut().insert(stale.clone());
+            stale
+        });
+
+        Ok(Some(HawkRequest {
+            nonce_key: format!("{id}:{ts}:{nonce}"),
+            id,
+            mac,
+            normalized,
+            stale,
+        }))
+    }
+}
+
+impl AuthenticationHandler for HawkHandler {
+    type AuthFut = Pin<Box<dyn Future<Output = AuthenticationResult> + Send>>;
+
+    type ChallengeFut = Pin<Box<dyn Future<Output = AuthResponse> + Send>>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let hawk_request = match self.parse_request(request) {
+            Ok(Some(hawk_request)) => hawk_request,
+            Ok(None) => return Box::pin(ready(Err(AuthenticationError::NoResult))),
+            Err(err) => return Box::pin(ready(Err(AuthenticationError::fail(err)))),
+        };
+
+        let credentials_provider = self.credentials_provider.clone();
+        let nonce_store = self.nonce_store.clone();
+        let nonce_expires_at = SystemTime::now() + self.max_clock_skew * 2;
+        Box::pin(async move {
+            let credentials = match credentials_provider.credentials(&hawk_request.id).await {
+                Ok(Some(credentials)) => credentials,
+                Ok(None) => {
+                    return Err(AuthenticationError::Fail(AuthError::InvalidCredentials(
+                        "Unknown Hawk credentials".to_owned(),
+                    )))
+                }
+                Err(err) => return Err(AuthenticationError::fail(err)),
+            };
+
+            hawk_mac(&credentials.key, &hawk_request.normalized)
+                .verify_slice(&hawk_request.mac)
+                .map_err(|_| AuthenticationError::Fail(AuthError::InvalidSignature))?;
+
+            if let Some(stale) = hawk_request.stale {
+                *stale.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(hawk_request.id);
+                return Err(AuthenticationError::Fail(AuthError::InvalidToken(
+                    "Stale timestamp".to_owned(),
+                )));
+            }
+
+            match nonce_store.try_use(&hawk_request.nonce_key, nonce_expires_at).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(AuthenticationError::Fail(AuthError::InvalidToken(
+                        "Hawk nonce has already been used".to_owned(),
+                    )))
+                }
+                Err(err) => return Err(AuthenticationError::fail(err)),
+            }
+
+            let mut claims = credentials.claims;
+            claims
+                .entry(claim_types::SUBJECT.into())
+                .or_insert(ClaimValue::PlainValue(ClaimPlainValue::String(hawk_request.id.into())));
+
+            Ok(UserPrincipal { claims })
+        })
+    }
+
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut {
+        let Some(id) = request
+            .get_extensions()
+            .get::<StaleTimestamp>()
+            .and_then(|stale| stale.0.lock().unwrap_or_else(PoisonError::into_inner).clone())
+        else {
+            return Box::pin(ready(hawk_challenge(HeaderValue::from_static("Hawk"))));
+        };
+
+        let credentials_provider = self.credentials_provider.clone();
+        Box::pin(async move {
+            let Ok(Some(credentials)) = credentials_provider.credentials(&id).await else {
+                return hawk_challenge(HeaderValue::from_static("Hawk"));
+            };
+
+            let ts = unix_now();
+            let tsm = STANDARD.encode(
+                hawk_mac(&credentials.key, &format!("hawk.1.ts\n{ts}\n"))
+                    .finalize()
+                    .into_bytes(),
+            );
+            let header_value =
+                HeaderValue::try_from(format!("Hawk ts=\"{ts}\", tsm=\"{tsm}\", error=\"Stale timestamp\""))
+                    .expect("Hawk challenge contains only valid header characters");
+
+            hawk_challenge(header_value)
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn hawk_challenge(header_value: HeaderValue) -> AuthResponse {
+    AuthResponse {
+        status_code: StatusCode::UNAUTHORIZED,
+        headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, header_value)]),
+        body: Vec::new(),
+    }
+}
+
+fn hawk_mac(key: &[u8], normalized: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(normalized.as_bytes());
+    mac
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+    use crate::core::{nonce::InMemoryNonceStore, testing::TestRequest};
+
+    // The example of the Hawk specification.
+    const ID: &str = "dh37fgj492je";
+    const KEY: &str = "werxhqb98rpaxn39848xrunpaw3489ruxnpa98w4rxn";
+    const AUTHORIZATION_HEADER: &str = "Hawk id=\"dh37fgj492je\", ts=\"1353832234\", nonce=\"j4h3g2\", \
+                                        ext=\"some-app-ext-data\", mac=\"6R4rV5iE+NPoym+WwjeHzjAGXUtLNIxmo1vpMofpLAE=\"";
+
+    struct Credentials;
+
+    #[async_trait]
+    impl HawkCredentialsProvider for Credentials {
+        async fn credentials(&self, id: &str) -> Result<Option<HawkCredentials>, anyhow::Error> {
+            Ok((id == ID).then(|| HawkCredentials {
+                key: KEY.as_bytes().to_vec(),
+                claims: Claims::default(),
+            }))
+        }
+    }
+
+    fn handler(max_clock_skew: Duration) -> HawkHandler {
+        HawkHandler {
+            credentials_provider: Arc::new(Credentials),
+            nonce_store: Arc::new(InMemoryNonceStore::new()),
+            max_clock_skew,
+        }
+    }
+
+    /// Accepts the timestamp of the example.
+    fn lenient_handler() -> HawkHandler {
+        handler(Duration::from_secs(100 * 365 * 24 * 3600))
+    }
+
+    fn request(uri: &str, authorization: &str) -> TestRequest {
+        TestRequest::new(
+            Method::GET,
+            uri,
+            &[("host", "example.com:8000"), ("authorization", authorization)],
+        )
+    }
+
+    #[tokio::test]
+    async fn specification_example_is_accepted() {
+        let mut request = request("/resource/1?b=1&a=2", AUTHORIZATION_HEADER);
+
+        let principal = lenient_handler().authenticate(&mut request).await.ok().unwrap();
+
+        assert_eq!(principal.claim_strs(claim_types::SUBJECT).next(), Some(ID));
+    }
+
+    #[tokio::test]
+    async fn tampered_request_is_rejected() {
+        let mut request = request("/resource/2?b=1&a=2", AUTHORIZATION_HEADER);
+
+        let result = lenient_handler().authenticate(&mut request).await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidSignature))
+        ));
+    }
+
+    #[tokio::test]
+    async fn replayed_nonce_is_rejected() {
+        let handler = lenient_handler();
+
+        let first = handler
+            .authenticate(&mut request("/resource/1?b=1&a=2", AUTHORIZATION_HEADER))
+            .await;
+        let replayed = handler
+            .authenticate(&mut request("/resource/1?b=1&a=2", AUTHORIZATION_HEADER))
+            .await;
+
+        assert!(first.is_ok());
+        assert!(matches!(
+            replayed,
+            Err(AuthenticationError::Fail(AuthError::InvalidToken(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stale_timestamp_with_valid_mac_gets_server_time() {
+        let handler = handler(Duration::from_secs(60));
+        let mut request = request("/resource/1?b=1&a=2", AUTHORIZATION_HEADER);
+
+        let result = handler.authenticate(&mut request).await;
+        let challenge = handler.challenge(&request).await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidToken(_)))
+        ));
+        let header = challenge.headers[WWW_AUTHENTICATE].to_str().unwrap();
+        assert!(header.contains("tsm=\""), "{header}");
+    }
+
+    #[tokio::test]
+    async fn stale_timestamp_with_invalid_mac_gets_no_server_time() {
+        let handler = handler(Duration::from_secs(60));
+        let mut request = request("/resource/2?b=1&a=2", AUTHORIZATION_HEADER);
+
+        let result = handler.authenticate(&mut request).await;
+        let challenge = handler.challenge(&request).await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidSignature))
+        ));
+        assert_eq!(challenge.headers[WWW_AUTHENTICATE], "Hawk");
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+#[cfg(feature = "proxy")]
+use crate::core::authentication::SuccessAuthenticationResult;
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    http::{AuthResponse, Request},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+pub const IDENTITY_ASSERTION_HEADER: HeaderName = HeaderName::from_static("x-identity-assertion");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityAssertion {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    /// Seconds since the Unix epoch.
+    pub exp: u64,
+}
+
+/// Mints short-lived assertions of who the user is for internal services, which verify them with a
+/// [`TrustedAssertionHandler`] sharing the secret instead of validating the original credentials again. An
+/// assertion is the base64url encoded JSON of an [`IdentityAssertion`] and its HMAC-SHA256, joined by a dot.
+pub struct IdentityAssertionSigner {
+    pub secret: Vec<u8>,
+    pub lifetime: Duration,
+    pub roles_claim_type: String,
+}
+
+impl IdentityAssertionSigner {
+    /// Assertions valid for a minute.
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            lifetime: Duration::from_secs(60),
+            roles_claim_type: claim_types::ROLE.to_owned(),
+        }
+    }
+
+    /// Fails when the user has no subject.
+    pub fn sign(&self, principal: &UserPrincipal) -> Result<String, anyhow::Error> {
+        let sub = principal
+            .claim_strs(claim_types::SUBJECT)
+            .next()
+            .ok_or_else(|| anyhow!("User has no subject"))?;
+        let assertion = IdentityAssertion {
+            sub: sub.to_owned(),
+            roles: principal
+                .claim_strs(&self.roles_claim_type)
+                .map(str::to_owned)
+                .collect(),
+            exp: unix_time(SystemTime::now() + self.lifetime),
+        };
+
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&assertion)?);
+        let signature = signature(&self.secret, &payload).finalize().into_bytes();
+        Ok(format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// The assertion as a value for [`IDENTITY_ASSERTION_HEADER`].
+    pub fn header_value(&self, principal: &UserPrincipal) -> Result<HeaderValue, anyhow::Error> {
+        let mut value = HeaderValue::try_from(self.sign(principal)?)?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+/// Lets an [`AuthProxy`](crate::proxy::AuthProxy) attach assertions to forwarded requests.
+#[cfg(feature = "proxy")]
+impl crate::proxy::IdentityHeader for IdentityAssertionSigner {
+    fn value(&self, authentication: &SuccessAuthenticationResult) -> Option<HeaderValue> {
+        self.header_value(&authentication.principal).ok()
+    }
+}
+
+/// Authenticates requests by the assertion of an [`IdentityAssertionSigner`]. Assertions signed with any of
+/// `secrets` are accepted, so the secret can be rotated by adding the new one here before the signer uses it.
+pub struct TrustedAssertionHandler {
+    pub secrets: Vec<Vec<u8>>,
+    pub header: HeaderName,
+    /// Allowed clock skew between the signer and this service.
+    pub leeway: Duration,
+    pub roles_claim_type: String,
+}
+
+impl TrustedAssertionHandler {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secrets: vec![secret],
+            header: IDENTITY_ASSERTION_HEADER,
+            leeway: Duration::from_secs(5),
+            roles_claim_type: claim_types::ROLE.to_owned(),
+        }
+    }
+
+    pub fn verify(&self, token: &str) -> Result<IdentityAssertion, AuthError> {
+        let (payload, token_signature) = token
+            .split_once('.')
+            .ok_or_else(|| AuthError::InvalidToken("Malformed identity assertion".to_owned()))?;
+        let token_signature = URL_SAFE_NO_PAD
+            .decode(token_signature)
+            .map_err(|_| AuthError::InvalidToken("Malformed identity assertion".to_owned()))?;
+        if !self
+            .secrets
+            .iter()
+            .any(|secret| signature(secret, payload).verify_slice(&token_signature).is_ok())
+        {
+            return Err(AuthError::InvalidSignature);
+        }
+
+        let assertion = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|payload| serde_json::from_slice::<IdentityAssertion>(&payload).ok())
+            .ok_or_else(|| AuthError::InvalidToken("Malformed identity assertion".to_owned()))?;
+        if assertion.exp.saturating_add(self.leeway.as_secs()) <= unix_time(SystemTime::now()) {
+            return Err(AuthError::Expired);
+        }
+
+        Ok(assertion)
+    }
+
+    fn principal(&self, assertion: IdentityAssertion) -> UserPrincipal {
+        let mut claims = HashMap::from([(
+            claim_types::SUBJECT.into(),
+            ClaimValue::PlainValue(ClaimPlainValue::String(assertion.sub.into())),
+        )]);
+        if !assertion.roles.is_empty() {
+            claims.insert(
+                (&self.roles_claim_type).into(),
+                ClaimValue::Array(
+                    assertion
+                        .roles
+                        .into_iter()
+                        .map(|role| ClaimPlainValue::String(role.into()))
+                        .collect(),
+                ),
+            );
+        }
+
+        UserPrincipal { claims }
+    }
+}
+
+impl AuthenticationHandler for TrustedAssertionHandler {
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let Some(header) = request.get_header(&self.header) else {
+            return ready(Err(AuthenticationError::NoResult));
+        };
+
+        let result = header
+            .to_str()
+            .map_err(|_| AuthError::InvalidToken("Identity assertion is not valid ASCII".to_owned()))
+            .and_then(|token| self.verify(token));
+        ready(
+            result
+                .map(|assertion| self.principal(assertion))
+                .map_err(AuthenticationError::fail),
+        )
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn signature(secret: &[u8], payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"assertion secret";
+    /// `{"sub":"alice","roles":["admin"],"exp":4102444800}` signed with [`SECRET`].
+    const ASSERTION: &str = "eyJzdWIiOiJhbGljZSIsInJvbGVzIjpbImFkbWluIl0sImV4cCI6NDEwMjQ0NDgwMH0.\
+                             ZyNcmgsdbO5NVfJbAmOnlmmW-c_SxltxFwhB53wD1qU";
+    /// The payload of [`ASSERTION`] with the role changed to `root`.
+    const TAMPERED_PAYLOAD: &str = "eyJzdWIiOiJhbGljZSIsInJvbGVzIjpbInJvb3QiXSwiZXhwIjo0MTAyNDQ0ODAwfQ";
+
+    fn principal(sub: &str, role: &str) -> UserPrincipal {
+        UserPrincipal {
+            claims: HashMap::from([
+                (
+                    claim_types::SUBJECT.into(),
+                    ClaimValue::PlainValue(ClaimPlainValue::String(sub.into())),
+                ),
+                (
+                    claim_types::ROLE.into(),
+                    ClaimValue::PlainValue(ClaimPlainValue::String(role.into())),
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn known_assertion_is_accepted() {
+        let assertion = TrustedAssertionHandler::new(SECRET.to_vec()).verify(ASSERTION).unwrap();
+
+        assert_eq!(assertion.sub, "alice");
+        assert_eq!(assertion.roles, ["admin"]);
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let (_, signature) = ASSERTION.split_once('.').unwrap();
+        let token = format!("{TAMPERED_PAYLOAD}.{signature}");
+
+        let result = TrustedAssertionHandler::new(SECRET.to_vec()).verify(&token);
+
+        assert!(matches!(result, Err(AuthError::InvalidSignature)));
+    }
+
+    #[test]
+    fn signed_assertion_round_trips() {
+        let token = IdentityAssertionSigner::new(SECRET.to_vec())
+            .sign(&principal("bob", "editor"))
+            .unwrap();
+
+        let handler = TrustedAssertionHandler::new(SECRET.to_vec());
+        let principal = handler.principal(handler.verify(&token).unwrap());
+
+        assert_eq!(principal.claim_strs(claim_types::SUBJECT).next(), Some("bob"));
+        assert_eq!(principal.claim_strs(claim_types::ROLE).next(), Some("editor"));
+    }
+
+    #[test]
+    fn rotated_secret_is_accepted() {
+        let handler = TrustedAssertionHandler {
+            secrets: vec![b"new secret".to_vec(), SECRET.to_vec()],
+            ..TrustedAssertionHandler::new(Vec::new())
+        };
+
+        assert!(handler.verify(ASSERTION).is_ok());
+    }
+
+    #[test]
+    fn other_secret_is_rejected() {
+        let result = TrustedAssertionHandler::new(b"other secret".to_vec()).verify(ASSERTION);
+
+        assert!(matches!(result, Err(AuthError::InvalidSignature)));
+    }
+
+    #[test]
+    fn expired_assertion_is_rejected() {
+        let signer = IdentityAssertionSigner {
+            lifetime: Duration::ZERO,
+            ..IdentityAssertionSigner::new(SECRET.to_vec())
+        };
+        let token = signer.sign(&principal("bob", "editor")).unwrap();
+        let handler = TrustedAssertionHandler {
+            leeway: Duration::ZERO,
+            ..TrustedAssertionHandler::new(SECRET.to_vec())
+        };
+
+        assert!(matches!(handler.verify(&token), Err(AuthError::Expired)));
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+
+use crate::{
+    core::{
+        authentication::Initializer,
+        cache::AuthCache,
+        circuit_breaker::{CircuitBreaker, CircuitState},
+        health::{DependencyHealth, HealthReporter, HealthStatus},
+        http_client::{default_http_client, error_for_status, HttpClient},
+    },
+    jwt::{parse_jwks, JwtKey, JwtKeyRing},
+};
+
+pub struct JwksOptions {
+    pub jwks_uri: String,
+    pub refresh_interval: Duration,
+    pub retry_interval: Duration,
+    pub jitter: f64,
+    pub max_staleness: Duration,
+    pub cache: Option<Arc<dyn AuthCache>>,
+    /// Skips fetching while the JWKS endpoint keeps failing. Register it with
+    /// [`AuthHealth`](crate::core::health::AuthHealth) to report its state.
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Defaults to a [`ReqwestHttpClient`](crate::core::http_client::ReqwestHttpClient).
+    pub http_client: Option<Arc<dyn HttpClient>>,
+    /// RFC 7638 thumbprints of the only keys accepted from the endpoint. Empty accepts every key.
+    pub pinned_thumbprints: Vec<String>,
+}
+
+impl JwksOptions {
+    pub fn new(jwks_uri: String) -> Self {
+        Self {
+            jwks_uri,
+            refresh_interval: Duration::from_secs(300),
+            retry_interval: Duration::from_secs(10),
+            jitter: 0.1,
+            max_staleness: Duration::from_secs(3600),
+            cache: None,
+            circuit_breaker: None,
+            http_client: None,
+            pinned_thumbprints: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwksHealth {
+    NotLoaded,
+    Fresh,
+    Stale,
+    Expired,
+}
+
+#[derive(Debug, Clone)]
+pub struct JwksStatus {
+    pub health: JwksHealth,
+    pub key_count: usize,
+    pub last_success: Option<SystemTime>,
+    pub last_attempt: Option<SystemTime>,
+    pub last_latency: Option<Duration>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub circuit: Option<CircuitState>,
+}
+
+pub struct JwksProvider {
+    options: JwksOptions,
+    key_ring: JwtKeyRing,
+    http_client: Arc<dyn HttpClient>,
+    status: Mutex<JwksStatus>,
+}
+
+impl JwksProvider {
+    pub fn new(options: JwksOptions) -> Arc<Self> {
+        let key_ring = match &options.circuit_breaker {
+            Some(circuit_breaker) => JwtKeyRing::new().with_circuit_breaker(circuit_breaker.clone()),
+            None => JwtKeyRing::new(),
+        };
+        Arc::new(Self {
+            http_client: options.http_client.clone().unwrap_or_else(default_http_client),
+            options,
+            key_ring,
+            status: Mutex::new(JwksStatus {
+                health: JwksHealth::NotLoaded,
+                key_count: 0,
+                last_success: None,
+                last_attempt: None,
+                last_latency: None,
+                last_error: None,
+                consecutive_failures: 0,
+                circuit: None,
+            }),
+        })
+    }
+
+    pub fn key_ring(&self) -> JwtKeyRing {
+        self.key_ring.clone()
+    }
+
+    pub fn status(&self) -> JwksStatus {
+        JwksStatus {
+            circuit: self.options.circuit_breaker.as_ref().map(|cb| cb.state()),
+            ..self.status.lock().unwrap_or_else(PoisonError::into_inner).clone()
+        }
+    }
+
+    pub fn spawn_refresh(self: &Arc<Self>) -> JoinHandle<()> {
+        let provider = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(provider) = provider.upgrade() {
+                let delay = provider.refresh_and_schedule().await;
+                drop(provider);
+                tokio::time::sleep(delay).await;
+            }
+        })
+    }
+
+    pub async fn refresh(&self) -> Result<(), anyhow::Error> {
+        let now = SystemTime::now();
+        let started_at = Instant::now();
+        let result = match &self.options.circuit_breaker {
+            Some(circuit_breaker) => circuit_breaker.call(self.fetch_keys()).await,
+            None => self.fetch_keys().await,
+        };
+        let latency = started_at.elapsed();
+        let cached_keys = match result {
+            Ok(_) => None,
+            Err(_) => self.cached_keys().await,
+        };
+
+        let mut status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+        status.last_attempt = Some(now);
+        status.last_latency = Some(latency);
+        match result {
+            Ok(keys) => {
+                status.key_count = keys.len();
+                self.key_ring.replace_keys(keys);
+                status.health = JwksHealth::Fresh;
+                status.last_success = Some(now);
+                status.last_error = None;
+                status.consecutive_failures = 0;
+                Ok(())
+            }
+            Err(err) => {
+                status.last_error = Some(err.to_string());
+                status.consecutive_failures += 1;
+                if let Some(keys) = cached_keys {
+                    status.key_count = keys.len();
+                    self.key_ring.replace_keys(keys);
+                    status.health = JwksHealth::Stale;
+                    return Err(err);
+                }
+
+                let stale_for = status
+                    .last_success
+                    .and_then(|last_success| now.duration_since(last_success).ok());
+                status.health = match stale_for {
+                    None => JwksHealth::NotLoaded,
+                    Some(stale_for) if stale_for <= self.options.max_staleness => JwksHealth::Stale,
+                    Some(_) => {
+                        self.key_ring.replace_keys([]);
+                        status.key_count = 0;
+                        JwksHealth::Expired
+                    }
+                };
+                Err(err)
+            }
+        }
+    }
+
+    async fn refresh_and_schedule(&self) -> Duration {
+        let interval = match self.refresh().await {
+            Ok(()) => self.options.refresh_interval,
+            Err(_) => self.options.retry_interval,
+        };
+
+        jittered(interval, self.options.jitter)
+    }
+
+    async fn fetch_keys(&self) -> Result<Vec<JwtKey>, anyhow::Error> {
+        let request = http::Request::get(&self.options.jwks_uri).body(Vec::new())?;
+        let document = error_for_status(self.http_client.send(request).await?)?.into_body();
+        let keys = parse_jwks(&document, &self.options.pinned_thumbprints)?;
+
+        if let Some(cache) = &self.options.cache {
+            // The cache only serves as a fallback, so failing to update it doesn't fail the refresh.
+            let _ = cache.set(&self.cache_key(), document, self.options.max_staleness).await;
+        }
+
+        Ok(keys)
+    }
+
+    async fn cached_keys(&self) -> Option<Vec<JwtKey>> {
+        let document = self.options.cache.as_ref()?.get(&self.cache_key()).await.ok()??;
+        parse_jwks(&document, &self.options.pinned_thumbprints).ok()
+    }
+
+    fn cache_key(&self) -> String {
+        format!("jwks:{}", self.options.jwks_uri)
+    }
+}
+
+impl HealthReporter for JwksProvider {
+    fn health(&self) -> DependencyHealth {
+        let status = self.status();
+        DependencyHealth {
+            name: format!("jwks:{}", self.options.jwks_uri),
+            status: match status.health {
+                JwksHealth::Fresh => HealthStatus::Healthy,
+                JwksHealth::Stale => HealthStatus::Degraded,
+                JwksHealth::NotLoaded | JwksHealth::Expired => HealthStatus::Unhealthy,
+            },
+            last_success: status.last_success,
+            latency: status.last_latency,
+            error: status.last_error,
+        }
+    }
+}
+
+#[async_trait]
+impl Initializer for JwksProvider {
+    fn name(&self) -> String {
+        format!("jwks:{}", self.options.jwks_uri)
+    }
+
+    async fn initialize(&self) -> Result<(), anyhow::Error> {
+        self.refresh().await
+    }
+}
+
+fn jittered(interval: Duration, jitter: f64) -> Duration {
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    interval.mul_f64((1.0 + jitter * (2.0 * random - 1.0)).max(0.0))
+}
@@ -1,23 +1,1026 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
     future::{ready, Ready},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use anyhow::{anyhow, bail};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use http::{
     header::{AUTHORIZATION, WWW_AUTHENTICATE},
-    HeaderMap, HeaderValue, StatusCode,
+    uri::Scheme,
+    HeaderMap, HeaderValue, StatusCode, Uri,
 };
-use jsonwebtoken::{DecodingKey, Validation};
+use jsonwebtoken::{errors::ErrorKind, jwk::Jwk, Algorithm, DecodingKey, Header, TokenData, Validation};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
 
 use crate::core::{
-    authentication::{AuthenticationError, AuthenticationHandler, AuthenticationResult},
-    http::{AuthResponse, Request},
-    principal::{ClaimPlainValue, ClaimValue, UserPrincipal},
+    authentication::{
+        AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult, BearerToken, ExpiredCredential,
+    },
+    authorization::AuthorizationFailure,
+    circuit_breaker::CircuitBreaker,
+    http::{AuthResponse, Request, RequestExtensions},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, Claims, UserPrincipal},
 };
 
+const REQUIRED_ACCESS_TOKEN_CLAIMS: [&str; 7] = ["iss", "exp", "aud", "sub", "client_id", "iat", "jti"];
+
+/// Long claim type URIs some identity providers issue, and the short names [`ClaimMapping::short_names`] maps
+/// them to.
+pub const SHORT_CLAIM_NAMES: [(&str, &str); 10] = [
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/nameidentifier",
+        claim_types::SUBJECT,
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+        claim_types::EMAIL,
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name",
+        claim_types::NAME,
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/givenname",
+        "given_name",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/surname",
+        "family_name",
+    ),
+    (
+        "http://schemas.microsoft.com/ws/2008/06/identity/claims/role",
+        claim_types::ROLE,
+    ),
+    (
+        "http://schemas.microsoft.com/ws/2008/06/identity/claims/groupsid",
+        claim_types::GROUP_SID,
+    ),
+    ("http://schemas.microsoft.com/identity/claims/tenantid", "tid"),
+    ("http://schemas.microsoft.com/identity/claims/objectidentifier", "oid"),
+    (
+        "http://schemas.microsoft.com/claims/authnmethodsreferences",
+        claim_types::AMR,
+    ),
+];
+
+#[derive(Debug)]
+pub enum AccessTokenProfileError {
+    InvalidType(Option<String>),
+    MissingClaim(&'static str),
+    InvalidScope,
+}
+
+impl Display for AccessTokenProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessTokenProfileError::InvalidType(Some(typ)) => {
+                write!(f, "Token type {typ} is not an at+jwt access token")
+            }
+            AccessTokenProfileError::InvalidType(None) => write!(f, "Token doesn't have a typ header"),
+            AccessTokenProfileError::MissingClaim(claim) => write!(f, "Access token doesn't contain {claim} claim"),
+            AccessTokenProfileError::InvalidScope => write!(f, "Access token scope claim is not a string"),
+        }
+    }
+}
+
+impl std::error::Error for AccessTokenProfileError {}
+
+#[derive(Debug)]
+pub struct MissingClaimError(pub String);
+
+impl Display for MissingClaimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Token doesn't contain required claim {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingClaimError {}
+
+pub type JwtKey = (Option<String>, DecodingKey);
+
+/// Clones share the same keys, so keys replaced through one handle are seen by all of them.
+#[derive(Clone, Default)]
+pub struct JwtKeyRing {
+    keys: Arc<RwLock<Vec<JwtKey>>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl JwtKeyRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_key(self, kid: impl Into<String>, key: DecodingKey) -> Self {
+        self.write_keys().push((Some(kid.into()), key));
+        self
+    }
+
+    pub fn add_fallback_key(self, key: DecodingKey) -> Self {
+        self.write_keys().push((None, key));
+        self
+    }
+
+    /// Rejects tokens with [`CircuitOpen`](crate::core::circuit_breaker::CircuitOpen) while the circuit of
+    /// the key source is open and its policy is to fail closed.
+    pub fn with_circuit_breaker(self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        Self {
+            circuit_breaker: Some(circuit_breaker),
+            ..self
+        }
+    }
+
+    pub fn replace_keys(&self, keys: impl IntoIterator<Item = JwtKey>) {
+        *self.write_keys() = keys.into_iter().collect();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_keys().is_empty()
+    }
+
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, anyhow::Error> {
+        if let Some(err) = self.circuit_breaker.as_ref().and_then(|cb| cb.fail_closed_error()) {
+            return Err(err.into());
+        }
+
+        let kid = jsonwebtoken::decode_header(token)?.kid;
+        let keys = self.read_keys();
+        let matching = |key_kid: &Option<String>| kid.is_none() || *key_kid == kid;
+        // Keys added without a kid are tried for tokens whose kid isn't registered, as single-key rings are.
+        let has_match = keys.iter().any(|(key_kid, _)| matching(key_kid));
+        let mut candidates = keys
+            .iter()
+            .filter(|(key_kid, _)| {
+                if has_match {
+                    matching(key_kid)
+                } else {
+                    key_kid.is_none()
+                }
+            })
+            .map(|(_, key)| key)
+            .peekable();
+        if candidates.peek().is_none() {
+            bail!("No decoding key matches kid {}", kid.unwrap_or_default());
+        }
+
+        let mut error = None;
+        for key in candidates {
+            match jsonwebtoken::decode::<T>(token, key, validation) {
+                Ok(token_data) => return Ok(token_data),
+                Err(err) if error.is_none() || *err.kind() != ErrorKind::InvalidSignature => error = Some(err),
+                Err(_) => {}
+            }
+        }
+
+        Err(error.expect("At least one key has been tried").into())
+    }
+
+    fn read_keys(&self) -> RwLockReadGuard<'_, Vec<JwtKey>> {
+        self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_keys(&self) -> RwLockWriteGuard<'_, Vec<JwtKey>> {
+        self.keys.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl From<DecodingKey> for JwtKeyRing {
+    fn from(key: DecodingKey) -> Self {
+        Self::new().add_fallback_key(key)
+    }
+}
+
+/// RFC 7638 thumbprint of a JWK: the base64url SHA-256 of its required members.
+pub fn jwk_thumbprint(jwk: &serde_json::Value) -> Option<String> {
+    let members: &[&str] = match jwk.get("kty")?.as_str()? {
+        "RSA" => &["e", "kty", "n"],
+        "EC" => &["crv", "kty", "x", "y"],
+        "OKP" => &["crv", "kty", "x"],
+        "oct" => &["k", "kty"],
+        _ => return None,
+    };
+    let required = members
+        .iter()
+        .map(|&member| Some((member, jwk.get(member)?.as_str()?)))
+        .collect::<Option<BTreeMap<_, _>>>()?;
+
+    Some(URL_SAFE_NO_PAD.encode(Sha256::digest(serde_json::to_vec(&required).ok()?)))
+}
+
+/// Parses the usable keys of a JWKS document. With `pinned_thumbprints`, keys whose
+/// [thumbprint](jwk_thumbprint) isn't listed are skipped.
+pub fn parse_jwks(document: &[u8], pinned_thumbprints: &[String]) -> Result<Vec<JwtKey>, anyhow::Error> {
+    #[derive(Deserialize)]
+    struct JwksDocument {
+        keys: Vec<serde_json::Value>,
+    }
+
+    let keys = serde_json::from_slice::<JwksDocument>(document)?
+        .keys
+        .into_iter()
+        .filter(|jwk| {
+            pinned_thumbprints.is_empty()
+                || jwk_thumbprint(jwk).is_some_and(|thumbprint| pinned_thumbprints.contains(&thumbprint))
+        })
+        .filter_map(|jwk| {
+            let jwk = serde_json::from_value::<Jwk>(jwk).ok()?;
+            Some((jwk.common.key_id.clone(), DecodingKey::from_jwk(&jwk).ok()?))
+        })
+        .collect::<Vec<_>>();
+    if keys.is_empty() && pinned_thumbprints.is_empty() {
+        bail!("JWKS document doesn't contain any usable keys");
+    }
+    if keys.is_empty() {
+        bail!("JWKS document doesn't contain any pinned keys");
+    }
+
+    Ok(keys)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BearerChallenge {
+    pub realm: Option<String>,
+    pub scope: Option<String>,
+    pub authorization_uri: Option<String>,
+    pub extra_params: Vec<(String, String)>,
+}
+
+impl BearerChallenge {
+    pub fn header_value(&self) -> HeaderValue {
+        let params = [
+            ("realm", self.realm.as_deref()),
+            ("scope", self.scope.as_deref()),
+            ("authorization_uri", self.authorization_uri.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .chain(
+            self.extra_params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+        .map(|(name, value)| format!("{name}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>();
+
+        if params.is_empty() {
+            return HeaderValue::from_static("Bearer");
+        }
+
+        HeaderValue::try_from(format!("Bearer {}", params.join(", "))).unwrap_or(HeaderValue::from_static("Bearer"))
+    }
+}
+
+/// How tokens past their `exp` are treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExpiryPolicy {
+    /// Rejects them once the validation leeway has passed.
+    #[default]
+    Reject,
+    /// Accepts tokens expired at most this long beyond the leeway, e.g. to absorb clock drift.
+    Grace(Duration),
+    /// Accepts expired tokens, inserting [`ExpiredCredential`] so policies can refuse them on sensitive routes.
+    Mark,
+}
+
+/// Counters of a [`ValidatedTokenCache`] since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenCacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries removed to stay within the size bound, not counting expired ones.
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct TokenCacheState {
+    entries: HashMap<[u8; 32], (UserPrincipal, SystemTime)>,
+    /// The keys of `entries` ordered by expiry, so the entry to evict is found without a scan.
+    expiry_order: BTreeSet<(SystemTime, [u8; 32])>,
+    metrics: TokenCacheMetrics,
+}
+
+impl TokenCacheState {
+    fn remove(&mut self, key: &[u8; 32]) {
+        if let Some((_, expires_at)) = self.entries.remove(key) {
+            self.expiry_order.remove(&(expires_at, *key));
+        }
+    }
+}
+
+static NEXT_TOKEN_CACHE_SCOPE: AtomicU64 = AtomicU64::new(0);
+
+/// Principals of tokens that passed validation, keyed by the SHA-256 of the token, so repeat tokens skip
+/// signature verification until they expire. Tokens without `exp` and tokens bound to a client certificate
+/// aren't cached, and cached tokens stay valid when their signing key is removed until [`Self::clear`] is
+/// called.
+///
+/// Clones share the storage, its size bound and [`Self::clear`], but not the entries: a token accepted by a
+/// handler configured with one clone isn't returned by another, as the handlers may validate tokens differently.
+pub struct ValidatedTokenCache {
+    state: Arc<Mutex<TokenCacheState>>,
+    scope: u64,
+    max_entries: usize,
+}
+
+impl ValidatedTokenCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            state: Arc::default(),
+            scope: NEXT_TOKEN_CACHE_SCOPE.fetch_add(1, Ordering::Relaxed),
+            max_entries,
+        }
+    }
+
+    pub fn get(&self, token: &str) -> Option<UserPrincipal> {
+        let key = self.key(token);
+        let mut state = self.lock_state();
+        let principal = state
+            .entries
+            .get(&key)
+            .filter(|(_, expires_at)| *expires_at > SystemTime::now())
+            .map(|(principal, _)| principal.clone());
+        match principal {
+            Some(_) => state.metrics.hits += 1,
+            None => state.metrics.misses += 1,
+        }
+
+        principal
+    }
+
+    /// When full, the entry expiring soonest is removed, which is an expired one when there are any.
+    pub fn insert(&self, token: &str, principal: UserPrincipal, expires_at: SystemTime) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let key = self.key(token);
+        let mut state = self.lock_state();
+        state.remove(&key);
+        if state.entries.len() >= self.max_entries {
+            if let Some((soonest, soonest_key)) = state.expiry_order.pop_first() {
+                state.entries.remove(&soonest_key);
+                if soonest > SystemTime::now() {
+                    state.metrics.evictions += 1;
+                }
+            }
+        }
+
+        state.entries.insert(key, (principal, expires_at));
+        state.expiry_order.insert((expires_at, key));
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.lock_state();
+        state.entries.clear();
+        state.expiry_order.clear();
+    }
+
+    pub fn metrics(&self) -> TokenCacheMetrics {
+        let state = self.lock_state();
+        TokenCacheMetrics {
+            entries: state.entries.len(),
+            ..state.metrics
+        }
+    }
+
+    fn key(&self, token: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.scope.to_le_bytes());
+        hasher.update(token);
+        hasher.finalize().into()
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, TokenCacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for ValidatedTokenCache {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            scope: NEXT_TOKEN_CACHE_SCOPE.fetch_add(1, Ordering::Relaxed),
+            max_entries: self.max_entries,
+        }
+    }
+}
+
+/// Normalizes token claims before the principal is built, in the order of the fields. Deserializes from config:
+///
+/// ```json
+/// { "short_names": true, "rename": { "preferred_username": "name" }, "split": ["scope"], "lowercase": ["email"] }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClaimMapping {
+    /// Renames the claim types in [`SHORT_CLAIM_NAMES`] unless the short name is already present.
+    pub short_names: bool,
+    /// Moves a claim to another claim type, replacing the claim already there.
+    pub rename: HashMap<String, String>,
+    /// Claims whose space-delimited string value becomes an array, e.g. `scope`.
+    pub split: Vec<String>,
+    /// Claims whose string values are lowercased, e.g. `email`.
+    pub lowercase: Vec<String>,
+}
+
+impl ClaimMapping {
+    pub fn apply(&self, claims: &mut HashMap<String, serde_json::Value>) {
+        if self.short_names {
+            for (long_name, short_name) in SHORT_CLAIM_NAMES {
+                if let Some(value) = claims.remove(long_name) {
+                    claims.entry(short_name.to_owned()).or_insert(value);
+                }
+            }
+        }
+
+        for (source, claim_type) in &self.rename {
+            if let Some(value) = claims.remove(source) {
+                claims.insert(claim_type.clone(), value);
+            }
+        }
+
+        for claim in &self.split {
+            let Some(serde_json::Value::String(value)) = claims.get(claim) else {
+                continue;
+            };
+            let values = value
+                .split_whitespace()
+                .map(|value| serde_json::Value::String(value.to_owned()))
+                .collect();
+            claims.insert(claim.clone(), serde_json::Value::Array(values));
+        }
+
+        for claim in &self.lowercase {
+            match claims.get_mut(claim) {
+                Some(serde_json::Value::String(value)) => *value = value.to_lowercase(),
+                Some(serde_json::Value::Array(values)) => {
+                    for value in values {
+                        if let serde_json::Value::String(value) = value {
+                            *value = value.to_lowercase();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 pub struct JwtBearerHandler {
     pub validation_opt: Validation,
-    pub decoding_key: DecodingKey,
+    pub keys: JwtKeyRing,
+    pub strict_access_token_profile: bool,
+    pub challenge: BearerChallenge,
+    /// Claims that must be present with exactly this string value.
+    pub expected_claims: HashMap<String, String>,
+    /// Copies a claim, addressed by a dotted path, to another claim type unless that type is already present.
+    pub claim_aliases: HashMap<String, String>,
+    pub expiry: ExpiryPolicy,
+    /// Applied before [`Self::claim_aliases`], so aliases and required claims see the mapped claims.
+    pub claim_mapping: ClaimMapping,
+    /// Claims that must be present and not null, checked after [`Self::claim_aliases`] are applied. Fails with
+    /// [`MissingClaimError`].
+    pub required_claims: Vec<String>,
+    pub token_cache: Option<ValidatedTokenCache>,
+}
+
+impl JwtBearerHandler {
+    pub fn builder() -> JwtBearerOptions {
+        JwtBearerOptions::new()
+    }
+
+    /// Validates `token` according to [`Self::expiry`]. Tokens accepted past their expiry aren't reported here,
+    /// see [`Self::expired_credential`].
+    pub fn validate_token(&self, token: &str, request: &impl Request) -> Result<UserPrincipal, anyhow::Error> {
+        if let Some(principal) = self.token_cache.as_ref().and_then(|cache| cache.get(token)) {
+            return Ok(principal);
+        }
+
+        let token_data = match self.expiry {
+            ExpiryPolicy::Reject => self
+                .keys
+                .decode::<HashMap<String, serde_json::Value>>(token, &self.validation_opt)?,
+            ExpiryPolicy::Grace(_) | ExpiryPolicy::Mark => {
+                let mut validation = self.validation_opt.clone();
+                validation.validate_exp = false;
+                self.keys
+                    .decode::<HashMap<String, serde_json::Value>>(token, &validation)?
+            }
+        };
+        let mut claims = token_data.claims;
+        if let ExpiryPolicy::Grace(grace) = self.expiry {
+            let expires_at = claims.get("exp").and_then(json_timestamp);
+            if expires_at.is_some_and(|expires_at| expires_at + self.leeway() + grace < SystemTime::now()) {
+                return Err(AuthError::Expired.into());
+            }
+        }
+
+        if self.strict_access_token_profile {
+            validate_access_token_profile(&token_data.header, &mut claims)?;
+        }
+
+        for (claim, expected) in &self.expected_claims {
+            if claims.get(claim).and_then(serde_json::Value::as_str) != Some(expected.as_str()) {
+                bail!("Claim {claim} doesn't have the expected value");
+            }
+        }
+
+        verify_certificate_binding(&claims, request)?;
+        let cache_until = claims
+            .get("exp")
+            .and_then(json_timestamp)
+            .filter(|_| !claims.contains_key("cnf"));
+
+        self.claim_mapping.apply(&mut claims);
+
+        for (source, claim_type) in &self.claim_aliases {
+            if claims.contains_key(claim_type) {
+                continue;
+            }
+
+            let mut path = source.split('.');
+            let value = path
+                .next()
+                .and_then(|root| path.try_fold(claims.get(root)?, |value, key| value.get(key)))
+                .cloned();
+            if let Some(value) = value {
+                claims.insert(claim_type.clone(), value);
+            }
+        }
+
+        if let Some(claim) = self
+            .required_claims
+            .iter()
+            .find(|claim| claims.get(*claim).is_none_or(serde_json::Value::is_null))
+        {
+            return Err(MissingClaimError(claim.clone()).into());
+        }
+
+        let mut principal_claims = HashMap::new();
+        for (claim_type, value) in claims {
+            insert_claim(&mut principal_claims, claim_type, value);
+        }
+
+        let principal = UserPrincipal {
+            claims: principal_claims,
+        };
+        if let Some((cache, expires_at)) = self.token_cache.as_ref().zip(cache_until) {
+            cache.insert(token, principal.clone(), expires_at);
+        }
+
+        Ok(principal)
+    }
+
+    /// When the principal's token expired, if it did beyond the validation leeway.
+    pub fn expired_credential(&self, principal: &UserPrincipal) -> Option<ExpiredCredential> {
+        let expired_at = principal.claim("exp").and_then(|exp| match exp.iter().next()? {
+            ClaimPlainValue::Int(exp) => Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(*exp).ok()?)),
+            ClaimPlainValue::Float(exp) => Some(UNIX_EPOCH + Duration::from_secs_f64(exp.max(0.0))),
+            _ => None,
+        })?;
+
+        (expired_at + self.leeway() < SystemTime::now()).then_some(ExpiredCredential { expired_at })
+    }
+
+    fn leeway(&self) -> Duration {
+        Duration::from_secs(self.validation_opt.leeway)
+    }
+}
+
+fn json_timestamp(value: &serde_json::Value) -> Option<SystemTime> {
+    let seconds = value
+        .as_u64()
+        .map(|seconds| seconds as f64)
+        .or_else(|| value.as_f64())?;
+    Some(UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+#[derive(Debug)]
+pub enum JwtBearerOptionsError {
+    MissingDecodingKey,
+    MissingAlgorithms,
+    InvalidAuthority(String),
+    InsecureAuthority(String),
+    EmptyIssuer,
+    EmptyAudience,
+    InvalidJwks(String),
+    InvalidDiscoveryDocument(String),
+}
+
+impl Display for JwtBearerOptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JwtBearerOptionsError::MissingDecodingKey => write!(f, "Decoding key is not configured"),
+            JwtBearerOptionsError::MissingAlgorithms => write!(f, "At least one signing algorithm must be allowed"),
+            JwtBearerOptionsError::InvalidAuthority(authority) => {
+                write!(f, "Authority {authority} is not an absolute URL")
+            }
+            JwtBearerOptionsError::InsecureAuthority(authority) => write!(
+                f,
+                "Authority {authority} doesn't use https. Disable require_https_metadata for development only"
+            ),
+            JwtBearerOptionsError::EmptyIssuer => write!(f, "Issuer must not be empty"),
+            JwtBearerOptionsError::EmptyAudience => write!(f, "Audience must not be empty"),
+            JwtBearerOptionsError::InvalidJwks(err) => write!(f, "JWKS document can't be loaded: {err}"),
+            JwtBearerOptionsError::InvalidDiscoveryDocument(err) => {
+                write!(f, "Discovery document can't be loaded: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JwtBearerOptionsError {}
+
+enum MetadataSource {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl MetadataSource {
+    fn read(&self) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            MetadataSource::Bytes(document) => Ok(document.clone()),
+            MetadataSource::File(path) => std::fs::read(path),
+        }
+    }
+}
+
+pub struct JwtBearerOptions {
+    authority: Option<String>,
+    issuers: Vec<String>,
+    audiences: Vec<String>,
+    require_https_metadata: bool,
+    algorithms: Vec<Algorithm>,
+    leeway: Duration,
+    keys: JwtKeyRing,
+    strict_access_token_profile: bool,
+    challenge: BearerChallenge,
+    expected_claims: HashMap<String, String>,
+    claim_aliases: HashMap<String, String>,
+    expiry: ExpiryPolicy,
+    claim_mapping: ClaimMapping,
+    required_claims: Vec<String>,
+    token_cache: Option<ValidatedTokenCache>,
+    jwks_documents: Vec<MetadataSource>,
+    discovery_document: Option<MetadataSource>,
+    pinned_thumbprints: Vec<String>,
+}
+
+impl JwtBearerOptions {
+    pub fn new() -> Self {
+        Self {
+            authority: None,
+            issuers: Vec::new(),
+            audiences: Vec::new(),
+            require_https_metadata: true,
+            algorithms: vec![Algorithm::RS256],
+            leeway: Duration::from_secs(60),
+            keys: JwtKeyRing::new(),
+            strict_access_token_profile: false,
+            challenge: BearerChallenge::default(),
+            expected_claims: HashMap::new(),
+            claim_aliases: HashMap::new(),
+            expiry: ExpiryPolicy::default(),
+            claim_mapping: ClaimMapping::default(),
+            required_claims: Vec::new(),
+            token_cache: None,
+            jwks_documents: Vec::new(),
+            discovery_document: None,
+            pinned_thumbprints: Vec::new(),
+        }
+    }
+
+    pub fn authority(self, authority: impl Into<String>) -> Self {
+        Self {
+            authority: Some(authority.into()),
+            ..self
+        }
+    }
+
+    pub fn issuers(self, issuers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            issuers: issuers.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    pub fn audiences(self, audiences: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            audiences: audiences.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    pub fn require_https_metadata(self, require_https_metadata: bool) -> Self {
+        Self {
+            require_https_metadata,
+            ..self
+        }
+    }
+
+    pub fn algorithms(self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        Self {
+            algorithms: algorithms.into_iter().collect(),
+            ..self
+        }
+    }
+
+    pub fn leeway(self, leeway: Duration) -> Self {
+        Self { leeway, ..self }
+    }
+
+    pub fn decoding_key(self, decoding_key: DecodingKey) -> Self {
+        Self {
+            keys: self.keys.add_fallback_key(decoding_key),
+            ..self
+        }
+    }
+
+    pub fn key(self, kid: impl Into<String>, decoding_key: DecodingKey) -> Self {
+        Self {
+            keys: self.keys.add_key(kid, decoding_key),
+            ..self
+        }
+    }
+
+    pub fn keys(self, keys: JwtKeyRing) -> Self {
+        Self { keys, ..self }
+    }
+
+    /// Adds the keys of an embedded JWKS document, so no network access is needed.
+    pub fn jwks(mut self, document: impl Into<Vec<u8>>) -> Self {
+        self.jwks_documents.push(MetadataSource::Bytes(document.into()));
+        self
+    }
+
+    /// Like [`Self::jwks`], reading the document when the handler is built.
+    pub fn jwks_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.jwks_documents.push(MetadataSource::File(path.into()));
+        self
+    }
+
+    /// Takes the issuer from an embedded OpenID discovery document. Its `jwks_uri` isn't fetched, so the keys
+    /// must come from [`Self::jwks`].
+    pub fn discovery_document(self, document: impl Into<Vec<u8>>) -> Self {
+        Self {
+            discovery_document: Some(MetadataSource::Bytes(document.into())),
+            ..self
+        }
+    }
+
+    pub fn discovery_file(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            discovery_document: Some(MetadataSource::File(path.into())),
+            ..self
+        }
+    }
+
+    /// Only accepts keys from JWKS documents whose RFC 7638 thumbprint is listed. Keys added directly with
+    /// [`Self::key`] aren't checked.
+    pub fn pinned_thumbprints(self, thumbprints: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            pinned_thumbprints: thumbprints.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    pub fn strict_access_token_profile(self, strict_access_token_profile: bool) -> Self {
+        Self {
+            strict_access_token_profile,
+            ..self
+        }
+    }
+
+    pub fn challenge(self, challenge: BearerChallenge) -> Self {
+        Self { challenge, ..self }
+    }
+
+    pub fn expected_claim(mut self, claim: impl Into<String>, value: impl Into<String>) -> Self {
+        self.expected_claims.insert(claim.into(), value.into());
+        self
+    }
+
+    pub fn claim_alias(mut self, source: impl Into<String>, claim_type: impl Into<String>) -> Self {
+        self.claim_aliases.insert(source.into(), claim_type.into());
+        self
+    }
+
+    pub fn claim_mapping(self, claim_mapping: ClaimMapping) -> Self {
+        Self { claim_mapping, ..self }
+    }
+
+    pub fn required_claim(mut self, claim: impl Into<String>) -> Self {
+        self.required_claims.push(claim.into());
+        self
+    }
+
+    pub fn token_cache(self, token_cache: ValidatedTokenCache) -> Self {
+        Self {
+            token_cache: Some(token_cache),
+            ..self
+        }
+    }
+
+    pub fn expiry(self, expiry: ExpiryPolicy) -> Self {
+        Self { expiry, ..self }
+    }
+
+    pub fn build(self) -> Result<JwtBearerHandler, JwtBearerOptionsError> {
+        #[derive(Deserialize)]
+        struct Discovery {
+            issuer: String,
+        }
+
+        let mut keys = self.keys;
+        for source in &self.jwks_documents {
+            let document = source
+                .read()
+                .map_err(|err| JwtBearerOptionsError::InvalidJwks(err.to_string()))?;
+            for (kid, key) in parse_jwks(&document, &self.pinned_thumbprints)
+                .map_err(|err| JwtBearerOptionsError::InvalidJwks(err.to_string()))?
+            {
+                keys = match kid {
+                    Some(kid) => keys.add_key(kid, key),
+                    None => keys.add_fallback_key(key),
+                };
+            }
+        }
+        if keys.is_empty() {
+            return Err(JwtBearerOptionsError::MissingDecodingKey);
+        }
+        let Some(&first_algorithm) = self.algorithms.first() else {
+            return Err(JwtBearerOptionsError::MissingAlgorithms);
+        };
+
+        let mut issuers = self.issuers;
+        if let Some(source) = &self.discovery_document {
+            let discovery = source
+                .read()
+                .map_err(|err| err.to_string())
+                .and_then(|document| serde_json::from_slice::<Discovery>(&document).map_err(|err| err.to_string()))
+                .map_err(JwtBearerOptionsError::InvalidDiscoveryDocument)?;
+            if !issuers.contains(&discovery.issuer) {
+                issuers.push(discovery.issuer);
+            }
+        }
+        if let Some(authority) = self.authority {
+            let uri = Uri::try_from(authority.as_str())
+                .ok()
+                .filter(|uri| uri.scheme().is_some() && uri.host().is_some())
+                .ok_or_else(|| JwtBearerOptionsError::InvalidAuthority(authority.clone()))?;
+            if self.require_https_metadata && uri.scheme() != Some(&Scheme::HTTPS) {
+                return Err(JwtBearerOptionsError::InsecureAuthority(authority));
+            }
+
+            if !issuers.contains(&authority) {
+                issuers.push(authority);
+            }
+        }
+
+        if issuers.iter().any(String::is_empty) {
+            return Err(JwtBearerOptionsError::EmptyIssuer);
+        }
+        if self.audiences.iter().any(String::is_empty) {
+            return Err(JwtBearerOptionsError::EmptyAudience);
+        }
+
+        let mut validation = Validation::new(first_algorithm);
+        validation.algorithms = self.algorithms;
+        validation.leeway = self.leeway.as_secs();
+        if !issuers.is_empty() {
+            validation.set_issuer(&issuers);
+        }
+        if self.audiences.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audiences);
+        }
+
+        Ok(JwtBearerHandler {
+            validation_opt: validation,
+            keys,
+            strict_access_token_profile: self.strict_access_token_profile,
+            challenge: self.challenge,
+            expected_claims: self.expected_claims,
+            claim_aliases: self.claim_aliases,
+            expiry: self.expiry,
+            claim_mapping: self.claim_mapping,
+            required_claims: self.required_claims,
+            token_cache: self.token_cache,
+        })
+    }
+}
+
+impl Default for JwtBearerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AzureAd;
+
+impl AzureAd {
+    pub fn jwks_uri(tenant: &str) -> String {
+        format!("https://login.microsoftonline.com/{tenant}/discovery/v2.0/keys")
+    }
+
+    /// Tokens carry the issuing tenant in the issuer, so multi-tenant apps list every tenant they accept.
+    /// Both v1 (`sts.windows.net`) and v2 issuers are accepted.
+    pub fn options(tenant_ids: &[&str], audience: impl Into<String>) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers(tenant_ids.iter().flat_map(|tenant| {
+                [
+                    format!("https://login.microsoftonline.com/{tenant}/v2.0"),
+                    format!("https://sts.windows.net/{tenant}/"),
+                ]
+            }))
+            .audiences([audience.into()])
+            .claim_alias("roles", claim_types::ROLE)
+    }
+}
+
+pub struct AwsCognito;
+
+impl AwsCognito {
+    pub fn issuer(region: &str, user_pool_id: &str) -> String {
+        format!("https://cognito-idp.{region}.amazonaws.com/{user_pool_id}")
+    }
+
+    pub fn jwks_uri(region: &str, user_pool_id: &str) -> String {
+        format!("{}/.well-known/jwks.json", Self::issuer(region, user_pool_id))
+    }
+
+    /// Cognito access tokens have no `aud`, so the app client is matched through `client_id` instead.
+    pub fn access_token_options(region: &str, user_pool_id: &str, client_id: impl Into<String>) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers([Self::issuer(region, user_pool_id)])
+            .expected_claim("token_use", "access")
+            .expected_claim("client_id", client_id)
+            .claim_alias("cognito:groups", claim_types::ROLE)
+            .claim_alias("username", claim_types::NAME)
+    }
+
+    pub fn id_token_options(region: &str, user_pool_id: &str, client_id: impl Into<String>) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers([Self::issuer(region, user_pool_id)])
+            .audiences([client_id.into()])
+            .expected_claim("token_use", "id")
+            .claim_alias("cognito:groups", claim_types::ROLE)
+            .claim_alias("cognito:username", claim_types::NAME)
+    }
+}
+
+pub struct GoogleCloud;
+
+impl GoogleCloud {
+    pub const JWKS_URI: &'static str = "https://www.googleapis.com/oauth2/v3/certs";
+
+    pub const IAP_JWKS_URI: &'static str = "https://www.gstatic.com/iap/verify/public_key-jwk";
+
+    /// Google-signed ID tokens, including those minted for service accounts calling Cloud Run or Functions.
+    pub fn id_token_options(audience: impl Into<String>) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers(["https://accounts.google.com", "accounts.google.com"])
+            .audiences([audience.into()])
+    }
+
+    /// The `x-goog-iap-jwt-assertion` header set by Identity-Aware Proxy. `audience` has the form
+    /// `/projects/<number>/global/backendServices/<id>` or `/projects/<number>/apps/<project id>`.
+    pub fn iap_options(audience: impl Into<String>) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers(["https://cloud.google.com/iap"])
+            .audiences([audience.into()])
+            .algorithms([Algorithm::ES256])
+    }
+}
+
+pub struct Firebase;
+
+impl Firebase {
+    pub const JWKS_URI: &'static str =
+        "https://www.googleapis.com/service_accounts/v1/jwk/securetoken@system.gserviceaccount.com";
+
+    /// `sub` is the Firebase uid.
+    pub fn options(project_id: &str) -> JwtBearerOptions {
+        JwtBearerOptions::new()
+            .issuers([format!("https://securetoken.google.com/{project_id}")])
+            .audiences([project_id.to_owned()])
+            .claim_alias("firebase.sign_in_provider", "sign_in_provider")
+            .claim_alias("firebase.tenant", "tenant")
+    }
 }
 
 impl AuthenticationHandler for JwtBearerHandler {
@@ -27,6 +1030,29 @@ impl AuthenticationHandler for JwtBearerHandler {
 
     type ForbidFut = Ready<AuthResponse>;
 
+    fn validate_configuration(&self) -> Result<(), anyhow::Error> {
+        if self.keys.is_empty() {
+            bail!("No signing keys are loaded");
+        }
+
+        let algorithms = &self.validation_opt.algorithms;
+        let Some(&first_algorithm) = algorithms.first() else {
+            bail!("No signing algorithms are allowed");
+        };
+        // jsonwebtoken rejects every token when the allowed algorithms need different kinds of keys.
+        if algorithms
+            .iter()
+            .any(|&algorithm| algorithm_key_type(algorithm) != algorithm_key_type(first_algorithm))
+        {
+            bail!("Allowed signing algorithms {algorithms:?} need different kinds of keys");
+        }
+        if self.validation_opt.validate_aud && self.validation_opt.aud.is_none() {
+            bail!("Audience validation is enabled, but no audiences are configured");
+        }
+
+        Ok(())
+    }
+
     fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
         let bearer_token = request.get_header(&AUTHORIZATION).and_then(|h| {
             let header_str = h.to_str().ok()?;
@@ -41,40 +1067,123 @@ impl AuthenticationHandler for JwtBearerHandler {
             return ready(Err(AuthenticationError::NoResult));
         };
 
-        let claims = jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(
-            bearer_token,
-            &self.decoding_key,
-            &self.validation_opt,
-        );
-
-        let claims = match claims {
-            Ok(token_data) => token_data.claims,
-            Err(err) => return ready(Err(AuthenticationError::Fail(err.into()))),
-        };
+        let result = self.validate_token(bearer_token, request);
+        if result.is_ok() {
+            let token = BearerToken(bearer_token.into());
+            request.get_extensions_mut().insert(token);
+        }
+        if let Some(expired) = result
+            .as_ref()
+            .ok()
+            .filter(|_| self.expiry == ExpiryPolicy::Mark)
+            .and_then(|principal| self.expired_credential(principal))
+        {
+            request.get_extensions_mut().insert(expired);
+        }
 
-        ready(Ok(UserPrincipal {
-            claims: claims
-                .into_iter()
-                .filter_map(|(t, v)| json_to_claim_value(v).map(|c| (t, c)))
-                .collect(),
-        }))
+        ready(result.map_err(AuthenticationError::fail))
     }
 
-    fn challenge(&self) -> Self::ChallengeFut {
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
         ready(AuthResponse {
             status_code: StatusCode::UNAUTHORIZED,
-            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))]),
+            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, self.challenge.header_value())]),
+            body: Vec::new(),
         })
     }
 
-    fn forbid(&self) -> Self::ForbidFut {
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
         ready(AuthResponse {
             status_code: StatusCode::FORBIDDEN,
             headers: HeaderMap::default(),
+            body: Vec::new(),
         })
     }
 }
 
+fn validate_access_token_profile(
+    header: &Header,
+    claims: &mut HashMap<String, serde_json::Value>,
+) -> Result<(), AccessTokenProfileError> {
+    let typ = header.typ.as_deref().map(str::to_ascii_lowercase);
+    if !matches!(typ.as_deref(), Some("at+jwt" | "application/at+jwt")) {
+        return Err(AccessTokenProfileError::InvalidType(header.typ.clone()));
+    }
+
+    if let Some(claim) = REQUIRED_ACCESS_TOKEN_CLAIMS
+        .into_iter()
+        .find(|claim| claims.get(*claim).is_none_or(serde_json::Value::is_null))
+    {
+        return Err(AccessTokenProfileError::MissingClaim(claim));
+    }
+
+    if let Some(scope) = claims.get_mut("scope") {
+        let scopes = scope
+            .as_str()
+            .ok_or(AccessTokenProfileError::InvalidScope)?
+            .split_whitespace()
+            .map(|s| serde_json::Value::String(s.to_owned()))
+            .collect();
+        *scope = serde_json::Value::Array(scopes);
+    }
+
+    Ok(())
+}
+
+fn algorithm_key_type(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => "oct",
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => "RSA",
+        Algorithm::ES256 | Algorithm::ES384 => "EC",
+        Algorithm::EdDSA => "OKP",
+    }
+}
+
+fn verify_certificate_binding(
+    claims: &HashMap<String, serde_json::Value>,
+    request: &impl Request,
+) -> Result<(), anyhow::Error> {
+    let Some(expected_thumbprint) = claims.get("cnf").and_then(|cnf| cnf.get("x5t#S256")) else {
+        return Ok(());
+    };
+
+    let certificate = request
+        .get_peer_certificate()
+        .ok_or_else(|| anyhow!("Token is bound to a client certificate, but none was presented"))?;
+    let thumbprint = URL_SAFE_NO_PAD.encode(Sha256::digest(&certificate.0));
+    if expected_thumbprint.as_str() != Some(thumbprint.as_str()) {
+        bail!("Client certificate doesn't match the token's x5t#S256 confirmation");
+    }
+
+    Ok(())
+}
+
+fn insert_claim(claims: &mut Claims, claim_type: String, value: serde_json::Value) {
+    match value {
+        serde_json::Value::Object(obj) if is_actor_claim(&claim_type) => {
+            for (nested_type, nested_value) in obj {
+                insert_claim(claims, format!("{claim_type}.{nested_type}"), nested_value);
+            }
+        }
+        value => {
+            if let Some(value) = json_to_claim_value(value) {
+                claims.insert(claim_type.into(), value);
+            }
+        }
+    }
+}
+
+fn is_actor_claim(claim_type: &str) -> bool {
+    claim_type == claim_types::ACT
+        || claim_type == claim_types::MAY_ACT
+        || claim_type.ends_with(&format!(".{}", claim_types::ACT))
+}
+
 fn json_to_claim_value(json_value: serde_json::Value) -> Option<ClaimValue> {
     match json_value {
         serde_json::Value::Array(arr) if !arr.is_empty() => json_arr_to_plain_values(arr).map(ClaimValue::Array),
@@ -103,8 +1212,66 @@ fn json_to_plain_value(json_value: serde_json::Value) -> Option<ClaimPlainValue>
                 num.as_i64().map(ClaimPlainValue::Int)
             }
         }
-        serde_json::Value::String(s) => Some(ClaimPlainValue::String(s)),
+        serde_json::Value::String(s) => Some(ClaimPlainValue::String(s.into())),
         serde_json::Value::Null => None,
-        _ => Some(ClaimPlainValue::String(json_value.to_string())),
+        _ => Some(ClaimPlainValue::String(json_value.to_string().into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+
+    use super::*;
+
+    const SECRET: &[u8] = b"key ring secret";
+
+    fn token(kid: Option<&str>) -> String {
+        let header = Header {
+            kid: kid.map(str::to_owned),
+            ..Header::new(Algorithm::HS256)
+        };
+        let claims = serde_json::json!({ "sub": "alice", "exp": 4102444800u64 });
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn decode(ring: &JwtKeyRing, token: &str) -> Result<TokenData<serde_json::Value>, anyhow::Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.validate_aud = false;
+        ring.decode(token, &validation)
+    }
+
+    #[test]
+    fn ring_from_single_key_accepts_token_with_kid() {
+        let ring = JwtKeyRing::from(DecodingKey::from_secret(SECRET));
+
+        let token_data = decode(&ring, &token(Some("k1"))).unwrap();
+
+        assert_eq!(token_data.claims["sub"], "alice");
+    }
+
+    #[test]
+    fn key_registered_under_kid_is_preferred() {
+        let ring = JwtKeyRing::new()
+            .add_fallback_key(DecodingKey::from_secret(b"other secret"))
+            .add_key("k1", DecodingKey::from_secret(SECRET));
+
+        assert!(decode(&ring, &token(Some("k1"))).is_ok());
+    }
+
+    #[test]
+    fn unknown_kid_without_fallback_keys_is_rejected() {
+        let ring = JwtKeyRing::new().add_key("k2", DecodingKey::from_secret(SECRET));
+
+        let error = decode(&ring, &token(Some("k1"))).unwrap_err();
+
+        assert_eq!(error.to_string(), "No decoding key matches kid k1");
+    }
+
+    #[test]
+    fn token_signed_with_another_key_is_rejected() {
+        let ring = JwtKeyRing::from(DecodingKey::from_secret(b"other secret"));
+
+        assert!(decode(&ring, &token(Some("k1"))).is_err());
     }
 }
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use jsonwebtoken::{Algorithm, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    core::{
+        authentication::{AuthenticationError, AuthenticationHandler, AuthenticationResult},
+        authorization::AuthorizationFailure,
+        cache::AuthCache,
+        circuit_breaker::CircuitBreaker,
+        http::{AuthResponse, Request},
+        http_client::{default_http_client, error_for_status, HttpClient, HttpClientOptions},
+        principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+    },
+    jwt::JwtKeyRing,
+};
+
+pub mod kubernetes_claims {
+    pub const NAMESPACE: &str = "kubernetes.namespace";
+    pub const SERVICE_ACCOUNT: &str = "kubernetes.serviceaccount";
+    pub const SERVICE_ACCOUNT_UID: &str = "kubernetes.serviceaccount.uid";
+    pub const POD: &str = "kubernetes.pod";
+    pub const POD_UID: &str = "kubernetes.pod.uid";
+    pub const GROUPS: &str = "kubernetes.groups";
+}
+
+const IN_CLUSTER_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+const IN_CLUSTER_CA_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/ca.crt";
+const SERVICE_ACCOUNT_PREFIX: &str = "system:serviceaccount:";
+
+pub struct TokenReviewOptions {
+    pub api_server: String,
+    /// Token used to call the API server. It needs permission to create `tokenreviews`.
+    pub token: String,
+    pub ca_certificate_pem: Option<Vec<u8>>,
+    pub audiences: Vec<String>,
+    pub cache: Option<Arc<dyn AuthCache>>,
+    pub cache_ttl: Duration,
+    /// Stops calling the API server while it keeps failing. Cached reviews are still served; other tokens
+    /// fail with [`CircuitOpen`](crate::core::circuit_breaker::CircuitOpen), answered with a 503.
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Defaults to a [`ReqwestHttpClient`](crate::core::http_client::ReqwestHttpClient) trusting
+    /// `ca_certificate_pem`.
+    pub http_client: Option<Arc<dyn HttpClient>>,
+}
+
+impl TokenReviewOptions {
+    pub fn new(api_server: String, token: String) -> Self {
+        Self {
+            api_server,
+            token,
+            ca_certificate_pem: None,
+            audiences: Vec::new(),
+            cache: None,
+            cache_ttl: Duration::from_secs(60),
+            circuit_breaker: None,
+            http_client: None,
+        }
+    }
+
+    /// Uses the pod's own service account and the API server address injected into every pod.
+    pub fn in_cluster() -> Result<Self, anyhow::Error> {
+        let host = std::env::var("KUBERNETES_SERVICE_HOST")?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT")?;
+        let host = if host.contains(':') { format!("[{host}]") } else { host };
+
+        Ok(Self {
+            ca_certificate_pem: Some(std::fs::read(IN_CLUSTER_CA_PATH)?),
+            ..Self::new(
+                format!("https://{host}:{port}"),
+                std::fs::read_to_string(IN_CLUSTER_TOKEN_PATH)?.trim().to_owned(),
+            )
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenReview {
+    status: TokenReviewStatus,
+}
+
+#[derive(Deserialize)]
+struct TokenReviewStatus {
+    #[serde(default)]
+    authenticated: bool,
+    #[serde(default)]
+    user: TokenReviewUser,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct TokenReviewUser {
+    #[serde(default)]
+    username: String,
+    uid: Option<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    extra: HashMap<String, Vec<String>>,
+}
+
+struct TokenReviewClient {
+    options: TokenReviewOptions,
+    http_client: Arc<dyn HttpClient>,
+}
+
+impl TokenReviewClient {
+    async fn review(&self, token: &str) -> Result<UserPrincipal, anyhow::Error> {
+        let cache_key = format!("k8s-token-review:{}", hex::encode(Sha256::digest(token)));
+        if let Some(cache) = &self.options.cache {
+            if let Some(cached) = cache.get(&cache_key).await? {
+                return review_principal(serde_json::from_slice(&cached)?);
+            }
+        }
+
+        let response = match &self.options.circuit_breaker {
+            Some(circuit_breaker) => circuit_breaker.call(self.request_review(token)).await?,
+            None => self.request_review(token).await?,
+        };
+        let review = serde_json::from_slice::<TokenReview>(&response)?;
+        if let Some(cache) = &self.options.cache {
+            if review.status.authenticated {
+                cache.set(&cache_key, response, self.options.cache_ttl).await?;
+            }
+        }
+
+        review_principal(review)
+    }
+
+    async fn request_review(&self, token: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let body = serde_json::json!({
+            "apiVersion": "authentication.k8s.io/v1",
+            "kind": "TokenReview",
+            "spec": { "token": token, "audiences": self.options.audiences },
+        });
+        let request = http::Request::post(format!(
+            "{}/apis/authentication.k8s.io/v1/tokenreviews",
+            self.options.api_server.trim_end_matches('/')
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", self.options.token))
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+        .body(serde_json::to_vec(&body)?)?;
+
+        Ok(error_for_status(self.http_client.send(request).await?)?.into_body())
+    }
+}
+
+fn review_principal(review: TokenReview) -> Result<UserPrincipal, anyhow::Error> {
+    let status = review.status;
+    if !status.authenticated {
+        bail!(
+            "TokenReview rejected the token: {}",
+            status.error.as_deref().unwrap_or("not authenticated")
+        );
+    }
+
+    let user = status.user;
+    let (namespace, service_account) = user
+        .username
+        .strip_prefix(SERVICE_ACCOUNT_PREFIX)
+        .and_then(|name| name.split_once(':'))
+        .ok_or_else(|| anyhow!("{} is not a service account", user.username))?;
+
+    let mut claims = HashMap::from([
+        (claim_types::SUBJECT.into(), string_claim(&user.username)),
+        (kubernetes_claims::NAMESPACE.into(), string_claim(namespace)),
+        (kubernetes_claims::SERVICE_ACCOUNT.into(), string_claim(service_account)),
+    ]);
+    if let Some(uid) = &user.uid {
+        claims.insert(kubernetes_claims::SERVICE_ACCOUNT_UID.into(), string_claim(uid));
+    }
+
+    let extra = |key: &str| user.extra.get(key).and_then(|values| values.first());
+    if let Some(pod) = extra("authentication.kubernetes.io/pod-name") {
+        claims.insert(kubernetes_claims::POD.into(), string_claim(pod));
+    }
+    if let Some(pod_uid) = extra("authentication.kubernetes.io/pod-uid") {
+        claims.insert(kubernetes_claims::POD_UID.into(), string_claim(pod_uid));
+    }
+
+    if !user.groups.is_empty() {
+        claims.insert(
+            kubernetes_claims::GROUPS.into(),
+            ClaimValue::Array(
+                user.groups
+                    .into_iter()
+                    .map(|value| ClaimPlainValue::String(value.into()))
+                    .collect(),
+            ),
+        );
+    }
+
+    Ok(UserPrincipal { claims })
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountTokenClaims {
+    sub: String,
+    #[serde(rename = "kubernetes.io")]
+    kubernetes: KubernetesClaims,
+}
+
+#[derive(Deserialize)]
+struct KubernetesClaims {
+    namespace: String,
+    serviceaccount: NamedObject,
+    pod: Option<NamedObject>,
+}
+
+#[derive(Deserialize)]
+struct NamedObject {
+    name: String,
+    uid: Option<String>,
+}
+
+struct OidcValidation {
+    validation: Validation,
+    keys: JwtKeyRing,
+}
+
+impl OidcValidation {
+    fn validate(&self, token: &str) -> Result<UserPrincipal, anyhow::Error> {
+        let token_claims = self
+            .keys
+            .decode::<ServiceAccountTokenClaims>(token, &self.validation)?
+            .claims;
+        let kubernetes = token_claims.kubernetes;
+
+        let mut claims = HashMap::from([
+            (claim_types::SUBJECT.into(), string_claim(&token_claims.sub)),
+            (kubernetes_claims::NAMESPACE.into(), string_claim(&kubernetes.namespace)),
+            (
+                kubernetes_claims::SERVICE_ACCOUNT.into(),
+                string_claim(&kubernetes.serviceaccount.name),
+            ),
+        ]);
+        if let Some(uid) = &kubernetes.serviceaccount.uid {
+            claims.insert(kubernetes_claims::SERVICE_ACCOUNT_UID.into(), string_claim(uid));
+        }
+        if let Some(pod) = &kubernetes.pod {
+            claims.insert(kubernetes_claims::POD.into(), string_claim(&pod.name));
+            if let Some(uid) = &pod.uid {
+                claims.insert(kubernetes_claims::POD_UID.into(), string_claim(uid));
+            }
+        }
+
+        Ok(UserPrincipal { claims })
+    }
+}
+
+enum Validator {
+    TokenReview(TokenReviewClient),
+    Oidc(OidcValidation),
+}
+
+/// Authenticates bearer tokens issued to Kubernetes service accounts, either by asking the API server
+/// (`TokenReview`, which also catches tokens of deleted pods) or by verifying them offline against the
+/// cluster's service account issuer.
+pub struct ServiceAccountHandler {
+    validator: Arc<Validator>,
+}
+
+impl ServiceAccountHandler {
+    pub fn token_review(options: TokenReviewOptions) -> Result<Self, anyhow::Error> {
+        let http_client = match &options.http_client {
+            Some(http_client) => http_client.clone(),
+            None => Arc::new(
+                HttpClientOptions {
+                    root_certificates_pem: options.ca_certificate_pem.iter().cloned().collect(),
+                    ..HttpClientOptions::new()
+                }
+                .build()?,
+            ),
+        };
+
+        Ok(Self {
+            validator: Arc::new(Validator::TokenReview(TokenReviewClient { options, http_client })),
+        })
+    }
+
+    /// `keys` are typically kept up to date by a `JwksProvider` pointed at [`Self::discover_jwks_uri`].
+    pub fn oidc(issuer: &str, audiences: &[&str], keys: JwtKeyRing) -> Self {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.algorithms = vec![Algorithm::RS256, Algorithm::ES256];
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(audiences);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        Self {
+            validator: Arc::new(Validator::Oidc(OidcValidation { validation, keys })),
+        }
+    }
+
+    pub async fn discover_jwks_uri(issuer: &str) -> Result<String, anyhow::Error> {
+        Self::discover_jwks_uri_with_client(default_http_client().as_ref(), issuer).await
+    }
+
+    pub async fn discover_jwks_uri_with_client(
+        http_client: &dyn HttpClient,
+        issuer: &str,
+    ) -> Result<String, anyhow::Error> {
+        #[derive(Deserialize)]
+        struct Discovery {
+            jwks_uri: String,
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let response = error_for_status(http_client.send(http::Request::get(url).body(Vec::new())?).await?)?;
+        let discovery = serde_json::from_slice::<Discovery>(response.body())?;
+
+        Ok(discovery.jwks_uri)
+    }
+}
+
+impl AuthenticationHandler for ServiceAccountHandler {
+    type AuthFut = Pin<Box<dyn Future<Output = AuthenticationResult> + Send>>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let token = request
+            .get_header(&AUTHORIZATION)
+            .and_then(|h| h.to_str().ok()?.strip_prefix("Bearer "))
+            .map(str::to_owned);
+        let Some(token) = token else {
+            return Box::pin(ready(Err(AuthenticationError::NoResult)));
+        };
+
+        let validator = self.validator.clone();
+        Box::pin(async move {
+            let principal = match validator.as_ref() {
+                Validator::TokenReview(client) => client.review(&token).await,
+                Validator::Oidc(oidc) => oidc.validate(&token),
+            };
+            principal.map_err(AuthenticationError::fail)
+        })
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))]),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn string_claim(value: &str) -> ClaimValue {
+    ClaimValue::PlainValue(ClaimPlainValue::String(value.into()))
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ldap3::{drive, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+
+use crate::core::{
+    credentials::CredentialValidator,
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+const INVALID_CREDENTIALS: u32 = 49;
+
+pub struct LdapOptions {
+    pub url: String,
+    pub starttls: bool,
+    pub connection_timeout: Duration,
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub user_search_base: String,
+    pub user_filter: String,
+    pub group_attribute: String,
+    pub group_roles: HashMap<String, String>,
+    pub max_idle_connections: usize,
+}
+
+impl LdapOptions {
+    pub fn new(url: String, bind_dn: String, bind_password: String, user_search_base: String) -> Self {
+        Self {
+            url,
+            starttls: false,
+            connection_timeout: Duration::from_secs(5),
+            bind_dn,
+            bind_password,
+            user_search_base,
+            user_filter: "(uid={username})".to_owned(),
+            group_attribute: "memberOf".to_owned(),
+            group_roles: HashMap::new(),
+            max_idle_connections: 4,
+        }
+    }
+}
+
+pub struct LdapCredentialValidator {
+    options: LdapOptions,
+    idle_connections: Mutex<Vec<Ldap>>,
+}
+
+impl LdapCredentialValidator {
+    pub fn new(options: LdapOptions) -> Self {
+        Self {
+            options,
+            idle_connections: Mutex::default(),
+        }
+    }
+
+    async fn connect(&self) -> Result<Ldap, anyhow::Error> {
+        let settings = LdapConnSettings::new()
+            .set_starttls(self.options.starttls)
+            .set_conn_timeout(self.options.connection_timeout);
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, &self.options.url).await?;
+        drive!(conn);
+
+        Ok(ldap)
+    }
+
+    async fn acquire_service_connection(&self) -> Result<Ldap, anyhow::Error> {
+        while let Some(mut ldap) = self.pop_idle_connection() {
+            if !ldap.is_closed() {
+                return Ok(ldap);
+            }
+        }
+
+        let mut ldap = self.connect().await?;
+        ldap.simple_bind(&self.options.bind_dn, &self.options.bind_password)
+            .await?
+            .success()?;
+
+        Ok(ldap)
+    }
+
+    fn pop_idle_connection(&self) -> Option<Ldap> {
+        self.idle_connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+    }
+
+    fn release_service_connection(&self, ldap: Ldap) {
+        let mut idle = self.idle_connections.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < self.options.max_idle_connections {
+            idle.push(ldap);
+        }
+    }
+
+    async fn find_user(&self, username: &str) -> Result<Option<SearchEntry>, anyhow::Error> {
+        let mut ldap = self.acquire_service_connection().await?;
+        let filter = self.options.user_filter.replace("{username}", &ldap_escape(username));
+        let (entries, _) = ldap
+            .search(
+                &self.options.user_search_base,
+                Scope::Subtree,
+                &filter,
+                vec![self.options.group_attribute.as_str()],
+            )
+            .await?
+            .success()?;
+        self.release_service_connection(ldap);
+
+        let mut entries = entries.into_iter();
+        match (entries.next(), entries.next()) {
+            (Some(entry), None) => Ok(Some(SearchEntry::construct(entry))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn verify_password(&self, user_dn: &str, password: &str) -> Result<bool, anyhow::Error> {
+        let mut ldap = self.connect().await?;
+        let result = ldap.simple_bind(user_dn, password).await?;
+        let _ = ldap.unbind().await;
+
+        if result.rc == INVALID_CREDENTIALS {
+            return Ok(false);
+        }
+
+        result.success()?;
+        Ok(true)
+    }
+
+    fn user_principal(&self, username: &str, mut entry: SearchEntry) -> UserPrincipal {
+        let mut claims = HashMap::from([(
+            claim_types::SUBJECT.into(),
+            ClaimValue::PlainValue(ClaimPlainValue::String(username.into())),
+        )]);
+
+        let roles = entry
+            .attrs
+            .remove(&self.options.group_attribute)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|group| self.options.group_roles.get(&group).cloned())
+            .map(|value| ClaimPlainValue::String(value.into()))
+            .collect::<Vec<_>>();
+        if !roles.is_empty() {
+            claims.insert(claim_types::ROLE.into(), ClaimValue::Array(roles));
+        }
+
+        UserPrincipal { claims }
+    }
+}
+
+#[async_trait]
+impl CredentialValidator for LdapCredentialValidator {
+    async fn validate(&self, username: &str, password: &str) -> Result<Option<UserPrincipal>, anyhow::Error> {
+        if username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+
+        let Some(entry) = self.find_user(username).await? else {
+            return Ok(None);
+        };
+
+        if !self.verify_password(&entry.dn, password).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(self.user_principal(username, entry)))
+    }
+}
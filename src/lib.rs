@@ -1,7 +1,71 @@
+pub mod anonymous;
+#[cfg(feature = "basic")]
+pub mod basic;
+#[cfg(feature = "connection-monitor")]
+pub mod connection_monitor;
+#[cfg(feature = "cookie")]
+pub mod cookie;
 pub mod core;
+#[cfg(feature = "correlation")]
+pub mod correlation;
+#[cfg(feature = "credentials")]
+pub mod credentials;
+#[cfg(feature = "data-protection")]
+pub mod data_protection;
+#[cfg(feature = "digest")]
+pub mod digest;
 pub mod framework;
+#[cfg(feature = "hawk")]
+pub mod hawk;
+#[cfg(feature = "identity-assertion")]
+pub mod identity_assertion;
+#[cfg(feature = "jwks")]
+pub mod jwks;
 #[cfg(feature = "jwt")]
 pub mod jwt;
+#[cfg(feature = "kubernetes")]
+pub mod kubernetes;
+#[cfg(feature = "ldap")]
+pub mod ldap;
+#[cfg(feature = "login")]
+pub mod login;
+#[cfg(feature = "magic-link")]
+pub mod magic_link;
+#[cfg(feature = "mfa")]
+pub mod mfa;
+#[cfg(feature = "negotiate")]
+pub mod negotiate;
+#[cfg(feature = "oauth")]
+pub mod oauth;
+#[cfg(feature = "oauth-login")]
+pub mod oauth_login;
+#[cfg(feature = "oidc")]
+pub mod oidc;
+#[cfg(feature = "policy-config")]
+pub mod policy_config;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+#[cfg(feature = "saml")]
+pub mod saml;
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
+#[cfg(feature = "social")]
+pub mod social;
+#[cfg(feature = "token-forwarding")]
+pub mod token_forwarding;
+#[cfg(feature = "tokens")]
+pub mod tokens;
+#[cfg(feature = "trusted-header")]
+pub mod trusted_header;
+#[cfg(feature = "webauthn")]
+pub mod webauthn;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
 #[cfg(feature = "jwt")]
 pub use jsonwebtoken;
+#[cfg(feature = "webauthn")]
+pub use webauthn_rs;
+
+#[cfg(feature = "macros")]
+pub use web_auth_rs_macros::{allow_anonymous, authorize};
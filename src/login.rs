@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode};
+use serde::Deserialize;
+
+use crate::core::{
+    authentication::{AuthenticationProperties, AuthenticationService, CompoundAuthenticationHandler, SchemeName},
+    credentials::{AccountLockedOut, CredentialValidator},
+    http::AuthResponse,
+    redirect::{redirect, ReturnUrlValidator},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub remember_me: bool,
+    #[serde(default)]
+    pub return_url: Option<String>,
+}
+
+impl LoginRequest {
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<Self, anyhow::Error> {
+        let media_type = content_type.and_then(|c| c.split(';').next()).map(str::trim);
+        match media_type {
+            Some(m) if m.eq_ignore_ascii_case("application/json") => Ok(serde_json::from_slice(body)?),
+            Some(m) if m.eq_ignore_ascii_case("application/x-www-form-urlencoded") => parse_form(body),
+            _ => bail!("Unsupported login request content type"),
+        }
+    }
+}
+
+pub enum LoginResponseMode {
+    Redirect {
+        default_url: String,
+        failure_url: Option<String>,
+    },
+    Json,
+}
+
+pub struct LoginEndpoint<Handler: CompoundAuthenticationHandler> {
+    pub auth_service: Arc<AuthenticationService<Handler>>,
+    pub validator: Arc<dyn CredentialValidator>,
+    pub scheme: Option<SchemeName>,
+    pub return_url: ReturnUrlValidator,
+    pub response_mode: LoginResponseMode,
+}
+
+impl<Handler> LoginEndpoint<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub async fn handle(&self, content_type: Option<&str>, body: &[u8]) -> AuthResponse {
+        let Ok(login) = LoginRequest::parse(content_type, body) else {
+            return self.failure(StatusCode::BAD_REQUEST, "invalid_request");
+        };
+
+        let principal = match self.validator.validate(&login.username, &login.password).await {
+            Ok(Some(principal)) => principal,
+            Ok(None) => return self.failure(StatusCode::UNAUTHORIZED, "invalid_credentials"),
+            Err(err) if err.is::<AccountLockedOut>() => return self.failure(StatusCode::FORBIDDEN, "locked_out"),
+            Err(_) => return self.failure(StatusCode::INTERNAL_SERVER_ERROR, "server_error"),
+        };
+
+        let properties = AuthenticationProperties {
+            is_persistent: login.remember_me,
+            ..Default::default()
+        };
+        let sign_in = self
+            .auth_service
+            .sign_in(self.scheme.as_ref().map(SchemeName::as_str), &principal, &properties)
+            .await
+            .unwrap_or_else(|err| err.response());
+        if !sign_in.status_code.is_success() {
+            return sign_in;
+        }
+
+        match &self.response_mode {
+            LoginResponseMode::Redirect { default_url, .. } => {
+                let mut response = self
+                    .return_url
+                    .redirect_after_sign_in(login.return_url.as_deref(), default_url);
+                response.headers.extend(sign_in.headers);
+                response
+            }
+            LoginResponseMode::Json => {
+                let mut response = sign_in;
+                if response.body.is_empty() {
+                    response.body = br#"{"authenticated":true}"#.to_vec();
+                }
+                response
+                    .headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                response
+            }
+        }
+    }
+
+    fn failure(&self, status_code: StatusCode, error: &str) -> AuthResponse {
+        match &self.response_mode {
+            LoginResponseMode::Redirect {
+                failure_url: Some(failure_url),
+                ..
+            } => {
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("error", error)
+                    .finish();
+                let separator = if failure_url.contains('?') { '&' } else { '?' };
+                redirect(&format!("{failure_url}{separator}{query}"))
+            }
+            _ => AuthResponse {
+                status_code,
+                headers: HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static("application/json"))]),
+                body: serde_json::json!({ "error": error }).to_string().into_bytes(),
+            },
+        }
+    }
+}
+
+fn parse_form(body: &[u8]) -> Result<LoginRequest, anyhow::Error> {
+    let mut username = None;
+    let mut password = None;
+    let mut remember_me = false;
+    let mut return_url = None;
+    for (name, value) in form_urlencoded::parse(body) {
+        match name.as_ref() {
+            "username" => username = Some(value.into_owned()),
+            "password" => password = Some(value.into_owned()),
+            "remember_me" => remember_me = matches!(value.as_ref(), "true" | "on" | "1"),
+            "return_url" => return_url = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+
+    Ok(LoginRequest {
+        username: username.ok_or_else(|| anyhow!("Login form doesn't contain a username"))?,
+        password: password.ok_or_else(|| anyhow!("Login form doesn't contain a password"))?,
+        remember_me,
+        return_url,
+    })
+}
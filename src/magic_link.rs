@@ -0,0 +1,94 @@
+use std::{future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use http::{HeaderMap, StatusCode};
+
+use crate::{
+    core::{
+        authentication::{AuthenticationProperties, AuthenticationService, CompoundAuthenticationHandler, SchemeName},
+        http::{AuthResponse, Request},
+        principal::UserPrincipal,
+        redirect::redirect,
+    },
+    tokens::{token_purposes, TokenProvider},
+};
+
+pub const TOKEN_PARAMETER: &str = "token";
+
+pub async fn magic_link(tokens: &dyn TokenProvider, base_url: &str, subject: &str) -> Result<String, anyhow::Error> {
+    let token = tokens.generate(token_purposes::MAGIC_LINK, subject).await?;
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair(TOKEN_PARAMETER, &token)
+        .finish();
+    let separator = if base_url.contains('?') { '&' } else { '?' };
+
+    Ok(format!("{base_url}{separator}{query}"))
+}
+
+#[async_trait]
+pub trait PrincipalLookup: Send + Sync + 'static {
+    async fn find(&self, subject: &str) -> Result<Option<UserPrincipal>, anyhow::Error>;
+}
+
+pub struct MagicLinkEndpoint<Handler: CompoundAuthenticationHandler> {
+    pub tokens: Arc<dyn TokenProvider>,
+    pub principals: Arc<dyn PrincipalLookup>,
+    pub auth_service: Arc<AuthenticationService<Handler>>,
+    pub scheme: Option<SchemeName>,
+    pub success_url: String,
+    pub failure_url: Option<String>,
+}
+
+impl<Handler> MagicLinkEndpoint<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub fn handle<'a>(&'a self, request: &impl Request) -> impl Future<Output = AuthResponse> + 'a {
+        let token = request.get_uri().query().and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find_map(|(name, value)| (name == TOKEN_PARAMETER).then(|| value.into_owned()))
+        });
+
+        async move {
+            let Some(token) = token else {
+                return self.failure();
+            };
+
+            let principal = match self.tokens.validate(token_purposes::MAGIC_LINK, &token).await {
+                Ok(subject) => self.principals.find(&subject).await,
+                Err(err) => Err(err),
+            };
+            let Ok(Some(principal)) = principal else {
+                return self.failure();
+            };
+
+            let sign_in = self
+                .auth_service
+                .sign_in(
+                    self.scheme.as_ref().map(SchemeName::as_str),
+                    &principal,
+                    &AuthenticationProperties::default(),
+                )
+                .await
+                .unwrap_or_else(|err| err.response());
+            if !sign_in.status_code.is_success() {
+                return sign_in;
+            }
+
+            let mut response = redirect(&self.success_url);
+            response.headers.extend(sign_in.headers);
+            response
+        }
+    }
+
+    fn failure(&self) -> AuthResponse {
+        match &self.failure_url {
+            Some(failure_url) => redirect(failure_url),
+            None => AuthResponse {
+                status_code: StatusCode::UNAUTHORIZED,
+                headers: HeaderMap::default(),
+                body: Vec::new(),
+            },
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    borrow::Cow,
+    future::{ready, Ready},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::core::{
+    authorization::{AuthorizationHandlerContext, AuthorizationRequirement},
+    credentials::constant_time_eq,
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+pub mod amr_values {
+    pub const OTP: &str = "otp";
+}
+
+const SECRET_LENGTH: usize = 20;
+
+#[derive(Clone)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn generate() -> Result<Self, anyhow::Error> {
+        let mut secret = vec![0u8; SECRET_LENGTH];
+        getrandom::getrandom(&mut secret)?;
+        Ok(Self(secret))
+    }
+
+    pub fn from_base32(encoded: &str) -> Result<Self, anyhow::Error> {
+        let normalized = encoded
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let secret = BASE32_NOPAD
+            .decode(normalized.as_bytes())
+            .map_err(|err| anyhow!("Invalid TOTP secret: {err}"))?;
+
+        Ok(Self(secret))
+    }
+
+    pub fn to_base32(&self) -> String {
+        BASE32_NOPAD.encode(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+pub struct Totp {
+    pub issuer: String,
+    pub digits: u32,
+    pub period: Duration,
+    pub skew: u64,
+}
+
+impl Totp {
+    pub fn new(issuer: String) -> Self {
+        Self {
+            issuer,
+            digits: 6,
+            period: Duration::from_secs(30),
+            skew: 1,
+        }
+    }
+
+    pub fn provisioning_uri(&self, secret: &TotpSecret, account_name: &str) -> String {
+        let label = encode_label(&format!("{}:{account_name}", self.issuer));
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("secret", &secret.to_base32())
+            .append_pair("issuer", &self.issuer)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &self.digits.to_string())
+            .append_pair("period", &self.period.as_secs().to_string())
+            .finish();
+
+        format!("otpauth://totp/{label}?{query}")
+    }
+
+    pub fn generate(&self, secret: &TotpSecret, time: SystemTime) -> String {
+        self.code(secret, self.time_step(time))
+    }
+
+    pub fn verify(&self, secret: &TotpSecret, code: &str, time: SystemTime) -> Option<u64> {
+        let code = code.trim();
+        if code.len() != self.digits as usize {
+            return None;
+        }
+
+        let current_step = self.time_step(time);
+        (current_step.saturating_sub(self.skew)..=current_step.saturating_add(self.skew))
+            .find(|step| constant_time_eq(self.code(secret, *step).as_bytes(), code.as_bytes()))
+    }
+
+    pub fn complete_verification(
+        &self,
+        principal: &UserPrincipal,
+        secret: &TotpSecret,
+        code: &str,
+        time: SystemTime,
+    ) -> Option<UserPrincipal> {
+        self.verify(secret, code, time)?;
+        Some(with_authentication_method(principal, amr_values::OTP))
+    }
+
+    fn time_step(&self, time: SystemTime) -> u64 {
+        let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        elapsed / self.period.as_secs().max(1)
+    }
+
+    fn code(&self, secret: &TotpSecret, step: u64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary =
+            u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+        let code = binary as u64 % 10u64.pow(self.digits);
+
+        format!("{code:0width$}", width = self.digits as usize)
+    }
+}
+
+#[derive(Clone)]
+pub struct MfaRequired;
+
+impl AuthorizationRequirement for MfaRequired {
+    type AuthorizeFut<'a> = Ready<()>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("MfaRequired")
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let has_otp = context
+            .principal()
+            .claim(claim_types::AMR)
+            .is_some_and(|c| c.iter().any(|v| v.as_str() == Some(amr_values::OTP)));
+        if has_otp {
+            context.succeed(&self.name());
+        }
+
+        ready(())
+    }
+}
+
+fn with_authentication_method(principal: &UserPrincipal, method: &str) -> UserPrincipal {
+    let method = ClaimPlainValue::String(method.into());
+    let mut methods = principal
+        .claim(claim_types::AMR)
+        .map(|c| c.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    if !methods.contains(&method) {
+        methods.push(method);
+    }
+
+    let mut principal = principal.clone();
+    principal
+        .claims
+        .insert(claim_types::AMR.into(), ClaimValue::Array(methods));
+    principal
+}
+
+fn encode_label(label: &str) -> String {
+    form_urlencoded::byte_serialize(label.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
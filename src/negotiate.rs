@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    http::{AuthResponse, Request, RequestExtensions},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+pub enum NegotiateStep {
+    Continue(Vec<u8>),
+    Complete {
+        principal_name: String,
+        group_sids: Vec<String>,
+        token: Option<Vec<u8>>,
+    },
+}
+
+pub trait NegotiateAcceptor: Send + Sync + 'static {
+    fn accept(&self, token: &[u8]) -> Result<NegotiateStep, anyhow::Error>;
+}
+
+#[derive(Clone)]
+pub struct NegotiateContinuation(pub Vec<u8>);
+
+#[derive(Clone)]
+pub struct NegotiateMutualAuthToken(pub Vec<u8>);
+
+impl NegotiateMutualAuthToken {
+    pub fn header_value(&self) -> HeaderValue {
+        negotiate_header_value(&self.0)
+    }
+}
+
+pub struct NegotiateHandler<Acceptor: NegotiateAcceptor> {
+    pub acceptor: Acceptor,
+}
+
+impl<A> AuthenticationHandler for NegotiateHandler<A>
+where
+    A: NegotiateAcceptor,
+{
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        let token = request
+            .get_header(&AUTHORIZATION)
+            .and_then(|h| h.to_str().ok()?.strip_prefix("Negotiate "))
+            .map(|token| STANDARD.decode(token.trim()));
+
+        let token = match token {
+            Some(Ok(token)) => token,
+            Some(Err(err)) => return ready(Err(AuthenticationError::Fail(AuthError::InvalidToken(err.to_string())))),
+            None => return ready(Err(AuthenticationError::NoResult)),
+        };
+
+        match self.acceptor.accept(&token) {
+            Ok(NegotiateStep::Continue(token)) => {
+                request.get_extensions_mut().insert(NegotiateContinuation(token));
+                ready(Err(AuthenticationError::Fail(AuthError::Other(
+                    "Negotiate handshake requires another round trip".into(),
+                ))))
+            }
+            Ok(NegotiateStep::Complete {
+                principal_name,
+                group_sids,
+                token,
+            }) => {
+                if let Some(token) = token {
+                    request.get_extensions_mut().insert(NegotiateMutualAuthToken(token));
+                }
+
+                ready(Ok(negotiate_principal(principal_name, group_sids)))
+            }
+            Err(err) => ready(Err(AuthenticationError::fail(err))),
+        }
+    }
+
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut {
+        let header_value = match request.get_extensions().get::<NegotiateContinuation>() {
+            Some(continuation) => negotiate_header_value(&continuation.0),
+            None => HeaderValue::from_static("Negotiate"),
+        };
+
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::from_iter([(WWW_AUTHENTICATE, header_value)]),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+fn negotiate_header_value(token: &[u8]) -> HeaderValue {
+    HeaderValue::try_from(format!("Negotiate {}", STANDARD.encode(token)))
+        .expect("base64 encoded token is a valid header value")
+}
+
+fn negotiate_principal(principal_name: String, group_sids: Vec<String>) -> UserPrincipal {
+    let mut claims = [
+        (claim_types::SUBJECT, principal_name.clone()),
+        (claim_types::NAME, principal_name),
+    ]
+    .into_iter()
+    .map(|(t, v)| (t.into(), ClaimValue::PlainValue(ClaimPlainValue::String(v.into()))))
+    .collect::<HashMap<_, _>>();
+
+    if !group_sids.is_empty() {
+        claims.insert(
+            claim_types::GROUP_SID.into(),
+            ClaimValue::Array(
+                group_sids
+                    .into_iter()
+                    .map(|value| ClaimPlainValue::String(value.into()))
+                    .collect(),
+            ),
+        );
+    }
+
+    UserPrincipal { claims }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::bail;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderValue,
+};
+use serde::Deserialize;
+
+use crate::core::http_client::{default_http_client, form_body, HttpClient};
+
+pub mod token_types {
+    pub const ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+    pub const REFRESH_TOKEN: &str = "urn:ietf:params:oauth:token-type:refresh_token";
+    pub const ID_TOKEN: &str = "urn:ietf:params:oauth:token-type:id_token";
+    pub const JWT: &str = "urn:ietf:params:oauth:token-type:jwt";
+}
+
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+pub struct TokenExchangeOptions {
+    pub token_endpoint: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub subject_token_type: String,
+    pub requested_token_type: Option<String>,
+    pub scope: Option<String>,
+    pub refresh_before_expiry: Duration,
+    pub max_cached_tokens: usize,
+    /// Defaults to a [`ReqwestHttpClient`](crate::core::http_client::ReqwestHttpClient).
+    pub http_client: Option<Arc<dyn HttpClient>>,
+}
+
+impl TokenExchangeOptions {
+    pub fn new(token_endpoint: String, client_id: String) -> Self {
+        Self {
+            token_endpoint,
+            client_id,
+            client_secret: None,
+            subject_token_type: token_types::ACCESS_TOKEN.to_owned(),
+            requested_token_type: None,
+            scope: None,
+            refresh_before_expiry: Duration::from_secs(30),
+            max_cached_tokens: 1024,
+            http_client: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExchangedToken {
+    pub access_token: String,
+    pub issued_token_type: String,
+    pub token_type: String,
+    pub scope: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    issued_token_type: String,
+    token_type: String,
+    scope: Option<String>,
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+pub struct TokenExchangeClient {
+    options: TokenExchangeOptions,
+    http_client: Arc<dyn HttpClient>,
+    cache: Mutex<HashMap<(String, String), ExchangedToken>>,
+}
+
+impl TokenExchangeClient {
+    pub fn new(options: TokenExchangeOptions) -> Self {
+        Self {
+            http_client: options.http_client.clone().unwrap_or_else(default_http_client),
+            options,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub async fn exchange(&self, subject_token: &str, audience: &str) -> Result<ExchangedToken, anyhow::Error> {
+        let key = (subject_token.to_owned(), audience.to_owned());
+        if let Some(token) = self.cached_token(&key) {
+            return Ok(token);
+        }
+
+        let token = self.request_token(subject_token, audience).await?;
+        self.cache_token(key, token.clone());
+
+        Ok(token)
+    }
+
+    async fn request_token(&self, subject_token: &str, audience: &str) -> Result<ExchangedToken, anyhow::Error> {
+        let mut form = vec![
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("subject_token", subject_token),
+            ("subject_token_type", &self.options.subject_token_type),
+            ("audience", audience),
+        ];
+        if let Some(requested_token_type) = &self.options.requested_token_type {
+            form.push(("requested_token_type", requested_token_type));
+        }
+        if let Some(scope) = &self.options.scope {
+            form.push(("scope", scope));
+        }
+
+        let request = http::Request::post(&self.options.token_endpoint).header(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        let request = match &self.options.client_secret {
+            Some(client_secret) => {
+                let credentials = STANDARD.encode(format!("{}:{client_secret}", self.options.client_id));
+                request.header(AUTHORIZATION, format!("Basic {credentials}"))
+            }
+            None => {
+                form.push(("client_id", &self.options.client_id));
+                request
+            }
+        };
+
+        let response = self.http_client.send(request.body(form_body(&form))?).await?;
+        if !response.status().is_success() {
+            match serde_json::from_slice::<ErrorResponse>(response.body()) {
+                Ok(error) => bail!(
+                    "Token exchange failed with {}: {}",
+                    error.error,
+                    error.error_description.unwrap_or_default()
+                ),
+                Err(_) => bail!("Token exchange failed with status {}", response.status()),
+            }
+        }
+
+        let response = serde_json::from_slice::<TokenResponse>(response.body())?;
+        Ok(ExchangedToken {
+            access_token: response.access_token,
+            issued_token_type: response.issued_token_type,
+            token_type: response.token_type,
+            scope: response.scope,
+            expires_at: response
+                .expires_in
+                .map(|expires_in| SystemTime::now() + Duration::from_secs(expires_in)),
+        })
+    }
+
+    fn cached_token(&self, key: &(String, String)) -> Option<ExchangedToken> {
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.get(key).filter(|token| self.is_fresh(token)).cloned()
+    }
+
+    fn cache_token(&self, key: (String, String), token: ExchangedToken) {
+        if token.expires_at.is_none() {
+            return;
+        }
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if cache.len() >= self.options.max_cached_tokens {
+            cache.retain(|_, token| self.is_fresh(token));
+        }
+        if cache.len() < self.options.max_cached_tokens {
+            cache.insert(key, token);
+        }
+    }
+
+    fn is_fresh(&self, token: &ExchangedToken) -> bool {
+        token
+            .expires_at
+            .is_some_and(|expires_at| expires_at > SystemTime::now() + self.options.refresh_before_expiry)
+    }
+}
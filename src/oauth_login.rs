@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail};
+use http::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HOST, LOCATION, SET_COOKIE},
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    core::{
+        authentication::{
+            AuthenticationError, AuthenticationHandler, AuthenticationProperties, AuthenticationResult,
+            AuthenticationService, CompoundAuthenticationHandler, SchemeName,
+        },
+        authorization::AuthorizationFailure,
+        http::{AuthResponse, Request},
+        http_client::{error_for_status, form_body, HttpClient, HttpClientOptions},
+        principal::{claim_types, ClaimPlainValue, ClaimValue, Claims, UserPrincipal},
+        redirect::ReturnUrlValidator,
+    },
+    correlation::CorrelationCookies,
+};
+
+pub type ClaimsMapper = Arc<dyn Fn(&serde_json::Value) -> Result<Claims, anyhow::Error> + Send + Sync>;
+
+pub struct UserInfoRequest {
+    pub endpoint: String,
+    pub method: Method,
+    pub headers: HeaderMap,
+    pub access_token_parameter: Option<String>,
+}
+
+impl UserInfoRequest {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            method: Method::GET,
+            headers: HeaderMap::from_iter([(ACCEPT, HeaderValue::from_static("application/json"))]),
+            access_token_parameter: None,
+        }
+    }
+}
+
+pub struct OAuth2LoginOptions {
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub user_info: UserInfoRequest,
+    pub callback_path: String,
+    pub public_origin: Option<String>,
+    pub scopes: Vec<String>,
+    pub claims_mapper: ClaimsMapper,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+pub struct OAuth2LoginResult {
+    pub principal: UserPrincipal,
+    pub access_token: String,
+    pub return_url: Option<String>,
+    pub correlation_cookie: HeaderValue,
+}
+
+pub struct OAuth2LoginHandler {
+    options: OAuth2LoginOptions,
+    correlation: CorrelationCookies,
+    http_client: Arc<dyn HttpClient>,
+}
+
+impl OAuth2LoginHandler {
+    pub fn new(options: OAuth2LoginOptions, correlation: CorrelationCookies) -> Self {
+        Self {
+            options,
+            correlation,
+            http_client: Arc::new(
+                HttpClientOptions {
+                    user_agent: Some("web-auth-rs".to_owned()),
+                    ..HttpClientOptions::new()
+                }
+                .build()
+                .unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Some providers (GitHub) reject requests without a `User-Agent`, which the default client sets.
+    pub fn with_http_client(self, http_client: Arc<dyn HttpClient>) -> Self {
+        Self { http_client, ..self }
+    }
+
+    pub fn options(&self) -> &OAuth2LoginOptions {
+        &self.options
+    }
+
+    pub async fn callback(&self, request: &impl Request) -> Result<OAuth2LoginResult, anyhow::Error> {
+        let query = request.get_uri().query().unwrap_or_default();
+        let params = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        if let Some(error) = params.get("error") {
+            bail!("Authorization failed with {error}");
+        }
+
+        let state = params
+            .get("state")
+            .ok_or_else(|| anyhow!("Callback doesn't contain a state"))?;
+        let code = params
+            .get("code")
+            .ok_or_else(|| anyhow!("Callback doesn't contain a code"))?;
+        let redirect_uri = self.redirect_uri(request)?;
+        let (correlation, correlation_cookie) = self.correlation.validate(request, state).await?;
+
+        let access_token = self.exchange_code(code, &redirect_uri).await?;
+        let user_info = self.fetch_user_info(&access_token).await?;
+
+        Ok(OAuth2LoginResult {
+            principal: UserPrincipal {
+                claims: (self.options.claims_mapper)(&user_info)?,
+            },
+            access_token,
+            return_url: correlation.return_url,
+            correlation_cookie,
+        })
+    }
+
+    async fn fetch_user_info(&self, access_token: &str) -> Result<serde_json::Value, anyhow::Error> {
+        let user_info = &self.options.user_info;
+        let request = match &user_info.access_token_parameter {
+            Some(parameter) => {
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair(parameter, access_token)
+                    .finish();
+                let separator = if user_info.endpoint.contains('?') { '&' } else { '?' };
+                http::Request::builder().uri(format!("{}{separator}{query}", user_info.endpoint))
+            }
+            None => http::Request::builder()
+                .uri(&user_info.endpoint)
+                .header(AUTHORIZATION, format!("Bearer {access_token}")),
+        };
+        let mut request = request.method(user_info.method.clone()).body(Vec::new())?;
+        request.headers_mut().extend(user_info.headers.clone());
+
+        let response = error_for_status(self.http_client.send(request).await?)?;
+        Ok(serde_json::from_slice(response.body())?)
+    }
+
+    async fn exchange_code(&self, code: &str, redirect_uri: &str) -> Result<String, anyhow::Error> {
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", &self.options.client_id),
+            ("client_secret", &self.options.client_secret),
+        ];
+        let request = http::Request::post(&self.options.token_endpoint)
+            .header(ACCEPT, HeaderValue::from_static("application/json"))
+            .header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            )
+            .body(form_body(&form))?;
+        let response = error_for_status(self.http_client.send(request).await?)?;
+
+        Ok(serde_json::from_slice::<TokenResponse>(response.body())?.access_token)
+    }
+
+    fn authorization_url(&self, state: &str, redirect_uri: &str) -> String {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.options.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.options.scopes.join(" "))
+            .append_pair("state", state)
+            .finish();
+        let separator = if self.options.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+
+        format!("{}{separator}{query}", self.options.authorization_endpoint)
+    }
+
+    fn redirect_uri(&self, request: &impl Request) -> Result<String, anyhow::Error> {
+        let origin = match &self.options.public_origin {
+            Some(origin) => origin.trim_end_matches('/').to_owned(),
+            None => {
+                let uri = request.get_uri();
+                let host = match uri.authority() {
+                    Some(authority) => authority.as_str(),
+                    None => request
+                        .get_header(&HOST)
+                        .and_then(|h| h.to_str().ok())
+                        .ok_or_else(|| anyhow!("Request doesn't have a host"))?,
+                };
+                format!("{}://{host}", uri.scheme_str().unwrap_or("https"))
+            }
+        };
+
+        Ok(format!("{origin}{}", self.options.callback_path))
+    }
+
+    fn challenge_response(&self, request: &impl Request) -> Result<AuthResponse, anyhow::Error> {
+        let redirect_uri = self.redirect_uri(request)?;
+        let (correlation, cookie) = self
+            .correlation
+            .create(Some(&ReturnUrlValidator::original_url(request)))?;
+        let location = HeaderValue::try_from(self.authorization_url(&correlation.state, &redirect_uri))?;
+
+        Ok(AuthResponse {
+            status_code: StatusCode::FOUND,
+            headers: HeaderMap::from_iter([(LOCATION, location), (SET_COOKIE, cookie)]),
+            body: Vec::new(),
+        })
+    }
+}
+
+impl AuthenticationHandler for OAuth2LoginHandler {
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, _: &mut impl Request) -> Self::AuthFut {
+        ready(Err(AuthenticationError::NoResult))
+    }
+
+    fn challenge(&self, request: &impl Request) -> Self::ChallengeFut {
+        ready(self.challenge_response(request).unwrap_or_else(|_| AuthResponse {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        }))
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+pub struct OAuth2CallbackEndpoint<Handler: CompoundAuthenticationHandler> {
+    pub login_handler: Arc<OAuth2LoginHandler>,
+    pub auth_service: Arc<AuthenticationService<Handler>>,
+    pub sign_in_scheme: Option<SchemeName>,
+    pub return_url: ReturnUrlValidator,
+    pub default_url: String,
+}
+
+impl<Handler> OAuth2CallbackEndpoint<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub async fn handle(&self, request: &impl Request) -> AuthResponse {
+        let Ok(result) = self.login_handler.callback(request).await else {
+            return AuthResponse {
+                status_code: StatusCode::UNAUTHORIZED,
+                headers: HeaderMap::default(),
+                body: Vec::new(),
+            };
+        };
+
+        let sign_in = self
+            .auth_service
+            .sign_in(
+                self.sign_in_scheme.as_ref().map(SchemeName::as_str),
+                &result.principal,
+                &AuthenticationProperties::default(),
+            )
+            .await
+            .unwrap_or_else(|err| err.response());
+        if !sign_in.status_code.is_success() {
+            return sign_in;
+        }
+
+        let mut response = self
+            .return_url
+            .redirect_after_sign_in(result.return_url.as_deref(), &self.default_url);
+        response.headers.append(SET_COOKIE, result.correlation_cookie);
+        for (name, value) in &sign_in.headers {
+            response.headers.append(name, value.clone());
+        }
+
+        response
+    }
+}
+
+pub fn json_claims_mapper(mappings: HashMap<String, String>) -> ClaimsMapper {
+    Arc::new(move |user_info| Ok(map_claims(user_info, &mappings)))
+}
+
+pub fn map_claims(user_info: &serde_json::Value, mappings: &HashMap<String, String>) -> Claims {
+    mappings
+        .iter()
+        .filter_map(|(key, claim_type)| {
+            let value = key.split('.').try_fold(user_info, |value, key| value.get(key))?;
+            let value = match value {
+                // Providers like GitHub use numeric ids, but subjects are always compared as strings.
+                serde_json::Value::Number(id) if claim_type == claim_types::SUBJECT => {
+                    ClaimValue::PlainValue(ClaimPlainValue::String(id.to_string().into()))
+                }
+                value => json_claim_value(value)?,
+            };
+            Some((claim_type.into(), value))
+        })
+        .collect()
+}
+
+fn json_claim_value(value: &serde_json::Value) -> Option<ClaimValue> {
+    match value {
+        serde_json::Value::Array(values) => {
+            Some(ClaimValue::Array(values.iter().filter_map(json_plain_value).collect()))
+        }
+        value => json_plain_value(value).map(ClaimValue::PlainValue),
+    }
+}
+
+fn json_plain_value(value: &serde_json::Value) -> Option<ClaimPlainValue> {
+    match value {
+        serde_json::Value::String(v) => Some(ClaimPlainValue::String(v.clone().into())),
+        serde_json::Value::Bool(v) => Some(ClaimPlainValue::Bool(*v)),
+        serde_json::Value::Number(v) => match v.as_i64() {
+            Some(v) => Some(ClaimPlainValue::Int(v)),
+            None => v.as_f64().map(ClaimPlainValue::Float),
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+use http::{
+    header::{CACHE_CONTROL, LOCATION},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use jsonwebtoken::Validation;
+
+use crate::{
+    core::{http::AuthResponse, nonce::NonceStore, session::SessionStore},
+    jwt::JwtKeyRing,
+};
+
+pub const BACK_CHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+pub struct EndSessionRequest {
+    pub end_session_endpoint: String,
+    pub id_token_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub post_logout_redirect_uri: Option<String>,
+    pub state: Option<String>,
+}
+
+impl EndSessionRequest {
+    pub fn url(&self) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        let params = [
+            ("id_token_hint", &self.id_token_hint),
+            ("client_id", &self.client_id),
+            ("post_logout_redirect_uri", &self.post_logout_redirect_uri),
+            ("state", &self.state),
+        ];
+        for (name, value) in params {
+            if let Some(value) = value {
+                query.append_pair(name, value);
+            }
+        }
+
+        let query = query.finish();
+        if query.is_empty() {
+            return self.end_session_endpoint.clone();
+        }
+
+        let separator = if self.end_session_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{separator}{query}", self.end_session_endpoint)
+    }
+
+    pub fn redirect(&self) -> Result<AuthResponse, http::header::InvalidHeaderValue> {
+        Ok(AuthResponse {
+            status_code: StatusCode::FOUND,
+            headers: HeaderMap::from_iter([(LOCATION, HeaderValue::try_from(self.url())?)]),
+            body: Vec::new(),
+        })
+    }
+}
+
+/// The [`Session::provider_session_id`](crate::core::session::Session::provider_session_id) of a session signed
+/// in through `issuer`. Back-channel logout only removes sessions carrying the issuer's key, as session ids and
+/// subjects are only unique per provider.
+pub fn provider_session_key(issuer: &str, session_id: &str) -> String {
+    // Issuers can't contain a fragment, so `#` separates the two unambiguously.
+    format!("{issuer}#{session_id}")
+}
+
+#[derive(Debug, Clone)]
+pub struct LogoutToken {
+    pub issuer: String,
+    pub token_id: String,
+    pub subject: Option<String>,
+    pub session_id: Option<String>,
+    pub expires_at: SystemTime,
+}
+
+pub struct BackChannelLogoutHandler {
+    pub validation_opt: Validation,
+    pub keys: JwtKeyRing,
+    pub session_store: Arc<dyn SessionStore>,
+    /// Records the `jti` of processed logout tokens until they expire, rejecting replays.
+    pub nonce_store: Arc<dyn NonceStore>,
+}
+
+impl BackChannelLogoutHandler {
+    pub fn logout_token_from_form(body: &[u8]) -> Option<String> {
+        form_urlencoded::parse(body)
+            .find(|(name, _)| name == "logout_token")
+            .map(|(_, value)| value.into_owned())
+    }
+
+    pub fn validate_logout_token(&self, logout_token: &str) -> Result<LogoutToken, anyhow::Error> {
+        let mut claims = self
+            .keys
+            .decode::<HashMap<String, serde_json::Value>>(logout_token, &self.validation_opt)?
+            .claims;
+
+        if claims.contains_key("nonce") {
+            bail!("Logout token must not contain a nonce claim");
+        }
+
+        if !claims.contains_key("iat") {
+            bail!("Logout token doesn't contain an iat claim");
+        }
+
+        let expires_at = claims
+            .get("exp")
+            .and_then(serde_json::Value::as_u64)
+            .map(|exp| UNIX_EPOCH + Duration::from_secs(exp))
+            .ok_or_else(|| anyhow!("Logout token doesn't contain an exp claim"))?;
+
+        let has_logout_event = claims
+            .get("events")
+            .and_then(|events| events.as_object())
+            .map(|events| events.contains_key(BACK_CHANNEL_LOGOUT_EVENT))
+            .unwrap_or(false);
+        if !has_logout_event {
+            bail!("Logout token doesn't contain the back-channel logout event");
+        }
+
+        let mut take_string = |name: &str| match claims.remove(name) {
+            Some(serde_json::Value::String(value)) => Some(value),
+            _ => None,
+        };
+
+        let issuer = take_string("iss").ok_or_else(|| anyhow!("Logout token doesn't contain an iss claim"))?;
+        let token_id = take_string("jti").ok_or_else(|| anyhow!("Logout token doesn't contain a jti claim"))?;
+        let subject = take_string("sub");
+        let session_id = take_string("sid");
+        if subject.is_none() && session_id.is_none() {
+            bail!("Logout token must contain a sub or sid claim");
+        }
+
+        Ok(LogoutToken {
+            issuer,
+            token_id,
+            subject,
+            session_id,
+            expires_at,
+        })
+    }
+
+    pub async fn logout(&self, logout_token: &str) -> AuthResponse {
+        // Invalid tokens are the provider's fault, store failures ours, so the provider may retry those.
+        let status_code = match self.validate_logout_token(logout_token) {
+            Ok(token) => match self.use_token_id(&token).await {
+                Ok(true) => match self.remove_sessions(&token).await {
+                    Ok(()) => StatusCode::OK,
+                    Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                },
+                Ok(false) => StatusCode::BAD_REQUEST,
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Err(_) => StatusCode::BAD_REQUEST,
+        };
+
+        AuthResponse {
+            status_code,
+            headers: HeaderMap::from_iter([(CACHE_CONTROL, HeaderValue::from_static("no-store"))]),
+            body: Vec::new(),
+        }
+    }
+
+    async fn use_token_id(&self, token: &LogoutToken) -> Result<bool, anyhow::Error> {
+        self.nonce_store
+            .try_use(&format!("logout:{}:{}", token.issuer, token.token_id), token.expires_at)
+            .await
+    }
+
+    async fn remove_sessions(&self, token: &LogoutToken) -> Result<(), anyhow::Error> {
+        match (&token.session_id, &token.subject) {
+            (Some(session_id), _) => {
+                let key = provider_session_key(&token.issuer, session_id);
+                self.session_store.remove_by_provider_session(&key).await
+            }
+            (None, Some(subject)) => {
+                // Subjects are only unique per provider too, so only the issuer's sessions of the subject go.
+                let prefix = provider_session_key(&token.issuer, "");
+                for session in self.session_store.load_by_subject(subject).await? {
+                    let is_issuers = session
+                        .provider_session_id
+                        .as_deref()
+                        .is_some_and(|key| key.starts_with(&prefix));
+                    if is_issuers {
+                        self.session_store.remove(&session.id).await?;
+                    }
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+
+    use super::*;
+    use crate::core::{
+        nonce::InMemoryNonceStore,
+        principal::UserPrincipal,
+        session::{InMemorySessionStore, Session},
+    };
+
+    const SECRET: &[u8] = b"logout secret";
+
+    fn logout_token(issuer: &str, token_id: &str, session_id: &str) -> String {
+        let claims = serde_json::json!({
+            "iss": issuer,
+            "jti": token_id,
+            "sid": session_id,
+            "iat": 1700000000u64,
+            "exp": 4102444800u64,
+            "events": { BACK_CHANNEL_LOGOUT_EVENT: {} },
+        });
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap()
+    }
+
+    fn session(id: &str, provider_session_id: String) -> Session {
+        let now = SystemTime::now();
+        Session {
+            id: id.to_owned(),
+            subject: "alice".to_owned(),
+            provider_session_id: Some(provider_session_id),
+            principal: UserPrincipal {
+                claims: Default::default(),
+            },
+            created_at: now,
+            validated_at: now,
+            expires_at: None,
+            is_persistent: false,
+        }
+    }
+
+    fn handler(session_store: Arc<InMemorySessionStore>) -> BackChannelLogoutHandler {
+        let mut validation_opt = Validation::new(Algorithm::HS256);
+        validation_opt.validate_aud = false;
+        BackChannelLogoutHandler {
+            validation_opt,
+            keys: JwtKeyRing::from(DecodingKey::from_secret(SECRET)),
+            session_store,
+            nonce_store: Arc::new(InMemoryNonceStore::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn session_id_is_scoped_to_issuer() {
+        let store = Arc::new(InMemorySessionStore::new());
+        let provider_a = provider_session_key("https://a.example", "sid-1");
+        let provider_b = provider_session_key("https://b.example", "sid-1");
+        store.store(session("local-a", provider_a)).await.unwrap();
+        store.store(session("local-b", provider_b)).await.unwrap();
+
+        let response = handler(store.clone())
+            .logout(&logout_token("https://a.example", "jti-1", "sid-1"))
+            .await;
+
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert!(store.load("local-a").await.unwrap().is_none());
+        assert!(store.load("local-b").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn replayed_logout_token_is_rejected() {
+        let handler = handler(Arc::new(InMemorySessionStore::new()));
+        let token = logout_token("https://a.example", "jti-1", "sid-1");
+
+        assert_eq!(handler.logout(&token).await.status_code, StatusCode::OK);
+        assert_eq!(handler.logout(&token).await.status_code, StatusCode::BAD_REQUEST);
+    }
+}
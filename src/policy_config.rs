@@ -0,0 +1,220 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures::future::join_all;
+use serde::Deserialize;
+
+use crate::core::authorization::{
+    AuthorizationHandlerContext, AuthorizationPolicyBuilder, AuthorizationRequirement, AuthorizationService,
+    AuthorizationServiceBuilder, PolicyRequirement,
+};
+
+/// Policies in a config file:
+///
+/// ```json
+/// { "policies": { "Admins": { "roles": ["admin"] }, "EuAdmins": { "policies": ["Admins"], "claims": { "region": ["eu"] } } } }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PolicyConfigFile {
+    #[serde(default)]
+    pub policies: HashMap<String, PolicyConfig>,
+}
+
+/// Every listed condition must hold; a policy without conditions only requires an authenticated user.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PolicyConfig {
+    /// The user must be in at least one of the roles.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// The user must have each claim, with one of the listed values when any are listed.
+    #[serde(default)]
+    pub claims: HashMap<String, Vec<String>>,
+    /// Other policies, from code or from the same file, that must also be satisfied.
+    #[serde(default)]
+    pub policies: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PolicyConfigError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    UnresolvedPolicies(Vec<String>),
+}
+
+impl Display for PolicyConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyConfigError::Io(err) => write!(f, "Failed to read the policy file: {err}"),
+            PolicyConfigError::Parse(err) => write!(f, "Failed to parse the policy file: {err}"),
+            PolicyConfigError::UnresolvedPolicies(policies) => write!(
+                f,
+                "Policies {} reference unknown policies or each other in a cycle",
+                policies.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyConfigError {}
+
+#[derive(Clone)]
+struct ConfiguredRequirement {
+    name: String,
+    roles: Vec<String>,
+    claims: HashMap<String, Vec<String>>,
+    policies: Vec<PolicyRequirement>,
+}
+
+impl AuthorizationRequirement for ConfiguredRequirement {
+    type AuthorizeFut<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("Configured({})", self.name))
+    }
+
+    fn register_pending(&self, context: &AuthorizationHandlerContext<'_>) {
+        context.add_pending_requirement(self.name());
+        for policy in &self.policies {
+            policy.register_pending(context);
+        }
+    }
+
+    fn authorize<'a>(&'a self, context: &'a AuthorizationHandlerContext<'a>) -> Self::AuthorizeFut<'a> {
+        let principal = context.principal();
+        let in_role = self.roles.is_empty() || self.roles.iter().any(|role| principal.is_in_role(role));
+        let has_claims = self.claims.iter().all(|(claim_type, values)| {
+            principal.claim(claim_type).is_some_and(|claim| {
+                values.is_empty()
+                    || claim
+                        .iter()
+                        .any(|value| value.as_str().is_some_and(|value| values.iter().any(|v| v == value)))
+            })
+        });
+        if in_role && has_claims {
+            context.succeed(&self.name());
+        }
+
+        Box::pin(async move {
+            join_all(self.policies.iter().map(|policy| policy.authorize(context))).await;
+        })
+    }
+}
+
+impl AuthorizationServiceBuilder {
+    /// Adds the file's policies next to the ones added in code. File policies replace code policies with the
+    /// same name.
+    pub fn add_policy_config(mut self, config: &PolicyConfigFile) -> Result<Self, PolicyConfigError> {
+        let mut pending = config.policies.iter().collect::<Vec<_>>();
+        pending.sort_by_key(|(name, _)| *name);
+
+        // Policies may reference each other, so they are added once everything they reference exists.
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(name, policy)| {
+                policy
+                    .policies
+                    .iter()
+                    .all(|dependency| dependency != *name && self.has_policy(dependency))
+            });
+            if ready.is_empty() {
+                return Err(PolicyConfigError::UnresolvedPolicies(
+                    waiting.into_iter().map(|(name, _)| name.clone()).collect(),
+                ));
+            }
+
+            for (name, policy) in ready {
+                let requirement = ConfiguredRequirement {
+                    name: name.clone(),
+                    roles: policy.roles.clone(),
+                    claims: policy.claims.clone(),
+                    policies: policy
+                        .policies
+                        .iter()
+                        .map(|dependency| self.policy(dependency))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| PolicyConfigError::UnresolvedPolicies(vec![name.clone()]))?,
+                };
+                self = self.add_policy(
+                    name.clone(),
+                    AuthorizationPolicyBuilder::new().add_requirement(requirement),
+                );
+            }
+            pending = waiting;
+        }
+
+        Ok(self)
+    }
+}
+
+/// Keeps an [`AuthorizationService`] in sync with a JSON policy file on top of the policies defined in code.
+pub struct PolicyFileReloader {
+    path: PathBuf,
+    base: AuthorizationServiceBuilder,
+    service: Arc<AuthorizationService>,
+}
+
+impl PolicyFileReloader {
+    /// `base` holds the policies defined in code, which every reload starts from.
+    pub fn new(
+        path: impl Into<PathBuf>,
+        base: AuthorizationServiceBuilder,
+        service: Arc<AuthorizationService>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            base,
+            service,
+        }
+    }
+
+    /// Loads the file and replaces the service's policies. On error the current policies stay in place.
+    pub async fn reload(&self) -> Result<(), PolicyConfigError> {
+        let path = self.path.clone();
+        let content = tokio::task::spawn_blocking(move || std::fs::read(path))
+            .await
+            .map_err(|err| PolicyConfigError::Io(std::io::Error::other(err)))?
+            .map_err(PolicyConfigError::Io)?;
+        let config = serde_json::from_slice::<PolicyConfigFile>(&content).map_err(PolicyConfigError::Parse)?;
+
+        self.service
+            .replace_policies(self.base.clone().add_policy_config(&config)?);
+        Ok(())
+    }
+
+    /// Reloads whenever the file's modification time changes, checking every `interval`. Invalid versions of
+    /// the file are skipped, so a half-written edit doesn't drop the policies.
+    pub fn watch(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut last_modified = None;
+            loop {
+                let modified = modified_at(&self.path).await;
+                if modified.is_some() && modified != last_modified {
+                    match self.reload().await {
+                        Ok(()) => last_modified = modified,
+                        #[cfg(feature = "tracing")]
+                        Err(err) => tracing::warn!(path = %self.path.display(), "{err}"),
+                        #[cfg(not(feature = "tracing"))]
+                        Err(_) => {}
+                    }
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .await
+        .ok()
+        .flatten()
+}
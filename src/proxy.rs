@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use http::{
+    header::{CONNECTION, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE},
+    uri::{Authority, PathAndQuery, Scheme},
+    HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode, Uri,
+};
+use hyper::{client::HttpConnector, Body, Client};
+
+use crate::core::{
+    authentication::{AuthenticationService, CompoundAuthenticationHandler, SuccessAuthenticationResult},
+    authorization_map::AuthorizationMap,
+    grpc::rpc_auth_response,
+    http::{AuthResponse, PeerAddress, ResponseHead},
+    principal::claim_types,
+    response_headers::{merge_response_headers, PendingResponseHeaders},
+};
+
+pub const X_FORWARDED_USER: HeaderName = HeaderName::from_static("x-forwarded-user");
+pub const X_FORWARDED_EMAIL: HeaderName = HeaderName::from_static("x-forwarded-email");
+pub const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+pub const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+const KEEP_ALIVE: HeaderName = HeaderName::from_static("keep-alive");
+
+/// Headers that only apply to a single connection, so they're never passed through.
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    CONNECTION,
+    KEEP_ALIVE,
+    PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+];
+
+/// A header telling the upstream who the user is, see [`AuthProxy::identity_header`].
+pub trait IdentityHeader: Send + Sync + 'static {
+    /// `None` leaves the header out.
+    fn value(&self, authentication: &SuccessAuthenticationResult) -> Option<HeaderValue>;
+}
+
+impl<F> IdentityHeader for F
+where
+    F: Fn(&SuccessAuthenticationResult) -> Option<HeaderValue> + Send + Sync + 'static,
+{
+    fn value(&self, authentication: &SuccessAuthenticationResult) -> Option<HeaderValue> {
+        self(authentication)
+    }
+}
+
+/// The first value of a claim of the user.
+pub struct ClaimHeader(pub String);
+
+impl IdentityHeader for ClaimHeader {
+    fn value(&self, authentication: &SuccessAuthenticationResult) -> Option<HeaderValue> {
+        let value = authentication.principal.claim_strs(&self.0).next()?;
+        HeaderValue::try_from(value).ok()
+    }
+}
+
+/// An authenticating reverse proxy in front of an application that doesn't authenticate itself, like
+/// oauth2-proxy. Requests are authenticated and checked against the [`AuthorizationMap`]; refused requests get the
+/// challenge or forbid response, and the others are sent to the upstream with headers describing the user.
+///
+/// Identity headers, [`X_FORWARDED_USER`] from the `sub` claim and [`X_FORWARDED_EMAIL`] by default, are always
+/// removed from incoming requests so clients can't set them, including the defaults and any header once
+/// configured but since removed with [`without_identity_header`](Self::without_identity_header). Headers added with
+/// [`strip_header`](Self::strip_header), e.g. the cookie of the proxy's own session, are used for authentication
+/// and then kept from the upstream. Upstream failures are answered with `502 Bad Gateway`.
+pub struct AuthProxy<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    service: Arc<AuthenticationService<Handler>>,
+    map: Arc<AuthorizationMap<Handler>>,
+    upstream_scheme: Scheme,
+    upstream_authority: Authority,
+    client: Client<HttpConnector>,
+    strip_headers: Vec<HeaderName>,
+    identity_headers: Vec<(HeaderName, Arc<dyn IdentityHeader>)>,
+    /// Every identity header name ever configured; only grows.
+    stripped_identity_headers: Vec<HeaderName>,
+}
+
+impl<Handler> AuthProxy<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    /// Forwards to `upstream`, e.g. `http://127.0.0.1:8080`, keeping the path and query of requests.
+    pub fn new(
+        service: Arc<AuthenticationService<Handler>>,
+        map: Arc<AuthorizationMap<Handler>>,
+        upstream: Uri,
+    ) -> Result<Self, anyhow::Error> {
+        let parts = upstream.into_parts();
+        let (Some(upstream_scheme), Some(upstream_authority)) = (parts.scheme, parts.authority) else {
+            anyhow::bail!("Upstream URI needs a scheme and an authority");
+        };
+
+        Ok(Self {
+            service,
+            map,
+            upstream_scheme,
+            upstream_authority,
+            client: Client::new(),
+            strip_headers: Vec::new(),
+            identity_headers: vec![
+                (X_FORWARDED_USER, Arc::new(ClaimHeader(claim_types::SUBJECT.to_owned()))),
+                (X_FORWARDED_EMAIL, Arc::new(ClaimHeader(claim_types::EMAIL.to_owned()))),
+            ],
+            stripped_identity_headers: vec![X_FORWARDED_USER, X_FORWARDED_EMAIL],
+        })
+    }
+
+    pub fn client(self, client: Client<HttpConnector>) -> Self {
+        Self { client, ..self }
+    }
+
+    pub fn strip_header(mut self, header: HeaderName) -> Self {
+        self.strip_headers.push(header);
+        self
+    }
+
+    /// Sends `header` to the upstream, replacing the default header of the same name.
+    pub fn identity_header(mut self, header: HeaderName, value: impl IdentityHeader) -> Self {
+        self.identity_headers.retain(|(name, _)| *name != header);
+        if !self.stripped_identity_headers.contains(&header) {
+            self.stripped_identity_headers.push(header.clone());
+        }
+        self.identity_headers.push((header, Arc::new(value)));
+        self
+    }
+
+    /// Stops sending an identity header; it's still removed from incoming requests.
+    pub fn without_identity_header(mut self, header: &HeaderName) -> Self {
+        self.identity_headers.retain(|(name, _)| name != header);
+        self
+    }
+
+    pub async fn handle(&self, mut request: Request<Body>) -> Response<Body> {
+        for header in &self.stripped_identity_headers {
+            request.headers_mut().remove(header);
+        }
+
+        self.service.handle_request(&mut request).await;
+        let result = self.map.authorize(&mut request).await;
+
+        let authentication = request.extensions().get::<SuccessAuthenticationResult>().cloned();
+        let success_headers = self.service.success_response_headers(&request);
+        let pending_headers = request.extensions().get::<PendingResponseHeaders>().cloned();
+
+        let mut response = match result {
+            Ok(()) => self.forward(request, authentication.as_ref()).await,
+            Err(response) => into_response(rpc_auth_response(&request, response)),
+        };
+        if let Some(success_headers) = success_headers {
+            merge_response_headers(response.headers_mut(), success_headers);
+        }
+        if let Some(pending_headers) = pending_headers {
+            for (name, value) in pending_headers.take().iter() {
+                response.headers_mut().append(name, value.clone());
+            }
+        }
+
+        if let Some(hook) = self.service.response_hook() {
+            let mut head = ResponseHead {
+                status_code: response.status(),
+                headers: std::mem::take(response.headers_mut()),
+            };
+            hook.on_response(authentication.as_ref(), &mut head).await;
+            *response.status_mut() = head.status_code;
+            *response.headers_mut() = head.headers;
+        }
+
+        response
+    }
+
+    async fn forward(
+        &self,
+        request: Request<Body>,
+        authentication: Option<&SuccessAuthenticationResult>,
+    ) -> Response<Body> {
+        let Ok(request) = self.upstream_request(request, authentication) else {
+            return status_response(StatusCode::BAD_REQUEST);
+        };
+
+        match self.client.request(request).await {
+            Ok(mut response) => {
+                remove_hop_by_hop_headers(response.headers_mut());
+                response
+            }
+            Err(_) => status_response(StatusCode::BAD_GATEWAY),
+        }
+    }
+
+    fn upstream_request(
+        &self,
+        mut request: Request<Body>,
+        authentication: Option<&SuccessAuthenticationResult>,
+    ) -> Result<Request<Body>, http::Error> {
+        let path_and_query = request
+            .uri()
+            .path_and_query()
+            .cloned()
+            .unwrap_or_else(|| PathAndQuery::from_static("/"));
+        *request.uri_mut() = Uri::builder()
+            .scheme(self.upstream_scheme.clone())
+            .authority(self.upstream_authority.clone())
+            .path_and_query(path_and_query)
+            .build()?;
+
+        let peer_address = request.extensions().get::<PeerAddress>().map(|address| address.0.ip());
+        let headers = request.headers_mut();
+        remove_hop_by_hop_headers(headers);
+        for header in &self.strip_headers {
+            headers.remove(header);
+        }
+        if let Some(host) = headers.remove(HOST) {
+            headers.insert(X_FORWARDED_HOST, host);
+        }
+        if let Some(peer_address) = peer_address {
+            headers.append(X_FORWARDED_FOR, HeaderValue::from_str(&peer_address.to_string())?);
+        }
+        if let Some(authentication) = authentication {
+            for (name, header) in &self.identity_headers {
+                if let Some(value) = header.value(authentication) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    for header in HOP_BY_HOP_HEADERS {
+        headers.remove(header);
+    }
+}
+
+fn status_response(status_code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status_code;
+    response
+}
+
+fn into_response(auth_response: AuthResponse) -> Response<Body> {
+    let mut response = Response::new(Body::from(auth_response.body));
+    *response.status_mut() = auth_response.status_code;
+    *response.headers_mut() = auth_response.headers;
+    response
+}
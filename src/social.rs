@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::{
+    core::principal::claim_types,
+    correlation::CorrelationCookies,
+    oauth_login::{json_claims_mapper, ClaimsMapper, OAuth2LoginHandler, OAuth2LoginOptions, UserInfoRequest},
+};
+
+pub struct Google;
+
+impl Google {
+    pub fn options(client_id: String, client_secret: String) -> OAuth2LoginOptions {
+        OAuth2LoginOptions {
+            client_id,
+            client_secret,
+            authorization_endpoint: "https://accounts.google.com/o/oauth2/v2/auth".to_owned(),
+            token_endpoint: "https://oauth2.googleapis.com/token".to_owned(),
+            user_info: UserInfoRequest::new("https://openidconnect.googleapis.com/v1/userinfo".to_owned()),
+            callback_path: "/signin-google".to_owned(),
+            public_origin: None,
+            scopes: scopes(&["openid", "profile", "email"]),
+            claims_mapper: mappings(&[
+                ("sub", claim_types::SUBJECT),
+                ("name", claim_types::NAME),
+                ("email", claim_types::EMAIL),
+                ("email_verified", "email_verified"),
+            ]),
+        }
+    }
+
+    pub fn handler(client_id: String, client_secret: String, correlation: CorrelationCookies) -> OAuth2LoginHandler {
+        OAuth2LoginHandler::new(Self::options(client_id, client_secret), correlation)
+    }
+}
+
+pub struct GitHub;
+
+impl GitHub {
+    pub fn options(client_id: String, client_secret: String) -> OAuth2LoginOptions {
+        OAuth2LoginOptions {
+            client_id,
+            client_secret,
+            authorization_endpoint: "https://github.com/login/oauth/authorize".to_owned(),
+            token_endpoint: "https://github.com/login/oauth/access_token".to_owned(),
+            user_info: UserInfoRequest::new("https://api.github.com/user".to_owned()),
+            callback_path: "/signin-github".to_owned(),
+            public_origin: None,
+            scopes: scopes(&["read:user", "user:email"]),
+            claims_mapper: mappings(&[
+                ("id", claim_types::SUBJECT),
+                ("login", claim_types::NAME),
+                ("email", claim_types::EMAIL),
+            ]),
+        }
+    }
+
+    pub fn handler(client_id: String, client_secret: String, correlation: CorrelationCookies) -> OAuth2LoginHandler {
+        OAuth2LoginHandler::new(Self::options(client_id, client_secret), correlation)
+    }
+}
+
+pub struct Microsoft;
+
+impl Microsoft {
+    pub fn options(client_id: String, client_secret: String, tenant: &str) -> OAuth2LoginOptions {
+        OAuth2LoginOptions {
+            client_id,
+            client_secret,
+            authorization_endpoint: format!("https://login.microsoftonline.com/{tenant}/oauth2/v2.0/authorize"),
+            token_endpoint: format!("https://login.microsoftonline.com/{tenant}/oauth2/v2.0/token"),
+            user_info: UserInfoRequest::new("https://graph.microsoft.com/oidc/userinfo".to_owned()),
+            callback_path: "/signin-microsoft".to_owned(),
+            public_origin: None,
+            scopes: scopes(&["openid", "profile", "email"]),
+            claims_mapper: mappings(&[
+                ("sub", claim_types::SUBJECT),
+                ("name", claim_types::NAME),
+                ("email", claim_types::EMAIL),
+            ]),
+        }
+    }
+
+    pub fn handler(client_id: String, client_secret: String, correlation: CorrelationCookies) -> OAuth2LoginHandler {
+        OAuth2LoginHandler::new(Self::options(client_id, client_secret, "common"), correlation)
+    }
+}
+
+fn scopes(scopes: &[&str]) -> Vec<String> {
+    scopes.iter().map(|s| (*s).to_owned()).collect()
+}
+
+fn mappings(mappings: &[(&str, &str)]) -> ClaimsMapper {
+    json_claims_mapper(
+        mappings
+            .iter()
+            .map(|(key, claim_type)| ((*key).to_owned(), (*claim_type).to_owned()))
+            .collect::<HashMap<_, _>>(),
+    )
+}
@@ -0,0 +1,198 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{header::AUTHORIZATION, HeaderValue, Request};
+use tokio::task::futures::TaskLocalFuture;
+use tower::{BoxError, Layer, Service};
+
+use crate::core::{
+    authentication::{BearerToken, SuccessAuthenticationResult},
+    http::host_matches,
+};
+#[cfg(feature = "oauth")]
+use crate::oauth::TokenExchangeClient;
+
+tokio::task_local! {
+    static INBOUND_TOKEN: Option<BearerToken>;
+}
+
+/// The bearer token of the inbound request handled by the current task, see [`InboundTokenLayer`].
+pub fn inbound_token() -> Option<BearerToken> {
+    INBOUND_TOKEN.try_with(Clone::clone).ok().flatten()
+}
+
+/// Runs the inner service with the request's [`BearerToken`] as [`inbound_token`]. Goes inside the
+/// authentication layer; with lazy authentication only requests authenticated before this layer have a token.
+/// Work spawned onto other tasks doesn't see the token and has to take it along.
+#[derive(Debug, Clone, Default)]
+pub struct InboundTokenLayer;
+
+impl<S> Layer<S> for InboundTokenLayer {
+    type Service = InboundToken<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InboundToken { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InboundToken<S> {
+    inner: S,
+}
+
+impl<S, Body> Service<Request<Body>> for InboundToken<S>
+where
+    S: Service<Request<Body>>,
+{
+    type Response = S::Response;
+
+    type Error = S::Error;
+
+    type Future = TaskLocalFuture<Option<BearerToken>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let token = req
+            .extensions()
+            .get::<SuccessAuthenticationResult>()
+            .and(req.extensions().get::<BearerToken>())
+            .cloned();
+        INBOUND_TOKEN.scope(token, self.inner.call(req))
+    }
+}
+
+/// What's sent to a destination in place of the inbound token.
+#[derive(Clone)]
+pub enum ForwardedCredential {
+    /// The inbound token itself.
+    Inbound,
+    /// A token for `audience` from an OAuth 2.0 token exchange.
+    #[cfg(feature = "oauth")]
+    Exchange {
+        client: Arc<TokenExchangeClient>,
+        audience: String,
+    },
+}
+
+#[derive(Clone)]
+struct ForwardingRule {
+    host: String,
+    credential: ForwardedCredential,
+}
+
+/// Adds the inbound token to outgoing requests whose host has a rule, the first matching rule deciding what's
+/// sent. Requests to other hosts, requests that already have an `Authorization` header, and requests made without
+/// an inbound token are sent as they are, so tokens only reach the services they're meant for. The token is taken
+/// from the outgoing request's [`BearerToken`] extension when it has one, and from [`inbound_token`] otherwise.
+#[derive(Clone, Default)]
+pub struct TokenForwardingLayer {
+    rules: Arc<Vec<ForwardingRule>>,
+}
+
+impl TokenForwardingLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forwards the inbound token to `host`, e.g. `api.example.com`, or to its subdomains with `*.example.com`.
+    pub fn forward_to(self, host: impl Into<String>) -> Self {
+        self.rule(host.into(), ForwardedCredential::Inbound)
+    }
+
+    /// Sends a token exchanged for `audience` to `host` instead of the inbound token.
+    #[cfg(feature = "oauth")]
+    pub fn exchange_for(
+        self,
+        host: impl Into<String>,
+        client: Arc<TokenExchangeClient>,
+        audience: impl Into<String>,
+    ) -> Self {
+        self.rule(
+            host.into(),
+            ForwardedCredential::Exchange {
+                client,
+                audience: audience.into(),
+            },
+        )
+    }
+
+    fn rule(mut self, host: String, credential: ForwardedCredential) -> Self {
+        Arc::make_mut(&mut self.rules).push(ForwardingRule { host, credential });
+        self
+    }
+}
+
+impl<S> Layer<S> for TokenForwardingLayer {
+    type Service = TokenForwarding<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TokenForwarding {
+            inner,
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TokenForwarding<S> {
+    inner: S,
+    rules: Arc<Vec<ForwardingRule>>,
+}
+
+impl<S, Body> Service<Request<Body>> for TokenForwarding<S>
+where
+    S: Service<Request<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    Body: Send + 'static,
+{
+    type Response = S::Response;
+
+    type Error = BoxError;
+
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        // The service that was polled ready has to handle the request, so a clone is left for the next one.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let credential = req
+            .uri()
+            .host()
+            .filter(|_| !req.headers().contains_key(AUTHORIZATION))
+            .and_then(|host| self.rules.iter().find(|rule| host_matches(&rule.host, host)))
+            .map(|rule| rule.credential.clone());
+        let token = credential
+            .as_ref()
+            .and_then(|_| req.extensions().get::<BearerToken>().cloned().or_else(inbound_token));
+
+        Box::pin(async move {
+            if let (Some(credential), Some(token)) = (credential, token) {
+                let token = match credential {
+                    ForwardedCredential::Inbound => token.0.to_string(),
+                    #[cfg(feature = "oauth")]
+                    ForwardedCredential::Exchange { client, audience } => {
+                        client.exchange(token.as_str(), &audience).await?.access_token
+                    }
+                };
+                let mut header = HeaderValue::try_from(format!("Bearer {token}"))?;
+                header.set_sensitive(true);
+                req.headers_mut().insert(AUTHORIZATION, header);
+            }
+
+            inner.call(req).await.map_err(Into::into)
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::core::nonce::NonceStore;
+
+pub mod token_purposes {
+    pub const CONFIRM_EMAIL: &str = "confirm-email";
+    pub const RESET_PASSWORD: &str = "reset-password";
+    pub const MAGIC_LINK: &str = "magic-link";
+}
+
+#[async_trait]
+pub trait TokenProvider: Send + Sync + 'static {
+    async fn generate(&self, purpose: &str, subject: &str) -> Result<String, anyhow::Error>;
+
+    async fn validate(&self, purpose: &str, token: &str) -> Result<String, anyhow::Error>;
+}
+
+pub struct HmacTokenProvider {
+    pub secret: Vec<u8>,
+    pub default_lifetime: Duration,
+    pub lifetimes: HashMap<String, Duration>,
+    pub nonce_store: Arc<dyn NonceStore>,
+}
+
+impl HmacTokenProvider {
+    pub fn new(secret: Vec<u8>, nonce_store: Arc<dyn NonceStore>) -> Self {
+        Self {
+            secret,
+            default_lifetime: Duration::from_secs(24 * 60 * 60),
+            lifetimes: HashMap::from([
+                (token_purposes::RESET_PASSWORD.to_owned(), Duration::from_secs(60 * 60)),
+                (token_purposes::MAGIC_LINK.to_owned(), Duration::from_secs(15 * 60)),
+            ]),
+            nonce_store,
+        }
+    }
+
+    fn signature(&self, purpose: &str, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(purpose.as_bytes());
+        mac.update(b".");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[async_trait]
+impl TokenProvider for HmacTokenProvider {
+    async fn generate(&self, purpose: &str, subject: &str) -> Result<String, anyhow::Error> {
+        let mut nonce = [0u8; 16];
+        getrandom::getrandom(&mut nonce)?;
+
+        let lifetime = self.lifetimes.get(purpose).copied().unwrap_or(self.default_lifetime);
+        let expires_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_add(lifetime)
+            .as_secs();
+        let payload = format!(
+            "{}.{expires_at}.{}",
+            URL_SAFE_NO_PAD.encode(subject),
+            hex::encode(nonce)
+        );
+        let signature = self.signature(purpose, &payload).finalize().into_bytes();
+
+        Ok(format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    async fn validate(&self, purpose: &str, token: &str) -> Result<String, anyhow::Error> {
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(|| anyhow!("Malformed token"))?;
+        self.signature(purpose, payload)
+            .verify_slice(&URL_SAFE_NO_PAD.decode(signature)?)
+            .map_err(|_| anyhow!("Invalid token signature"))?;
+
+        let mut parts = payload.splitn(3, '.');
+        let (Some(subject), Some(expires_at), Some(nonce)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(anyhow!("Malformed token"));
+        };
+
+        let expires_at = UNIX_EPOCH + Duration::from_secs(expires_at.parse()?);
+        if expires_at <= SystemTime::now() {
+            return Err(anyhow!("Token has expired"));
+        }
+
+        if !self
+            .nonce_store
+            .try_use(&format!("{purpose}:{nonce}"), expires_at)
+            .await?
+        {
+            return Err(anyhow!("Token has already been used"));
+        }
+
+        Ok(String::from_utf8(URL_SAFE_NO_PAD.decode(subject)?)?)
+    }
+}
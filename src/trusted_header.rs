@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::{ready, Ready},
+    net::IpAddr,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use http::{HeaderMap, HeaderName, StatusCode};
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    credentials::constant_time_eq,
+    http::{AuthResponse, Request},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+#[cfg(feature = "jwt")]
+use crate::jwt::JwtBearerHandler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<Self, IpNetworkError> {
+        let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(IpNetworkError::InvalidPrefixLength(prefix_len));
+        }
+
+        Ok(Self { address, prefix_len })
+    }
+
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let address = match address {
+            IpAddr::V6(v6) if self.address.is_ipv4() => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+            address => address,
+        };
+
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => prefix_matches(
+                u128::from(u32::from(network)) << 96,
+                u128::from(u32::from(address)) << 96,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                prefix_matches(u128::from(network), u128::from(address), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = IpNetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (
+                address,
+                Some(
+                    prefix_len
+                        .parse()
+                        .map_err(|_| IpNetworkError::Malformed(s.to_owned()))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        let address = IpAddr::from_str(address).map_err(|_| IpNetworkError::Malformed(s.to_owned()))?;
+        let prefix_len = prefix_len.unwrap_or(if address.is_ipv4() { 32 } else { 128 });
+
+        Self::new(address, prefix_len)
+    }
+}
+
+fn prefix_matches(network: u128, address: u128, prefix_len: u8) -> bool {
+    let mask = u128::MAX.checked_shl(128 - u32::from(prefix_len)).unwrap_or(0);
+    network & mask == address & mask
+}
+
+#[derive(Debug)]
+pub enum IpNetworkError {
+    Malformed(String),
+    InvalidPrefixLength(u8),
+}
+
+impl Display for IpNetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpNetworkError::Malformed(network) => write!(f, "{network} is not an IP address or CIDR range"),
+            IpNetworkError::InvalidPrefixLength(prefix_len) => write!(f, "Prefix length {prefix_len} is too long"),
+        }
+    }
+}
+
+impl std::error::Error for IpNetworkError {}
+
+/// How the handler decides that the request really came through the authenticating proxy.
+pub enum TrustedPeer {
+    Networks(Vec<IpNetwork>),
+    SharedSecret { header: HeaderName, secret: String },
+}
+
+impl TrustedPeer {
+    fn is_trusted(&self, request: &impl Request) -> bool {
+        match self {
+            TrustedPeer::Networks(networks) => request
+                .get_peer_address()
+                .is_some_and(|peer| networks.iter().any(|network| network.contains(peer.ip()))),
+            TrustedPeer::SharedSecret { header, secret } => request
+                .get_header(header)
+                .is_some_and(|value| constant_time_eq(value.as_bytes(), secret.as_bytes())),
+        }
+    }
+}
+
+pub struct TrustedHeaderHandler {
+    pub trusted_peer: TrustedPeer,
+    pub user_header: HeaderName,
+    pub email_header: Option<HeaderName>,
+    pub groups_header: Option<HeaderName>,
+    pub group_separator: char,
+    pub groups_claim_type: String,
+    /// A signed identity assertion such as Cloudflare Access's `Cf-Access-Jwt-Assertion`. When configured,
+    /// the principal is built from the validated token instead of the plain headers.
+    #[cfg(feature = "jwt")]
+    pub jwt_assertion: Option<(HeaderName, JwtBearerHandler)>,
+}
+
+impl TrustedHeaderHandler {
+    pub fn new(trusted_peer: TrustedPeer) -> Self {
+        Self {
+            trusted_peer,
+            user_header: HeaderName::from_static("x-forwarded-user"),
+            email_header: Some(HeaderName::from_static("x-forwarded-email")),
+            groups_header: Some(HeaderName::from_static("x-forwarded-groups")),
+            group_separator: ',',
+            groups_claim_type: claim_types::ROLE.to_owned(),
+            #[cfg(feature = "jwt")]
+            jwt_assertion: None,
+        }
+    }
+
+    /// Headers set by oauth2-proxy with `--set-xauthrequest`.
+    pub fn oauth2_proxy(trusted_peer: TrustedPeer) -> Self {
+        Self {
+            user_header: HeaderName::from_static("x-auth-request-user"),
+            email_header: Some(HeaderName::from_static("x-auth-request-email")),
+            groups_header: Some(HeaderName::from_static("x-auth-request-groups")),
+            ..Self::new(trusted_peer)
+        }
+    }
+
+    /// Cloudflare Access. `jwt_handler` must validate the team domain's issuer, the application's AUD tag
+    /// and use the keys published at `https://<team>.cloudflareaccess.com/cdn-cgi/access/certs`.
+    #[cfg(feature = "jwt")]
+    pub fn cloudflare_access(trusted_peer: TrustedPeer, jwt_handler: JwtBearerHandler) -> Self {
+        Self {
+            email_header: Some(HeaderName::from_static("cf-access-authenticated-user-email")),
+            groups_header: None,
+            jwt_assertion: Some((HeaderName::from_static("cf-access-jwt-assertion"), jwt_handler)),
+            ..Self::new(trusted_peer)
+        }
+    }
+
+    fn has_identity_headers(&self, request: &impl Request) -> bool {
+        #[cfg(feature = "jwt")]
+        if let Some((header, _)) = &self.jwt_assertion {
+            return request.get_header(header).is_some();
+        }
+
+        request.get_header(&self.user_header).is_some()
+    }
+
+    fn principal(&self, request: &impl Request) -> Result<UserPrincipal, anyhow::Error> {
+        #[cfg(feature = "jwt")]
+        if let Some((header, jwt_handler)) = &self.jwt_assertion {
+            let token = header_str(request, header)?.ok_or_else(|| anyhow!("Identity assertion is missing"))?;
+            return jwt_handler.validate_token(token, request);
+        }
+
+        let user = header_str(request, &self.user_header)?
+            .filter(|user| !user.is_empty())
+            .ok_or_else(|| anyhow!("Trusted user header is empty"))?;
+        let mut claims = HashMap::from([(
+            claim_types::SUBJECT.into(),
+            ClaimValue::PlainValue(ClaimPlainValue::String(user.into())),
+        )]);
+
+        let email = self.optional_header(request, self.email_header.as_ref())?;
+        if let Some(email) = email.filter(|email| !email.is_empty()) {
+            claims.insert(
+                claim_types::EMAIL.into(),
+                ClaimValue::PlainValue(ClaimPlainValue::String(email.into())),
+            );
+        }
+
+        let groups = self
+            .optional_header(request, self.groups_header.as_ref())?
+            .unwrap_or_default()
+            .split(self.group_separator)
+            .map(str::trim)
+            .filter(|group| !group.is_empty())
+            .map(|group| ClaimPlainValue::String(group.into()))
+            .collect::<Vec<_>>();
+        if !groups.is_empty() {
+            claims.insert((&self.groups_claim_type).into(), ClaimValue::Array(groups));
+        }
+
+        Ok(UserPrincipal { claims })
+    }
+
+    fn optional_header<'a>(
+        &self,
+        request: &'a impl Request,
+        header: Option<&HeaderName>,
+    ) -> Result<Option<&'a str>, anyhow::Error> {
+        Ok(header.map(|h| header_str(request, h)).transpose()?.flatten())
+    }
+}
+
+fn header_str<'a>(request: &'a impl Request, header: &HeaderName) -> Result<Option<&'a str>, anyhow::Error> {
+    request
+        .get_header(header)
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| anyhow!("Header {header} is not valid ASCII"))
+        })
+        .transpose()
+}
+
+impl AuthenticationHandler for TrustedHeaderHandler {
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        if !self.has_identity_headers(request) {
+            return ready(Err(AuthenticationError::NoResult));
+        }
+
+        // Identity headers are trivially spoofable by anyone who can reach the app without the proxy.
+        if !self.trusted_peer.is_trusted(request) {
+            return ready(Err(AuthenticationError::Fail(AuthError::Other(
+                "Identity headers were sent by an untrusted peer".into(),
+            ))));
+        }
+
+        ready(self.principal(request).map_err(AuthenticationError::fail))
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
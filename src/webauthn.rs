@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use webauthn_rs::{
+    prelude::{
+        CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+        RegisterPublicKeyCredential, RequestChallengeResponse, Url, Uuid,
+    },
+    Webauthn, WebauthnBuilder,
+};
+
+use crate::core::{
+    authentication::{AuthenticationProperties, AuthenticationService, CompoundAuthenticationHandler, SchemeName},
+    http::AuthResponse,
+    principal::UserPrincipal,
+};
+
+#[async_trait]
+pub trait CredentialRepository: Send + Sync + 'static {
+    async fn credentials(&self, user_id: Uuid) -> Result<Vec<Passkey>, anyhow::Error>;
+
+    async fn add_credential(&self, user_id: Uuid, passkey: Passkey) -> Result<(), anyhow::Error>;
+
+    async fn update_credential(&self, user_id: Uuid, passkey: Passkey) -> Result<(), anyhow::Error>;
+
+    async fn principal(&self, user_id: Uuid) -> Result<Option<UserPrincipal>, anyhow::Error>;
+}
+
+pub struct PasskeyOptions {
+    pub rp_id: String,
+    pub rp_origin: String,
+    pub rp_name: String,
+    pub scheme: Option<SchemeName>,
+    pub ceremony_timeout: Duration,
+}
+
+impl PasskeyOptions {
+    pub fn new(rp_id: String, rp_origin: String) -> Self {
+        Self {
+            rp_name: rp_id.clone(),
+            rp_id,
+            rp_origin,
+            scheme: None,
+            ceremony_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+struct Ceremony<State> {
+    user_id: Uuid,
+    state: State,
+    expires_at: Instant,
+}
+
+pub struct PasskeyService<Handler: CompoundAuthenticationHandler> {
+    webauthn: Webauthn,
+    scheme: Option<SchemeName>,
+    ceremony_timeout: Duration,
+    repository: Arc<dyn CredentialRepository>,
+    auth_service: Arc<AuthenticationService<Handler>>,
+    registrations: Mutex<HashMap<String, Ceremony<PasskeyRegistration>>>,
+    authentications: Mutex<HashMap<String, Ceremony<PasskeyAuthentication>>>,
+}
+
+impl<Handler> PasskeyService<Handler>
+where
+    Handler: CompoundAuthenticationHandler,
+{
+    pub fn new(
+        options: PasskeyOptions,
+        repository: Arc<dyn CredentialRepository>,
+        auth_service: Arc<AuthenticationService<Handler>>,
+    ) -> Result<Self, anyhow::Error> {
+        let rp_origin = Url::parse(&options.rp_origin)?;
+        let webauthn = WebauthnBuilder::new(&options.rp_id, &rp_origin)?
+            .rp_name(&options.rp_name)
+            .timeout(options.ceremony_timeout)
+            .build()?;
+
+        Ok(Self {
+            webauthn,
+            scheme: options.scheme,
+            ceremony_timeout: options.ceremony_timeout,
+            repository,
+            auth_service,
+            registrations: Mutex::default(),
+            authentications: Mutex::default(),
+        })
+    }
+
+    pub async fn start_registration(
+        &self,
+        user_id: Uuid,
+        user_name: &str,
+        display_name: &str,
+    ) -> Result<(String, CreationChallengeResponse), anyhow::Error> {
+        let existing = self
+            .repository
+            .credentials(user_id)
+            .await?
+            .iter()
+            .map(|passkey| passkey.cred_id().clone())
+            .collect::<Vec<_>>();
+        let (challenge, state) = self.webauthn.start_passkey_registration(
+            user_id,
+            user_name,
+            display_name,
+            (!existing.is_empty()).then_some(existing),
+        )?;
+
+        let ceremony_id = self.store_ceremony(&self.registrations, user_id, state)?;
+        Ok((ceremony_id, challenge))
+    }
+
+    pub async fn finish_registration(
+        &self,
+        ceremony_id: &str,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<(), anyhow::Error> {
+        let ceremony = take_ceremony(&self.registrations, ceremony_id)?;
+        let passkey = self.webauthn.finish_passkey_registration(credential, &ceremony.state)?;
+        self.repository.add_credential(ceremony.user_id, passkey).await
+    }
+
+    pub async fn start_authentication(
+        &self,
+        user_id: Uuid,
+    ) -> Result<(String, RequestChallengeResponse), anyhow::Error> {
+        let passkeys = self.repository.credentials(user_id).await?;
+        let (challenge, state) = self.webauthn.start_passkey_authentication(&passkeys)?;
+
+        let ceremony_id = self.store_ceremony(&self.authentications, user_id, state)?;
+        Ok((ceremony_id, challenge))
+    }
+
+    pub async fn finish_authentication(
+        &self,
+        ceremony_id: &str,
+        credential: &PublicKeyCredential,
+        properties: &AuthenticationProperties,
+    ) -> Result<AuthResponse, anyhow::Error> {
+        let ceremony = take_ceremony(&self.authentications, ceremony_id)?;
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(credential, &ceremony.state)?;
+
+        if result.needs_update() {
+            for mut passkey in self.repository.credentials(ceremony.user_id).await? {
+                if passkey.update_credential(&result) == Some(true) {
+                    self.repository.update_credential(ceremony.user_id, passkey).await?;
+                }
+            }
+        }
+
+        let principal = self
+            .repository
+            .principal(ceremony.user_id)
+            .await?
+            .ok_or_else(|| anyhow!("User of the passkey no longer exists"))?;
+
+        Ok(self
+            .auth_service
+            .sign_in(self.scheme.as_ref().map(SchemeName::as_str), &principal, properties)
+            .await?)
+    }
+
+    fn store_ceremony<State>(
+        &self,
+        ceremonies: &Mutex<HashMap<String, Ceremony<State>>>,
+        user_id: Uuid,
+        state: State,
+    ) -> Result<String, anyhow::Error> {
+        let mut id = [0u8; 16];
+        getrandom::getrandom(&mut id)?;
+        let id = hex::encode(id);
+
+        let now = Instant::now();
+        let mut ceremonies = ceremonies.lock().unwrap_or_else(PoisonError::into_inner);
+        ceremonies.retain(|_, c| c.expires_at > now);
+        ceremonies.insert(
+            id.clone(),
+            Ceremony {
+                user_id,
+                state,
+                expires_at: now + self.ceremony_timeout,
+            },
+        );
+
+        Ok(id)
+    }
+}
+
+fn take_ceremony<State>(
+    ceremonies: &Mutex<HashMap<String, Ceremony<State>>>,
+    ceremony_id: &str,
+) -> Result<Ceremony<State>, anyhow::Error> {
+    let mut ceremonies = ceremonies.lock().unwrap_or_else(PoisonError::into_inner);
+    ceremonies
+        .remove(ceremony_id)
+        .filter(|c| c.expires_at > Instant::now())
+        .ok_or_else(|| anyhow!("WebAuthn ceremony is not found or expired"))
+}
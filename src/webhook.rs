@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+use hmac::{Hmac, Mac};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use sha2::Sha256;
+
+use crate::core::{
+    authentication::{AuthError, AuthenticationError, AuthenticationHandler, AuthenticationResult},
+    authorization::AuthorizationFailure,
+    http::{AuthResponse, Request, RequestBody, RequestExtensions},
+    principal::{claim_types, ClaimPlainValue, ClaimValue, UserPrincipal},
+};
+
+pub mod webhook_claims {
+    pub const SOURCE: &str = "webhook_source";
+}
+
+pub enum WebhookSignatureScheme {
+    /// `X-Hub-Signature-256: sha256=<hex hmac of the body>`.
+    GitHub,
+    /// `Stripe-Signature: t=<unix time>,v1=<hex hmac of "t.body">`, rejected when older than `tolerance`.
+    Stripe { tolerance: Duration },
+}
+
+impl WebhookSignatureScheme {
+    fn header(&self) -> HeaderName {
+        match self {
+            WebhookSignatureScheme::GitHub => HeaderName::from_static("x-hub-signature-256"),
+            WebhookSignatureScheme::Stripe { .. } => HeaderName::from_static("stripe-signature"),
+        }
+    }
+}
+
+pub struct WebhookSource {
+    pub name: String,
+    pub scheme: WebhookSignatureScheme,
+    /// Every secret is tried, so a new secret can be added before the sender switches to it.
+    pub secrets: Vec<Vec<u8>>,
+}
+
+impl WebhookSource {
+    pub fn github(name: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            scheme: WebhookSignatureScheme::GitHub,
+            secrets: vec![secret.into()],
+        }
+    }
+
+    pub fn stripe(name: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            scheme: WebhookSignatureScheme::Stripe {
+                tolerance: Duration::from_secs(5 * 60),
+            },
+            secrets: vec![secret.into()],
+        }
+    }
+
+    fn verify(&self, signature: &str, body: &[u8], now: SystemTime) -> Result<(), anyhow::Error> {
+        match &self.scheme {
+            WebhookSignatureScheme::GitHub => {
+                let signature = signature
+                    .strip_prefix("sha256=")
+                    .ok_or_else(|| anyhow!("Malformed GitHub signature"))?;
+                self.verify_hmac(&[body], &[hex::decode(signature)?])
+            }
+            WebhookSignatureScheme::Stripe { tolerance } => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for (key, value) in signature.split(',').filter_map(|part| part.trim().split_once('=')) {
+                    match key {
+                        "t" => timestamp = Some(value),
+                        // Stripe sends one v1 entry per active secret while a secret is being rolled.
+                        "v1" => signatures.extend(hex::decode(value).ok()),
+                        _ => {}
+                    }
+                }
+
+                let timestamp = timestamp.ok_or_else(|| anyhow!("Stripe signature doesn't have a timestamp"))?;
+                let signed_at = UNIX_EPOCH + Duration::from_secs(timestamp.parse()?);
+                let age = now.duration_since(signed_at).unwrap_or_else(|err| err.duration());
+                if age > *tolerance {
+                    bail!("Stripe signature timestamp is outside the tolerance");
+                }
+
+                self.verify_hmac(&[timestamp.as_bytes(), b".", body], &signatures)
+            }
+        }
+    }
+
+    fn verify_hmac(&self, message: &[&[u8]], signatures: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        let valid = self.secrets.iter().any(|secret| {
+            signatures.iter().any(|signature| {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+                for part in message {
+                    mac.update(part);
+                }
+                mac.verify_slice(signature).is_ok()
+            })
+        });
+        if !valid {
+            return Err(AuthError::InvalidSignature.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Authenticates webhook deliveries by their signature. The signature covers the raw body, so the body must
+/// be buffered into a [`RequestBody`] extension before authentication runs.
+pub struct WebhookSignatureHandler {
+    pub sources: Vec<WebhookSource>,
+}
+
+impl WebhookSignatureHandler {
+    pub fn new(sources: Vec<WebhookSource>) -> Self {
+        Self { sources }
+    }
+
+    pub fn verify(&self, request: &impl Request, body: &[u8]) -> AuthenticationResult {
+        let now = SystemTime::now();
+        let mut error = None;
+        // Several sources may share a signature format, e.g. two GitHub apps with different secrets.
+        for (source, signature) in self.signed_sources(request) {
+            let verified = signature
+                .to_str()
+                .map_err(|_| anyhow!("Webhook signature is not valid ASCII"))
+                .and_then(|signature| source.verify(signature, body, now));
+            match verified {
+                Ok(()) => {
+                    let source = ClaimValue::PlainValue(ClaimPlainValue::String(source.name.clone().into()));
+                    return Ok(UserPrincipal {
+                        claims: HashMap::from([
+                            (claim_types::SUBJECT.into(), source.clone()),
+                            (webhook_claims::SOURCE.into(), source),
+                        ]),
+                    });
+                }
+                Err(err) => error = Some(err),
+            }
+        }
+
+        Err(error.map_or(AuthenticationError::NoResult, AuthenticationError::fail))
+    }
+
+    fn signed_sources<'a>(
+        &'a self,
+        request: &'a impl Request,
+    ) -> impl Iterator<Item = (&'a WebhookSource, &'a HeaderValue)> {
+        self.sources.iter().filter_map(|source| {
+            let signature = request.get_header(&source.scheme.header())?;
+            Some((source, signature))
+        })
+    }
+}
+
+impl AuthenticationHandler for WebhookSignatureHandler {
+    type AuthFut = Ready<AuthenticationResult>;
+
+    type ChallengeFut = Ready<AuthResponse>;
+
+    type ForbidFut = Ready<AuthResponse>;
+
+    fn authenticate(&self, request: &mut impl Request) -> Self::AuthFut {
+        if self.signed_sources(request).next().is_none() {
+            return ready(Err(AuthenticationError::NoResult));
+        }
+
+        let Some(body) = request.get_extensions().get::<RequestBody>().cloned() else {
+            return ready(Err(AuthenticationError::Fail(AuthError::Other(
+                "Webhook body hasn't been buffered into a RequestBody extension".into(),
+            ))));
+        };
+
+        ready(self.verify(request, &body.0))
+    }
+
+    fn challenge(&self, _: &impl Request) -> Self::ChallengeFut {
+        ready(AuthResponse {
+            status_code: StatusCode::UNAUTHORIZED,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+
+    fn forbid(&self, _: Option<&AuthorizationFailure>) -> Self::ForbidFut {
+        ready(AuthResponse {
+            status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::default(),
+            body: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+    use crate::core::testing::TestRequest;
+
+    // The example of GitHub's documentation on validating webhook deliveries.
+    const GITHUB_SECRET: &str = "It's a Secret to Everybody";
+    const GITHUB_BODY: &[u8] = b"Hello, World!";
+    const GITHUB_SIGNATURE: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    const STRIPE_TIMESTAMP: u64 = 1492774577;
+    const STRIPE_BODY: &[u8] = br#"{"id":"evt_test"}"#;
+    const STRIPE_SIGNATURE: &str = "22f7d74836ddad3284a2b80853dd2fe731655c1bfddcff3c3666a500cf3abd80";
+    const OLD_STRIPE_SIGNATURE: &str = "5ea6a3950bfdf95bcb05ee69f8bf76ffd92759b844cac711c90b9b9053593279";
+
+    fn github_request(signature: &str, body: &[u8]) -> TestRequest {
+        let mut request = TestRequest::new(Method::POST, "/webhooks", &[("x-hub-signature-256", signature)]);
+        request.get_extensions_mut().insert(RequestBody(body.into()));
+        request
+    }
+
+    fn stripe_time(offset: Duration) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(STRIPE_TIMESTAMP) + offset
+    }
+
+    #[tokio::test]
+    async fn github_signature_is_accepted() {
+        let handler = WebhookSignatureHandler::new(vec![WebhookSource::github("github", GITHUB_SECRET)]);
+
+        let principal = handler
+            .authenticate(&mut github_request(GITHUB_SIGNATURE, GITHUB_BODY))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(principal.claim_strs(webhook_claims::SOURCE).next(), Some("github"));
+    }
+
+    #[tokio::test]
+    async fn github_tampered_body_is_rejected() {
+        let handler = WebhookSignatureHandler::new(vec![WebhookSource::github("github", GITHUB_SECRET)]);
+
+        let result = handler
+            .authenticate(&mut github_request(GITHUB_SIGNATURE, b"Hello, World?"))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::Fail(AuthError::InvalidSignature))
+        ));
+    }
+
+    #[tokio::test]
+    async fn github_source_with_matching_secret_is_chosen() {
+        let handler = WebhookSignatureHandler::new(vec![
+            WebhookSource::github("other", "another secret"),
+            WebhookSource::github("github", GITHUB_SECRET),
+        ]);
+
+        let principal = handler
+            .authenticate(&mut github_request(GITHUB_SIGNATURE, GITHUB_BODY))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(principal.claim_strs(webhook_claims::SOURCE).next(), Some("github"));
+    }
+
+    #[test]
+    fn stripe_signature_is_accepted() {
+        let source = WebhookSource::stripe("stripe", "whsec_test_secret");
+        let signature = format!("t={STRIPE_TIMESTAMP},v1={STRIPE_SIGNATURE}");
+
+        source
+            .verify(&signature, STRIPE_BODY, stripe_time(Duration::from_secs(60)))
+            .unwrap();
+    }
+
+    #[test]
+    fn stripe_signature_of_rolled_secret_is_accepted() {
+        let source = WebhookSource::stripe("stripe", "whsec_old_secret");
+        let signature = format!("t={STRIPE_TIMESTAMP},v1={STRIPE_SIGNATURE},v1={OLD_STRIPE_SIGNATURE}");
+
+        source
+            .verify(&signature, STRIPE_BODY, stripe_time(Duration::ZERO))
+            .unwrap();
+    }
+
+    #[test]
+    fn stripe_tampered_timestamp_is_rejected() {
+        let source = WebhookSource::stripe("stripe", "whsec_test_secret");
+        let signature = format!("t={},v1={STRIPE_SIGNATURE}", STRIPE_TIMESTAMP + 1);
+
+        assert!(source
+            .verify(&signature, STRIPE_BODY, stripe_time(Duration::ZERO))
+            .is_err());
+    }
+
+    #[test]
+    fn stripe_signature_outside_tolerance_is_rejected() {
+        let source = WebhookSource::stripe("stripe", "whsec_test_secret");
+        let signature = format!("t={STRIPE_TIMESTAMP},v1={STRIPE_SIGNATURE}");
+
+        assert!(source
+            .verify(&signature, STRIPE_BODY, stripe_time(Duration::from_secs(10 * 60)))
+            .is_err());
+    }
+}